@@ -1,10 +1,22 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use crate::vrsr::TeleplayId;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(version, author, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub mode: Option<Mode>,
+    /// Named profile of the config files to use
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Config file applied over the system, user and project ones
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Override a setting, e.g. `--set timeout=60` or `--set sites.xmb.proxy=http://127.0.0.1:8080`
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub overrides: Vec<String>,
 }
 
 #[derive(Subcommand)]
@@ -18,10 +30,49 @@ pub enum Mode {
         all: bool,
         #[arg(long)]
         nocache: bool,
+        /// Result page to start from
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value = "1")]
+        page: u64,
+        /// Follow the result pages until the last one
+        #[arg(long)]
+        all_pages: bool,
+        /// Max result pages fetched with --all-pages
+        #[arg(long, default_value = "10")]
+        max_pages: usize,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Show the covers inline, for terminals with the iTerm2 image protocol
+        #[arg(long)]
+        preview: bool,
+    },
+    /// Browse the categories, category pages and hot list of a platform
+    Browse {
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+        /// Category id or name, lists the categories when missing
+        #[arg(short = 't', long)]
+        category: Option<String>,
+        #[arg(long, default_value = "time")]
+        sort: Sort,
+        /// Category page to start from
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..), default_value = "1")]
+        page: u64,
+        /// Number of category pages to walk
+        #[arg(long, default_value = "1")]
+        pages: usize,
+        /// Show the hot list of the home page
+        #[arg(long)]
+        hot: bool,
+        #[arg(long)]
+        nocache: bool,
+        /// Show the covers inline, for terminals with the iTerm2 image protocol
+        #[arg(long)]
+        preview: bool,
     },
     /// Download a video from a platform
     Download {
-        id: u64,
+        /// Teleplay id, or the url of its detail page whose host picks the platform
+        id: String,
         #[arg(short, long, default_value = "jugougou")]
         src: Src,
         #[arg(short, long, default_value = "1")]
@@ -32,23 +83,370 @@ pub enum Mode {
         save_dir: Option<String>,
         #[arg(short, long)]
         print: bool,
-        #[arg(short, long, default_value = "32")]
-        climit: usize,
+        /// Segments downloaded at the same time, 32 unless configured
+        #[arg(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        climit: Option<usize>,
+        /// Episode path template, e.g. `{title} ({year})/Season {season}/{title} - S{season:02}E{episode:02}`
+        #[arg(long)]
+        naming: Option<String>,
+        /// Write Kodi/Jellyfin nfo files
+        #[arg(long)]
+        nfo: bool,
+        /// Save the cover and detail page images next to the episodes
+        #[arg(long)]
+        artwork: bool,
+        #[command(flatten)]
+        hls: HlsArgs,
+        #[command(flatten)]
+        browser: BrowserArgs,
+    },
+    /// Download from any url, a search, detail or play page of a platform or a video file
+    Get {
+        url: String,
+        /// File a .m3u8 or .mp4 url is saved to
+        #[arg(short, long, default_value = "output.mp4")]
+        output: String,
+        #[arg(long)]
+        nocache: bool,
+        #[arg(long)]
+        save_dir: Option<String>,
+        /// List the sources and episodes of a teleplay instead of downloading
+        #[arg(short, long)]
+        print: bool,
+        /// Source to download a teleplay from
+        #[arg(short, long, default_value = "1")]
+        index: usize,
+        /// Segments downloaded at the same time, 32 unless configured
+        #[arg(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        climit: Option<usize>,
+        /// Episode path template, e.g. `{title} ({year})/Season {season}/{title} - S{season:02}E{episode:02}`
+        #[arg(long)]
+        naming: Option<String>,
+        /// Write Kodi/Jellyfin nfo files
+        #[arg(long)]
+        nfo: bool,
+        /// Save the cover and detail page images next to the episodes
+        #[arg(long)]
+        artwork: bool,
+        #[command(flatten)]
+        hls: HlsArgs,
+        #[command(flatten)]
+        browser: BrowserArgs,
     },
     /// Convert a video to M3U8 format
     M3U8 {
         url: String,
         #[arg(short, long, default_value = "output.mp4")]
         output: String,
-        #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..), default_value = "32")]
-        climit: usize,
+        /// Segments downloaded at the same time, 32 unless configured
+        #[arg(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        climit: Option<usize>,
+        /// Record a live or event playlist until it ends
+        #[arg(long)]
+        live: bool,
+        /// Stop recording after this many seconds
+        #[arg(short, long, requires = "live")]
+        duration: Option<u64>,
+        #[command(flatten)]
+        hls: HlsArgs,
+        /// Only report the ad segments that would be dropped
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Open a page in the browser and list the video requests it makes
+    Sniff {
+        url: String,
+        /// Url regex marking a video request, replaces the default rules
+        #[arg(long)]
+        url_pattern: Vec<String>,
+        /// Content type regex marking a video response, replaces the default rules
+        #[arg(long)]
+        content_type: Vec<String>,
+        /// Resource type marking a video response, e.g. media or xhr
+        #[arg(long)]
+        resource_type: Vec<String>,
+        /// Css selector of the element starting the player
+        #[arg(long)]
+        click: Option<String>,
+        /// List every candidate instead of the best one
+        #[arg(short, long)]
+        all: bool,
+        #[command(flatten)]
+        browser: BrowserArgs,
+    },
+    /// Manage the teleplays followed by sync
+    Subscribe {
+        #[command(subcommand)]
+        action: SubscribeAction,
+    },
+    /// Download the new episodes of every subscribed teleplay
+    Sync {
+        /// Segments downloaded at the same time, 32 unless configured
+        #[arg(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        climit: Option<usize>,
+        #[command(flatten)]
+        hls: HlsArgs,
+        #[command(flatten)]
+        browser: BrowserArgs,
+        /// Only list the new episodes
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Query the downloaded teleplays and episodes
+    Library {
+        #[command(subcommand)]
+        action: LibraryAction,
+    },
+    /// Manage the domains each platform is served on
+    Mirrors {
+        #[command(subcommand)]
+        action: MirrorAction,
+    },
+    /// Check that the parsers still work: search, fetch a detail page and resolve an episode
+    /// on every site
+    #[command(alias = "check-sites")]
+    Doctor {
+        /// Sites to check, all when missing
+        #[arg(short, long)]
+        src: Vec<Src>,
+        /// Keyword searched on every site
+        #[arg(short, long)]
+        keyword: Option<String>,
+        /// Teleplay whose detail page and first episode are checked instead of the first result
+        #[arg(long)]
+        id: Option<TeleplayId>,
+        /// Print the reports as json
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        browser: BrowserArgs,
+    },
+    /// Post the login form of a platform set in the config, keeping the session cookies
+    Login {
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+    },
+    /// Manage the cookies sent to each platform
+    Cookies {
+        #[command(subcommand)]
+        action: CookieAction,
+    },
+    /// Inspect the layered settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Verify downloaded video files
+    Verify {
+        path: String,
+        #[arg(short, long)]
+        recursive: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SubscribeAction {
+    /// Follow a teleplay
+    Add {
+        id: TeleplayId,
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+        /// Source to download from
+        #[arg(short, long, default_value = "1")]
+        index: usize,
+        #[arg(long)]
+        save_dir: Option<String>,
+        /// Episode path template, `{title}`, `{year}`, `{season}`, `{episode}`, `{name}` and
+        /// `{index}` are replaced
+        #[arg(long)]
+        naming: Option<String>,
+        /// Write Kodi/Jellyfin nfo files
+        #[arg(long)]
+        nfo: bool,
+        /// Save the cover and detail page images next to the episodes
+        #[arg(long)]
+        artwork: bool,
+    },
+    /// Stop following a teleplay
+    Remove {
+        id: TeleplayId,
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+    },
+    /// List the subscriptions
+    List,
+    /// Show the episodes fetched by sync
+    History {
+        #[arg(short, long, default_value = "50")]
+        limit: usize,
     },
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Subcommand)]
+pub enum MirrorAction {
+    /// List the hosts of the platforms and the ones in use
+    List,
+    /// Request every host, switching a platform whose host is down to the first working one
+    Check {
+        /// Platforms to check, all when missing
+        #[arg(short, long)]
+        src: Vec<Src>,
+    },
+    /// Add a mirror host of a platform
+    Add {
+        host: String,
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+    },
+    /// Forget a mirror host
+    Remove {
+        host: String,
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+    },
+    /// Use a host for a platform from now on, moving the saved urls to it
+    Use {
+        host: String,
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CookieAction {
+    /// List the saved cookies without their values
+    List {
+        /// Platforms to list, all when missing
+        #[arg(short, long)]
+        src: Vec<Src>,
+    },
+    /// Import a Netscape cookies.txt exported by a browser, keeping the cookies of the platforms
+    Import {
+        file: PathBuf,
+        /// Platforms to import for, all when missing
+        #[arg(short, long)]
+        src: Vec<Src>,
+    },
+    /// Forget the cookies of a platform
+    Clear {
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Print the effective settings and where each one comes from
+    Show {
+        /// Print the settings as json
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LibraryAction {
+    /// List the downloaded teleplays
+    List,
+    /// Show a teleplay and its episode files
+    Show {
+        id: TeleplayId,
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+    },
+    /// Find teleplays by title, actor, director or genre
+    Search { keyword: String },
+    /// Forget a teleplay
+    Remove {
+        id: TeleplayId,
+        #[arg(short, long, default_value = "jugougou")]
+        src: Src,
+        /// Delete the episode files too
+        #[arg(long)]
+        delete_files: bool,
+    },
+}
+
+#[derive(Args, Clone)]
+pub struct HlsArgs {
+    /// Preferred audio languages, e.g. zh,en
+    #[arg(long, value_delimiter = ',')]
+    pub lang: Vec<String>,
+    /// Do not download subtitle renditions
+    #[arg(long)]
+    pub nosub: bool,
+    /// Drop ad segments spliced into the playlist
+    #[arg(long)]
+    pub skip_ad: bool,
+    /// Extra url pattern marking ad segments
+    #[arg(long)]
+    pub ad_pattern: Vec<String>,
+}
+
+#[derive(Args, Clone)]
+pub struct FilterArgs {
+    /// Only show results of this year
+    #[arg(long)]
+    pub year: Option<String>,
+    /// Only show results of this region, e.g. 大陆
+    #[arg(long)]
+    pub region: Option<String>,
+    /// Only show results of this genre, e.g. 悬疑
+    #[arg(long)]
+    pub genre: Option<String>,
+    /// Only show completed or updating results
+    #[arg(long)]
+    pub status: Option<Status>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Status {
+    Completed,
+    Updating,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Sort {
+    /// Latest update
+    Time,
+    /// Popularity
+    Hits,
+    Score,
+}
+
+#[derive(Args, Clone)]
+pub struct BrowserArgs {
+    /// Run the browser used to resolve episodes without a window
+    #[arg(long)]
+    pub headless: bool,
+    /// Max tabs resolving episodes at the same time
+    #[arg(long, default_value = "2")]
+    pub max_tabs: usize,
+    /// Seconds to wait for a browser to resolve an episode
+    #[arg(long, default_value = "60")]
+    pub browser_timeout: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
 pub enum Src {
     ZBKYYY,
     IJUJITV,
     JUGOUGOU,
     XMB,
 }
+
+#[test]
+fn test_parse_climit() {
+    let url = "https://a.com/index.m3u8";
+    let cli = Cli::try_parse_from(["vspider-rs", "m3u8", url, "-c", "4"]).unwrap();
+    let Some(Mode::M3U8 { climit, .. }) = cli.mode else {
+        panic!("not the m3u8 command");
+    };
+    assert_eq!(climit, Some(4));
+    let cli = Cli::try_parse_from(["vspider-rs", "sync", "-c", "8"]).unwrap();
+    let Some(Mode::Sync { climit, .. }) = cli.mode else {
+        panic!("not the sync command");
+    };
+    assert_eq!(climit, Some(8));
+    assert!(Cli::try_parse_from(["vspider-rs", "m3u8", url, "-c", "0"]).is_err());
+}
@@ -0,0 +1,110 @@
+use crate::vrsr::{Request, TeleplayInfo};
+use base64::Engine;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+const ARTWORK_CACHE_TIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Images saved for a teleplay, as file names relative to its directory.
+#[derive(Debug, Default)]
+pub struct Artwork {
+    pub poster: Option<String>,
+    pub fanart: Vec<String>,
+}
+
+fn extension(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" | "jpeg" | "png" | "webp" => extension,
+        _ => "jpg".to_string(),
+    }
+}
+
+async fn save(requestor: &Arc<impl Request>, url: &str, path: &Path) -> bool {
+    if path.exists() {
+        return true;
+    }
+    let result = match requestor
+        .request_bytes_with_cache(url, ARTWORK_CACHE_TIME)
+        .await
+    {
+        Ok(image) => path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, image))
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result.as_ref() {
+        println!("download artwork {} error: {}", url, e);
+    }
+    result.is_ok()
+}
+
+/// Save the cover as `poster` and the detail page images as `fanart` and `extrafanart/fanartN`,
+/// the names Kodi and Jellyfin pick up. Failed images are left out.
+pub async fn download_artwork(
+    requestor: &Arc<impl Request>,
+    info: &TeleplayInfo,
+    dir: &Path,
+) -> Artwork {
+    let mut artwork = Artwork::default();
+    if let Some(cover) = info.cover.as_ref() {
+        let name = format!("poster.{}", extension(cover));
+        if save(requestor, cover, &dir.join(&name)).await {
+            artwork.poster.replace(name);
+        }
+    }
+    for (index, image) in info.artwork.iter().enumerate() {
+        let name = if index == 0 {
+            format!("fanart.{}", extension(image))
+        } else {
+            format!("extrafanart/fanart{}.{}", index, extension(image))
+        };
+        if save(requestor, image, &dir.join(&name)).await {
+            artwork.fanart.push(name);
+        }
+    }
+    artwork
+}
+
+/// Escape sequence showing an image inline with the iTerm2 image protocol, also understood by
+/// WezTerm, Konsole and mintty. `width` is in terminal cells.
+pub fn preview(image: &[u8], width: usize) -> String {
+    format!(
+        "\x1b]1337;File=inline=1;size={};width={};preserveAspectRatio=1:{}\x07",
+        image.len(),
+        width,
+        base64::engine::general_purpose::STANDARD.encode(image)
+    )
+}
+
+/// Print the cover of a teleplay above its details, nothing when it can not be fetched.
+pub async fn print_preview(requestor: &Arc<impl Request>, info: &TeleplayInfo, width: usize) {
+    let Some(cover) = info.cover.as_ref() else {
+        return;
+    };
+    match requestor
+        .request_bytes_with_cache(cover, ARTWORK_CACHE_TIME)
+        .await
+    {
+        Ok(image) => println!("{}", preview(&image, width)),
+        Err(e) => log::warn!("preview {} error: {}", cover, e),
+    }
+}
+
+#[test]
+fn test_artwork_names() {
+    assert_eq!(extension("https://a.com/upload/1.png?t=2"), "png");
+    assert_eq!(extension("https://a.com/upload/1.PNG"), "png");
+    assert_eq!(extension("https://a.com/cover.php?id=1"), "jpg");
+    assert_eq!(
+        preview(b"abc", 20),
+        "\x1b]1337;File=inline=1;size=3;width=20;preserveAspectRatio=1:YWJj\x07"
+    );
+}
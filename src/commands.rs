@@ -1,23 +1,65 @@
-use crate::args::Src;
-use crate::downloader::{DownloadError, M3U8DownloadBuilder, MP4DownloadBuilder};
+use crate::args::{Cli, ConfigAction, CookieAction, FilterArgs, HlsArgs, LibraryAction, MirrorAction, Sort, Src, Status, SubscribeAction};
+use crate::artwork::{download_artwork, print_preview, Artwork};
+use crate::config::{self, Config, ConfigError, ConfigSources, ParseApiSettings, SiteSettings};
+use crate::doctor::{check_site, SiteReport, DEFAULT_KEYWORD};
+use crate::library::{EpisodeRecord, Library, LIBRARY_FILE};
+use crate::mirror::MirrorStore;
+use crate::naming::{default_template, episode_nfo, show_dir, tvshow_nfo, NamingContext};
+use crate::subscribe::{now, HistoryEntry, Subscription, SubscriptionStore, DATA_DIR};
+use crate::downloader::{verify_file, ClientOptions, DownloadError};
+use crate::downloader::{M3U8DownloadBuilder, MP4DownloadBuilder};
+use crate::vrsr::cookie::{parse_netscape, CookieJar, COOKIES_DIR};
 use crate::vrsr::error::Error as VRSRError;
+use crate::vrsr::mirror;
+use crate::vrsr::parseapi::{self, Extractor, ParseApi, ParseChain};
 use crate::vrsr::GeneralResource;
+use crate::vrsr::{BrowserPool, SearchFilter, SniffRule, Sniffer, TeleplayStatus};
+use crate::vrsr::{Category, SortOrder};
 use crate::vrsr::IJUJITVParser;
 use crate::vrsr::JUGOUGOUParser;
 use crate::vrsr::ZBKYYYParser;
 use crate::vrsr::XMBParser;
+use crate::vrsr::request::Requestor;
 use crate::vrsr::{
-    create_resource, create_teleplay, Episode, GeneralTeleplay, RequestorBuilder, Resource,
-    Teleplay, URIType,
+    create_resource, create_teleplay, parse_id, Episode, GeneralTeleplay, RequestorBuilder,
+    Page, Resource, ResourceInfo, Teleplay, TeleplayId, TeleplayInfo, URIType, Uri,
 };
 use crate::vrsr::{EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
+use clap::ValueEnum;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use url::Url;
+
+pub struct SearchOptions {
+    pub page: usize,
+    pub pages: usize,
+    pub filter: SearchFilter,
+    pub preview: bool,
+}
+
+impl From<FilterArgs> for SearchFilter {
+    fn from(args: FilterArgs) -> Self {
+        SearchFilter {
+            year: args.year,
+            region: args.region,
+            genre: args.genre,
+            status: args.status.map(|status| match status {
+                Status::Completed => TeleplayStatus::Completed,
+                Status::Updating => TeleplayStatus::Updating,
+            }),
+        }
+    }
+}
 
 async fn search_resource<'a, R, P>(
     mut resource: GeneralResource<'a, R, P>,
+    requestor: &Arc<R>,
     arg_value: &str,
     keyword: &str,
+    options: &SearchOptions,
 ) -> Result<(), VRSRError>
 where
     R: Request,
@@ -26,12 +68,32 @@ where
     println!("===========================");
     println!("{} [{}]", arg_value, resource.name().to_string());
     println!("===========================");
-    let teleplays = resource.search(keyword).await?;
+    let teleplays = resource
+        .search_pages(keyword, options.page, options.pages)
+        .await?;
+    let mut matched = 0;
     for teleplay in teleplays.iter() {
-        let teleplay_locked = teleplay.lock().await;
+        let mut teleplay_locked = teleplay.lock().await;
+        if options.filter.check(teleplay_locked.info()).is_none() {
+            // the search page lacks a filtered field, the detail page has it
+            if let Err(e) = teleplay_locked.request().await {
+                println!("skip {}, request detail error: {}", teleplay_locked.info().title, e);
+                continue;
+            }
+        }
+        if !options.filter.accepts(teleplay_locked.info()) {
+            continue;
+        }
+        matched += 1;
+        if options.preview {
+            print_preview(requestor, teleplay_locked.info(), 20).await;
+        }
         println!("{}", teleplay_locked.info());
         println!("---------------------------");
     }
+    if !options.filter.is_empty() {
+        println!("{}/{} results match the filters", matched, teleplays.len());
+    }
     Ok(())
 }
 
@@ -41,98 +103,328 @@ pub enum CommandError {
     ParserResourceError(#[from] VRSRError),
     #[error("M3U8 download error: {0:?}")]
     M3U8DownloadError(#[from] DownloadError),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Library error: {0}")]
+    LibraryError(#[from] rusqlite::Error),
+    #[error("Verify failed: {0} files")]
+    VerifyFailed(usize),
+    #[error("Site check failed: {0} sites")]
+    CheckFailed(usize),
+    #[error("Download failed: {0} episodes")]
+    DownloadFailed(usize),
+    #[error("Sync failed: {0} subscriptions")]
+    SyncFailed(usize),
+    #[error("No site serves {0}")]
+    UnknownSite(String),
+    #[error("Not a search, detail or play page: {0}")]
+    UnknownPage(String),
+    #[error("Config error: {0}")]
+    ConfigError(#[from] ConfigError),
+    #[error("No login form configured for {0}, set sites.{0}.login")]
+    NoLogin(String),
+    #[error("Login to {0} failed")]
+    LoginFailed(String),
 }
 
-pub async fn search(keyword: &str, src: Src, all: bool, nocache: bool) -> Result<(), CommandError> {
-    let requestor = RequestorBuilder::new().ignore_cache(nocache).build();
-    if all {
-        search_resource(
-            create_resource(requestor.clone(), ZBKYYYParser::new()),
-            "zbkyyy",
-            keyword,
-        )
-        .await?;
-        search_resource(
-            create_resource(requestor.clone(), IJUJITVParser::new()),
-            "ijujitv",
-            keyword,
-        )
-        .await?;
-        search_resource(
-            create_resource(requestor.clone(), XMBParser::new()),
-            "xmb",
-            keyword,
-        )
-        .await?;
-        search_resource(
-            create_resource(requestor.clone(), JUGOUGOUParser::new()),
-            "jugougou",
-            keyword,
-        )
-        .await?;
+pub async fn search(
+    keyword: &str,
+    src: Src,
+    all: bool,
+    nocache: bool,
+    options: &SearchOptions,
+) -> Result<(), CommandError> {
+    let srcs = if all {
+        vec![Src::ZBKYYY, Src::IJUJITV, Src::XMB, Src::JUGOUGOU]
     } else {
-        match src {
-            Src::ZBKYYY => {
-                search_resource(
-                    create_resource(requestor.clone(), ZBKYYYParser::new()),
-                    "zbkyyy",
-                    keyword,
-                )
-                .await?
-            }
-            Src::IJUJITV => {
-                search_resource(
-                    create_resource(requestor.clone(), IJUJITVParser::new()),
-                    "ijujitv",
-                    keyword,
-                )
-                .await?
-            }
-            Src::JUGOUGOU => {
-                search_resource(
-                    create_resource(requestor.clone(), JUGOUGOUParser::new()),
-                    "jugougou",
-                    keyword,
-                )
-                .await?
-            }
-            Src::XMB => {
-                search_resource(
-                    create_resource(requestor.clone(), XMBParser::new()),
-                    "xmb",
-                    keyword,
-                )
-                .await?
+        vec![src]
+    };
+    for src in srcs {
+        search_src(src, keyword, nocache, options).await?;
+    }
+    Ok(())
+}
+
+async fn search_src(
+    src: Src,
+    keyword: &str,
+    nocache: bool,
+    options: &SearchOptions,
+) -> Result<(), CommandError> {
+    let requestor = site_requestor(src, nocache)?;
+    match src {
+        Src::ZBKYYY => {
+            search_resource(
+                create_resource(requestor.clone(), ZBKYYYParser::new()),
+                &requestor,
+                "zbkyyy",
+                keyword,
+                options,
+            )
+            .await?
+        }
+        Src::IJUJITV => {
+            search_resource(
+                create_resource(requestor.clone(), IJUJITVParser::new()),
+                &requestor,
+                "ijujitv",
+                keyword,
+                options,
+            )
+            .await?
+        }
+        Src::JUGOUGOU => {
+            search_resource(
+                create_resource(requestor.clone(), JUGOUGOUParser::new()),
+                &requestor,
+                "jugougou",
+                keyword,
+                options,
+            )
+            .await?
+        }
+        Src::XMB => {
+            search_resource(
+                create_resource(requestor.clone(), XMBParser::new()),
+                &requestor,
+                "xmb",
+                keyword,
+                options,
+            )
+            .await?
+        }
+    }
+    Ok(())
+}
+
+pub struct BrowseOptions {
+    pub category: Option<String>,
+    pub sort: SortOrder,
+    pub page: usize,
+    pub pages: usize,
+    pub hot: bool,
+    pub preview: bool,
+}
+
+impl From<Sort> for SortOrder {
+    fn from(sort: Sort) -> Self {
+        match sort {
+            Sort::Time => SortOrder::Time,
+            Sort::Hits => SortOrder::Hits,
+            Sort::Score => SortOrder::Score,
+        }
+    }
+}
+
+async fn browse_resource<'a, R, P>(
+    mut resource: GeneralResource<'a, R, P>,
+    requestor: &Arc<R>,
+    arg_value: &str,
+    options: &BrowseOptions,
+) -> Result<(), VRSRError>
+where
+    R: Request,
+    P: GenerateInfo + ResourceParse + TeleplayParse + EpisodeParse,
+{
+    println!("===========================");
+    println!("{} [{}]", arg_value, resource.name());
+    println!("===========================");
+    let teleplays = if options.hot {
+        resource.hot().await?
+    } else {
+        let categories = resource.categories().await?;
+        let Some(category) = options.category.as_ref() else {
+            for category in categories.iter() {
+                println!("{}", category);
             }
+            return Ok(());
+        };
+        let category = categories
+            .iter()
+            .find(|c| &c.id == category)
+            .or_else(|| categories.iter().find(|c| c.name.contains(category.as_str())))
+            .cloned()
+            .unwrap_or_else(|| Category {
+                id: category.clone(),
+                name: category.clone(),
+            });
+        resource
+            .browse(&category, options.sort, options.page, options.pages)
+            .await?
+    };
+    for teleplay in teleplays.iter() {
+        let teleplay_locked = teleplay.lock().await;
+        if options.preview {
+            print_preview(requestor, teleplay_locked.info(), 20).await;
+        }
+        println!("{}", teleplay_locked.info());
+        println!("---------------------------");
+    }
+    Ok(())
+}
+
+pub async fn browse(src: Src, nocache: bool, options: &BrowseOptions) -> Result<(), CommandError> {
+    let requestor = site_requestor(src, nocache)?;
+    match src {
+        Src::ZBKYYY => {
+            browse_resource(
+                create_resource(requestor.clone(), ZBKYYYParser::new()),
+                &requestor,
+                "zbkyyy",
+                options,
+            )
+            .await?
+        }
+        Src::IJUJITV => {
+            browse_resource(
+                create_resource(requestor.clone(), IJUJITVParser::new()),
+                &requestor,
+                "ijujitv",
+                options,
+            )
+            .await?
+        }
+        Src::JUGOUGOU => {
+            browse_resource(
+                create_resource(requestor.clone(), JUGOUGOUParser::new()),
+                &requestor,
+                "jugougou",
+                options,
+            )
+            .await?
+        }
+        Src::XMB => {
+            browse_resource(
+                create_resource(requestor.clone(), XMBParser::new()),
+                &requestor,
+                "xmb",
+                options,
+            )
+            .await?
         }
     }
     Ok(())
 }
 
+pub struct DownloadOptions {
+    pub index: usize,
+    pub save_dir: Option<String>,
+    pub print: bool,
+    pub climit: usize,
+    pub hls: HlsArgs,
+    /// Path template of an episode below the save directory, see [`NamingContext::render`].
+    pub naming: String,
+    /// Write Kodi/Jellyfin `.nfo` files next to the episodes.
+    pub nfo: bool,
+    /// Save the cover and detail page images next to the episodes.
+    pub artwork: bool,
+    /// Urls of episodes fetched before, left out of the download.
+    pub skip: Vec<String>,
+    /// Only list the episodes that would be downloaded.
+    pub dry_run: bool,
+    /// Play page url of the one episode to download, its source replaces `index`.
+    pub episode: Option<String>,
+}
+
+/// An episode present in the save directory after a download.
+pub struct Fetched {
+    pub name: String,
+    pub url: String,
+    /// Name of the play source the episode was taken from.
+    pub source: Option<String>,
+    /// Resolved video uri, only known for episodes downloaded now.
+    pub uri: Option<Uri>,
+    pub file: String,
+    /// Downloaded now rather than found from an earlier run.
+    pub new: bool,
+}
+
+/// An episode whose uri could not be resolved or whose download failed.
+pub struct Failed {
+    pub name: String,
+    pub url: String,
+    pub error: String,
+}
+
+impl std::fmt::Display for Failed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[FAIL] {} {}\n       {}", self.name, self.url, self.error)
+    }
+}
+
+pub struct FetchResult {
+    pub info: TeleplayInfo,
+    /// Path of the saved cover.
+    pub poster: Option<String>,
+    pub title: String,
+    pub fetched: Vec<Fetched>,
+    pub failed: Vec<Failed>,
+}
+
+/// The user agent, headers and proxy of the page requests, sent with the downloads too.
+fn download_client(site: &SiteSettings) -> Result<ClientOptions, CommandError> {
+    let mut client = ClientOptions::new();
+    if let Some(user_agent) = site.user_agent.as_ref() {
+        client.header("User-Agent", user_agent);
+    }
+    for (name, value) in site.headers.iter() {
+        client.header(name, value);
+    }
+    if let Some(proxy) = site.proxy.as_ref() {
+        client.proxy(proxy)?;
+    }
+    Ok(client)
+}
+
+fn configure_hls<'a>(
+    builder: &'a mut M3U8DownloadBuilder,
+    hls: &HlsArgs,
+    site_ad_patterns: &[String],
+    client: &ClientOptions,
+) -> &'a mut M3U8DownloadBuilder {
+    let mut ad_patterns = site_ad_patterns.to_vec();
+    ad_patterns.extend(hls.ad_pattern.iter().cloned());
+    builder
+        .client(client.clone())
+        .timeout(config::settings().download_timeout)
+        .try_count(config::settings().download_retries)
+        .languages(hls.lang.clone())
+        .subtitles(!hls.nosub)
+        .filter_ads(hls.skip_ad)
+        .ad_patterns(ad_patterns)
+}
+
+/// Whether two urls are the same page, whichever mirror host they are on.
+fn same_page(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.path() == b.path(),
+        _ => a == b,
+    }
+}
+
 async fn dwonload_teleplay<'a, R, P>(
     mut teleplay: GeneralTeleplay<R, P>,
-    index: usize,
-    save_dir: &Option<String>,
-    print: bool,
-    climit: usize,
-) -> Result<(), VRSRError>
+    requestor: &Arc<R>,
+    site_ad_patterns: &[String],
+    client: &ClientOptions,
+    options: &DownloadOptions,
+) -> Result<FetchResult, VRSRError>
 where
     R: Request,
     P: TeleplayParse + EpisodeParse,
 {
     teleplay.request().await?;
     println!("{}", teleplay.info());
-    let save_path = if let Some(save_dir) = save_dir {
-        std::path::Path::new(save_dir)
-    } else {
-        std::path::Path::new(teleplay.title())
-    };
-    if !save_path.exists() {
-        std::fs::create_dir_all(save_path)?;
-    }
+    let save_path = std::path::Path::new(options.save_dir.as_deref().unwrap_or("."));
+    let title = teleplay.title().to_string();
+    let info = teleplay.info().clone();
+    let mut metadata_written = false;
+    let mut poster = None;
+    let mut fetched = Vec::new();
+    let mut failed = Vec::new();
     let teleplay_src = teleplay.episodes();
 
-    if print {
+    if options.print {
         for (index, result) in teleplay_src.iter().enumerate() {
             println!(
                 "{} -> {}",
@@ -146,7 +438,18 @@ where
             println!();
         }
     } else {
-        if let Some(result) = teleplay_src.get(index - 1) {
+        let mut source_index = options.index.checked_sub(1);
+        if let Some(url) = options.episode.as_ref() {
+            source_index = None;
+            for (index, (_, episodes)) in teleplay_src.iter().enumerate() {
+                for episode in episodes.iter() {
+                    if same_page(url, episode.lock().await.url()) {
+                        source_index.get_or_insert(index);
+                    }
+                }
+            }
+        }
+        if let Some(result) = source_index.and_then(|index| teleplay_src.get(index)) {
             let pbars = MultiProgress::new();
             let m3u8_style = ProgressStyle::with_template(
                 "[{prefix}][{elapsed_precise}] {bar:100.cyan/blue} {pos:>4}/{len:4} {msg}",
@@ -169,8 +472,41 @@ where
             let episode_count = result.1.len();
             for (index, episode) in result.1.iter().enumerate() {
                 let mut episode_locked = episode.lock().await;
-                let save_file_path = save_path.join(format!("{}.mp4", episode_locked.name()));
+                if options.skip.iter().any(|url| same_page(url, episode_locked.url())) {
+                    continue;
+                }
+                if let Some(url) = options.episode.as_ref() {
+                    if !same_page(url, episode_locked.url()) {
+                        continue;
+                    }
+                }
+                let context = NamingContext::new(&info, episode_locked.name(), index + 1);
+                let relative = context.render(&options.naming);
+                let save_file_path = save_path.join(&relative);
                 let save_file = save_file_path.to_string_lossy().to_string();
+                let mut record = Fetched {
+                    name: episode_locked.name().to_string(),
+                    url: episode_locked.url().to_string(),
+                    source: result.0.clone(),
+                    uri: None,
+                    file: save_file.clone(),
+                    new: false,
+                };
+                if options.dry_run {
+                    if !save_file_path.exists() {
+                        println!("new episode: {} -> {}", record.name, save_file);
+                    }
+                    continue;
+                }
+
+                if let Some(parent) = save_file_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                if (options.nfo || options.artwork) && !metadata_written {
+                    let dir = show_dir(save_path, &relative);
+                    poster = write_show_metadata(requestor, &info, &dir, options).await;
+                    metadata_written = true;
+                }
 
                 let pbar = pbars.add(ProgressBar::hidden());
                 pbar.set_style(parse_style.clone());
@@ -182,116 +518,1031 @@ where
                     pbar.set_length(100);
                     pbar.set_position(100);
                     pbar.finish();
+                    if options.nfo {
+                        write_episode_nfo(&save_file_path, &context)?;
+                    }
+                    fetched.push(record);
                     continue;
                 }
-                let uri = episode_locked.request().await?;
+                // one broken episode does not stop the others
+                let uri = match episode_locked.request().await {
+                    Ok(uri) => uri,
+                    Err(e) => {
+                        pbar.abandon_with_message(format!("{} {}", save_file, e));
+                        failed.push(Failed {
+                            name: record.name,
+                            url: record.url,
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                record.new = true;
+                record.uri.replace(uri.clone());
 
-                match uri.utype {
+                let result = match uri.utype {
                     URIType::M3U8 => {
                         pbar.set_style(m3u8_style.clone());
-                        let mut downloader = m3u8_builder
-                            .uri(uri.uri)
-                            .pbar(pbar)
-                            .timeout(3)
-                            .climit(climit)
-                            .save_file(&save_file)
-                            .build();
-                        downloader.download().await.unwrap();
+                        let mut downloader =
+                            configure_hls(&mut m3u8_builder, &options.hls, site_ad_patterns, client)
+                                .uri(uri.uri)
+                                .pbar(pbar.clone())
+                                .climit(options.climit)
+                                .save_file(&save_file)
+                                .build();
+                        downloader.download().await
                     }
                     URIType::MP4 => {
                         pbar.set_style(mp4_style.clone());
                         let mut downloader = mp4_builder
                             .uri(uri.uri)
-                            .pbar(pbar)
-                            .timeout(3)
+                            .pbar(pbar.clone())
+                            .timeout(config::settings().download_timeout)
+                            .client(client.clone())
                             .save_file(&save_file)
                             .build();
-                        downloader.download().await.unwrap();
+                        downloader.download().await
                     }
-                    _ => {
-                        println!("Unsupported URI type");
+                    utype => {
+                        let error = format!("Unsupported URI type {:?}", utype);
+                        pbar.abandon_with_message(format!("{} {}", save_file, error));
+                        failed.push(Failed {
+                            name: record.name,
+                            url: record.url,
+                            error,
+                        });
+                        continue;
                     }
+                };
+                if let Err(e) = result {
+                    pbar.abandon_with_message(format!("{} {}", save_file, e));
+                    failed.push(Failed {
+                        name: record.name,
+                        url: record.url,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+                // only a finished download gets its nfo, a failed one is retried next time
+                if options.nfo {
+                    write_episode_nfo(&save_file_path, &context)?;
                 }
+                fetched.push(record);
             }
         } else {
             println!("No such episode");
         }
     }
 
+    if !failed.is_empty() {
+        println!("{} episodes failed:", failed.len());
+        for failure in failed.iter() {
+            println!("{}", failure);
+        }
+    }
+    Ok(FetchResult {
+        info,
+        poster,
+        title,
+        fetched,
+        failed,
+    })
+}
+
+/// The `.nfo` next to an episode file, kept when it exists already.
+fn write_episode_nfo(save_file: &std::path::Path, context: &NamingContext) -> std::io::Result<()> {
+    let nfo_path = save_file.with_extension("nfo");
+    if !nfo_path.exists() {
+        std::fs::write(nfo_path, episode_nfo(context))?;
+    }
     Ok(())
 }
 
-pub async fn download(
-    id: u64,
-    src: Src,
-    index: usize,
-    nocache: bool,
-    save_dir: &Option<String>,
-    print: bool,
-    climit: usize,
-) -> Result<(), CommandError> {
-    let requestor = RequestorBuilder::new().ignore_cache(nocache).build();
+/// Artwork and `tvshow.nfo` of a teleplay, failures only leave them out. Returns the poster path.
+async fn write_show_metadata<R: Request>(
+    requestor: &Arc<R>,
+    info: &TeleplayInfo,
+    dir: &std::path::Path,
+    options: &DownloadOptions,
+) -> Option<String> {
+    let artwork = if options.artwork {
+        download_artwork(requestor, info, dir).await
+    } else {
+        Artwork::default()
+    };
+    if options.nfo {
+        if let Err(e) = std::fs::write(dir.join("tvshow.nfo"), tvshow_nfo(info, &artwork)) {
+            println!("write tvshow.nfo error: {}", e);
+        }
+    }
+    artwork
+        .poster
+        .map(|poster| dir.join(poster).to_string_lossy().to_string())
+}
 
-    match src {
+async fn fetch(
+    id: &TeleplayId,
+    src: Src,
+    requestor: Arc<Requestor>,
+    options: &DownloadOptions,
+) -> Result<FetchResult, CommandError> {
+    let client = download_client(&config::settings().site(src))?;
+    let result = match src {
         Src::ZBKYYY => {
+            let parser = ZBKYYYParser::new();
             dwonload_teleplay(
-                create_teleplay(requestor, ZBKYYYParser::new(), id),
-                index,
-                save_dir,
-                print,
-                climit,
+                create_teleplay(requestor.clone(), parser.clone(), id),
+                &requestor,
+                parser.generate_resource_info().ad_patterns(),
+                &client,
+                options,
             )
             .await?
         }
         Src::IJUJITV => {
+            let parser = IJUJITVParser::new();
             dwonload_teleplay(
-                create_teleplay(requestor, IJUJITVParser::new(), id),
-                index,
-                save_dir,
-                print,
-                climit,
+                create_teleplay(requestor.clone(), parser.clone(), id),
+                &requestor,
+                parser.generate_resource_info().ad_patterns(),
+                &client,
+                options,
             )
             .await?
         }
         Src::JUGOUGOU => {
+            let parser = JUGOUGOUParser::new();
             dwonload_teleplay(
-                create_teleplay(requestor, JUGOUGOUParser::new(), id),
-                index,
-                save_dir,
-                print,
-                climit,
+                create_teleplay(requestor.clone(), parser.clone(), id),
+                &requestor,
+                parser.generate_resource_info().ad_patterns(),
+                &client,
+                options,
             )
             .await?
         }
         Src::XMB => {
+            let parser = XMBParser::new();
             dwonload_teleplay(
-                create_teleplay(requestor, XMBParser::new(), id),
-                index,
-                save_dir,
-                print,
-                climit,
+                create_teleplay(requestor.clone(), parser.clone(), id),
+                &requestor,
+                parser.generate_resource_info().ad_patterns(),
+                &client,
+                options,
             )
             .await?
         }
     };
+    Ok(result)
+}
+
+/// Resource info of every site, the registry urls are matched against.
+fn resource_info(src: Src) -> ResourceInfo {
+    match src {
+        Src::ZBKYYY => ZBKYYYParser::new().generate_resource_info(),
+        Src::IJUJITV => IJUJITVParser::new().generate_resource_info(),
+        Src::JUGOUGOU => JUGOUGOUParser::new().generate_resource_info(),
+        Src::XMB => XMBParser::new().generate_resource_info(),
+    }
+}
+
+/// The built-in host of `src`, known without building its parser.
+fn default_host(src: Src) -> &'static str {
+    match src {
+        Src::ZBKYYY => ZBKYYYParser::HOST,
+        Src::IJUJITV => IJUJITVParser::HOST,
+        Src::JUGOUGOU => JUGOUGOUParser::HOST,
+        Src::XMB => XMBParser::HOST,
+    }
+}
+
+/// The site serving `url`.
+pub fn src_of_url(url: &Url) -> Option<Src> {
+    Src::value_variants()
+        .iter()
+        .copied()
+        .find(|src| resource_info(*src).owns(url))
+}
+
+/// The teleplay `id` names, an id on `src` or the detail page url of any site.
+fn teleplay_of(id: &str, src: Src) -> Result<(TeleplayId, Src), CommandError> {
+    let Ok(url) = Url::parse(id) else {
+        return Ok((TeleplayId::from(id), src));
+    };
+    let Some(src) = src_of_url(&url) else {
+        return Err(CommandError::UnknownSite(url.host_str().unwrap_or(id).to_string()));
+    };
+    match resource_info(src).page(&url) {
+        Some(Page::Detail(id)) | Some(Page::Play(id)) => Ok((id, src)),
+        _ => Ok((parse_id(url.as_str())?, src)),
+    }
+}
+
+pub async fn download(
+    id: &str,
+    src: Src,
+    nocache: bool,
+    options: &DownloadOptions,
+) -> Result<(), CommandError> {
+    let (id, src) = teleplay_of(id, src)?;
+    download_teleplay(&id, src, nocache, options).await
+}
+
+async fn download_teleplay(
+    id: &TeleplayId,
+    src: Src,
+    nocache: bool,
+    options: &DownloadOptions,
+) -> Result<(), CommandError> {
+    let requestor = site_requestor(src, nocache)?;
+    let result = fetch(id, src, requestor, options).await?;
+    if !options.dry_run {
+        record_library(src, &result)?;
+    }
+    if !result.failed.is_empty() {
+        return Err(CommandError::DownloadFailed(result.failed.len()));
+    }
+    Ok(())
+}
+
+fn open_library() -> Result<Library, CommandError> {
+    std::fs::create_dir_all(DATA_DIR)?;
+    Ok(Library::open(std::path::Path::new(DATA_DIR).join(LIBRARY_FILE))?)
+}
+
+/// Record the teleplay and its episode files in the library, with size and duration read from
+/// the files.
+fn record_library(src: Src, result: &FetchResult) -> Result<(), CommandError> {
+    if result.fetched.is_empty() {
+        return Ok(());
+    }
+    let library = open_library()?;
+    library.add_teleplay(src, &result.info)?;
+    if let Some(poster) = result.poster.as_ref() {
+        library.set_poster(src, &result.info.id, poster)?;
+    }
+    for fetched in result.fetched.iter() {
+        let (size, duration) = match verify_file(&fetched.file, None) {
+            Ok(report) => (report.size, report.duration),
+            Err(_) => (0, None),
+        };
+        library.add_episode(&EpisodeRecord {
+            src,
+            teleplay_id: result.info.id.clone(),
+            source: fetched.source.clone(),
+            name: fetched.name.clone(),
+            url: fetched.url.clone(),
+            uri: fetched.uri.as_ref().map(|uri| uri.uri.clone()),
+            uri_type: fetched.uri.as_ref().map(|uri| format!("{:?}", uri.utype)),
+            file: fetched.file.clone(),
+            size,
+            duration,
+            downloaded: now(),
+        })?;
+    }
     Ok(())
 }
 
-pub async fn m3u8_download(url: &str, output: &str, climit: usize) -> Result<(), CommandError> {
+pub fn library(action: LibraryAction) -> Result<(), CommandError> {
+    let library = open_library()?;
+    match action {
+        LibraryAction::List => {
+            for record in library.teleplays()? {
+                println!("{}", record);
+            }
+        }
+        LibraryAction::Show { id, src } => {
+            let Some(record) = library.teleplay(src, &id)? else {
+                println!("not in library {:?} {}", src, id);
+                return Ok(());
+            };
+            println!("{}", record.info);
+            if let Some(poster) = record.poster.as_ref() {
+                println!("|封面:{}", poster);
+            }
+            println!("---------------------------");
+            for episode in library.episodes(src, &id)? {
+                println!("{}", episode);
+            }
+            println!("{}", record);
+        }
+        LibraryAction::Search { keyword } => {
+            for record in library.search(&keyword)? {
+                println!("{}", record);
+            }
+        }
+        LibraryAction::Remove {
+            id,
+            src,
+            delete_files,
+        } => {
+            let Some(episodes) = library.remove(src, &id)? else {
+                println!("not in library {:?} {}", src, id);
+                return Ok(());
+            };
+            if delete_files {
+                for episode in episodes.iter() {
+                    if let Err(e) = std::fs::remove_file(&episode.file) {
+                        println!("delete {} error: {}", episode.file, e);
+                    }
+                }
+            }
+            println!("removed {:?} {}, {} episodes", src, id, episodes.len());
+        }
+    }
+    Ok(())
+}
+
+pub fn subscribe(action: SubscribeAction) -> Result<(), CommandError> {
+    let mut store = SubscriptionStore::open(DATA_DIR)?;
+    match action {
+        SubscribeAction::Add {
+            id,
+            src,
+            index,
+            save_dir,
+            naming,
+            nfo,
+            artwork,
+        } => {
+            if store.find(src, &id).is_some() {
+                println!("already subscribed {:?} {}", src, id);
+                return Ok(());
+            }
+            let naming = naming
+                .unwrap_or_else(|| default_template(save_dir.is_some()).to_string());
+            println!("subscribed {:?} {}", src, id);
+            let mut subscription = Subscription::new(src, id, index, save_dir, naming);
+            subscription.nfo = nfo;
+            subscription.artwork = artwork;
+            store.add(subscription);
+            store.save()?;
+        }
+        SubscribeAction::Remove { id, src } => {
+            if store.remove(src, &id) {
+                store.save()?;
+                println!("unsubscribed {:?} {}", src, id);
+            } else {
+                println!("not subscribed {:?} {}", src, id);
+            }
+        }
+        SubscribeAction::List => {
+            for subscription in store.subscriptions() {
+                println!("{}", subscription);
+            }
+        }
+        SubscribeAction::History { limit } => {
+            let history = store.history()?;
+            for entry in history.iter().rev().take(limit).rev() {
+                println!("{}", entry);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fetch every subscribed teleplay again, bypassing the cached pages, and download only the
+/// episodes that were not fetched before.
+pub async fn sync(climit: usize, hls: &HlsArgs, dry_run: bool) -> Result<(), CommandError> {
+    let mut store = SubscriptionStore::open(DATA_DIR)?;
+    let mut failed = 0;
+    let mut failed_subscriptions = 0;
+    let mut histories = Vec::new();
+    for position in 0..store.subscriptions().len() {
+        let subscription = store.subscriptions()[position].clone();
+        println!("sync {}", subscription);
+        let options = DownloadOptions {
+            index: subscription.index,
+            save_dir: subscription.save_dir.clone(),
+            print: false,
+            climit,
+            hls: hls.clone(),
+            naming: subscription.naming.clone(),
+            nfo: subscription.nfo,
+            artwork: subscription.artwork,
+            skip: subscription.downloaded.clone(),
+            dry_run,
+            episode: None,
+        };
+        let result = match site_requestor(subscription.src, true) {
+            Ok(requestor) => fetch(&subscription.id, subscription.src, requestor, &options).await,
+            Err(e) => Err(e),
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // one broken site or teleplay must not hold back the other subscriptions
+                println!("sync {} error: {}", subscription, e);
+                failed_subscriptions += 1;
+                continue;
+            }
+        };
+        failed += result.failed.len();
+        if dry_run {
+            continue;
+        }
+        let history = result
+            .fetched
+            .iter()
+            .filter(|fetched| fetched.new)
+            .map(|fetched| HistoryEntry::new(&subscription, &fetched.name, &fetched.file))
+            .collect::<Vec<_>>();
+        println!("{} new episodes", history.len());
+        if let Err(e) = record_library(subscription.src, &result) {
+            println!("record library error: {}", e);
+        }
+        let subscription = store.get_mut(position);
+        subscription.title.replace(result.title);
+        subscription
+            .downloaded
+            .extend(result.fetched.into_iter().map(|fetched| fetched.url));
+        subscription.last_sync.replace(now());
+        histories.extend(history);
+    }
+    if !dry_run {
+        store.save()?;
+        store.append_history(&histories)?;
+    }
+    if failed_subscriptions > 0 {
+        return Err(CommandError::SyncFailed(failed_subscriptions));
+    }
+    if failed > 0 {
+        return Err(CommandError::DownloadFailed(failed));
+    }
+    Ok(())
+}
+
+/// Download what `url` points at, a video file straight away, otherwise the search results,
+/// the teleplay or the episode by the page of the site owning the host.
+pub async fn get(
+    url: &str,
+    output: &str,
+    nocache: bool,
+    mut options: DownloadOptions,
+) -> Result<(), CommandError> {
+    let parsed = Url::parse(url).map_err(VRSRError::from)?;
+    let path = parsed.path().to_ascii_lowercase();
+    if path.ends_with(".m3u8") {
+        let hls = options.hls.clone();
+        return m3u8_download(url, output, options.climit, false, None, &hls, false).await;
+    }
+    if path.ends_with(".mp4") {
+        if let Some(parent) = std::path::Path::new(output).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut downloader = MP4DownloadBuilder::new()
+            .uri(url)
+            .save_file(output)
+            .timeout(config::settings().download_timeout)
+            .client(download_client(&config::settings().site_layer())?)
+            .build();
+        downloader.download().await?;
+        return Ok(());
+    }
+
+    let Some(src) = src_of_url(&parsed) else {
+        return Err(CommandError::UnknownSite(
+            parsed.host_str().unwrap_or(url).to_string(),
+        ));
+    };
+    match resource_info(src).page(&parsed) {
+        Some(Page::Search(keyword)) => {
+            let options = SearchOptions {
+                page: 1,
+                pages: 1,
+                filter: SearchFilter::default(),
+                preview: false,
+            };
+            search(&keyword, src, false, nocache, &options).await
+        }
+        Some(Page::Detail(id)) => download_teleplay(&id, src, nocache, &options).await,
+        Some(Page::Play(id)) => {
+            options.episode.replace(url.to_string());
+            download_teleplay(&id, src, nocache, &options).await
+        }
+        None => Err(CommandError::UnknownPage(url.to_string())),
+    }
+}
+
+pub async fn m3u8_download(
+    url: &str,
+    output: &str,
+    climit: usize,
+    live: bool,
+    duration: Option<u64>,
+    hls: &HlsArgs,
+    dry_run: bool,
+) -> Result<(), CommandError> {
     let path = std::path::Path::new(output);
     if let Some(parent) = path.parent() {
         if !parent.exists() {
-            std::fs::create_dir_all(parent).unwrap();
+            std::fs::create_dir_all(parent)?;
         }
     }
+    let client = download_client(&config::settings().site_layer())?;
     let mut builder = M3U8DownloadBuilder::new();
-    builder
+    configure_hls(&mut builder, hls, &[], &client)
         .uri(url)
         .save_file(output)
-        .timeout(5)
         .climit(climit)
+        .live(live)
+        .duration(duration)
         .ignore_cache(true);
     let mut downloader = builder.build();
-    downloader.download().await.unwrap();
+    if dry_run {
+        println!("{}", downloader.ad_report().await?);
+        return Ok(());
+    }
+    downloader.download().await?;
+    Ok(())
+}
+
+pub struct SniffOptions {
+    pub url_patterns: Vec<String>,
+    pub content_types: Vec<String>,
+    pub resource_types: Vec<String>,
+    pub click: Option<String>,
+    pub all: bool,
+}
+
+pub async fn sniff(url: &str, options: &SniffOptions) -> Result<(), CommandError> {
+    let mut rules = Vec::new();
+    for pattern in options.url_patterns.iter() {
+        rules.push(SniffRule::url(pattern)?);
+    }
+    for pattern in options.content_types.iter() {
+        rules.push(SniffRule::content_type(pattern)?);
+    }
+    for resource_type in options.resource_types.iter() {
+        rules.push(SniffRule::resource_type(resource_type));
+    }
+    let mut sniffer = Sniffer::new();
+    if !rules.is_empty() {
+        sniffer = sniffer.rules(rules);
+    }
+    let click = options.click.clone();
+    let candidates = sniffer
+        .sniff(&BrowserPool::shared(), url, move |tab| {
+            if let Some(selector) = click {
+                tab.wait_for_element(&selector)
+                    .map_err(|_| VRSRError::BrowserError)?
+                    .click()
+                    .map_err(|_| VRSRError::BrowserError)?;
+            }
+            Ok(())
+        })
+        .await?;
+    if options.all {
+        for candidate in candidates.iter() {
+            println!("{}", candidate);
+        }
+    } else {
+        println!("{}", candidates[0]);
+    }
+    Ok(())
+}
+
+fn collect_videos(path: &std::path::Path, recursive: bool, videos: &mut Vec<std::path::PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                collect_videos(&path, recursive, videos)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "mp4" || extension == "ts")
+        {
+            videos.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn verify(path: &str, recursive: bool) -> Result<(), CommandError> {
+    let path = std::path::Path::new(path);
+    let mut videos = Vec::new();
+    if path.is_dir() {
+        collect_videos(path, recursive, &mut videos)?;
+        videos.sort();
+    } else {
+        videos.push(path.to_path_buf());
+    }
+    let mut failed = 0;
+    for video in videos.iter() {
+        match verify_file(video, None) {
+            Ok(report) => {
+                if !report.is_ok() {
+                    failed += 1;
+                }
+                println!("{}", report);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("[FAIL] {}\n    {}", video.display(), e);
+            }
+        }
+    }
+    println!("verified {} files, {} failed", videos.len(), failed);
+    if failed > 0 {
+        return Err(CommandError::VerifyFailed(failed));
+    }
+    Ok(())
+}
+
+async fn check_src(
+    src: Src,
+    keyword: &str,
+    id: Option<&TeleplayId>,
+) -> Result<SiteReport, CommandError> {
+    let requestor = site_requestor(src, true)?;
+    let report = match src {
+        Src::ZBKYYY => check_site(requestor, ZBKYYYParser::new(), "zbkyyy", keyword, id).await,
+        Src::IJUJITV => check_site(requestor, IJUJITVParser::new(), "ijujitv", keyword, id).await,
+        Src::JUGOUGOU => {
+            check_site(requestor, JUGOUGOUParser::new(), "jugougou", keyword, id).await
+        }
+        Src::XMB => check_site(requestor, XMBParser::new(), "xmb", keyword, id).await,
+    };
+    Ok(report)
+}
+
+/// Load the config files, env vars and `--set` overrides, and use them for the rest of the
+/// process.
+pub fn install_config(cli: &Cli) -> Result<(), CommandError> {
+    let sources =
+        ConfigSources::discover(cli.config.clone(), cli.profile.clone(), cli.overrides.clone());
+    config::install(Config::load(&sources)?);
+    Ok(())
+}
+
+/// A requestor for the pages of `src`, with the cache directory, timeout, retries, headers and
+/// proxy configured for it.
+fn site_requestor(src: Src, nocache: bool) -> Result<Arc<Requestor>, CommandError> {
+    let settings = config::settings();
+    let site = settings.site(src);
+    let mut builder = RequestorBuilder::new();
+    builder
+        .cache_dir(&settings.cache_dir)
+        .timeout(site.timeout.unwrap_or(settings.timeout))
+        .try_count(site.retries.unwrap_or(settings.retries))
+        .ignore_cache(nocache);
+    if let Some(user_agent) = site.user_agent.as_ref() {
+        builder.header("User-Agent", user_agent);
+    }
+    for (name, value) in site.headers.iter() {
+        builder.header(name, value);
+    }
+    if let Some(proxy) = site.proxy.as_ref() {
+        builder.proxy(proxy)?;
+    }
+    builder
+        .cookie_jar(Arc::new(open_cookie_jar(src)?))
+        .hosts(resource_info(src).hosts());
+    Ok(builder.build())
+}
+
+fn open_cookie_jar(src: Src) -> Result<CookieJar, CommandError> {
+    let path = std::path::Path::new(DATA_DIR)
+        .join(COOKIES_DIR)
+        .join(format!("{}.json", config::site_name(src)));
+    Ok(CookieJar::open(path)?)
+}
+
+/// Post the login form configured for `src`, the cookies with an expiry it sets are kept in the
+/// jar of the site for the later runs.
+pub async fn login(src: Src) -> Result<(), CommandError> {
+    let name = config::site_name(src);
+    let Some(login) = config::settings().site(src).login else {
+        return Err(CommandError::NoLogin(name));
+    };
+    let info = resource_info(src);
+    let url = Url::parse(info.host())
+        .and_then(|host| host.join(&login.url))
+        .map_err(VRSRError::from)?;
+    let url = mirror::rewrite_url(url.as_str(), &info.hosts(), info.host());
+    let requestor = site_requestor(src, true)?;
+    let body = requestor
+        .post_request(&url, login.form.into_iter().collect())
+        .await?;
+    if login.success.is_some_and(|success| !body.contains(&success)) {
+        return Err(CommandError::LoginFailed(name));
+    }
+    // the jar is saved once the requestor holding it is gone
+    drop(requestor);
+    let count = open_cookie_jar(src)?.cookies().len();
+    println!("logged in to {}, {} cookies saved", name, count);
+    Ok(())
+}
+
+pub fn cookies(action: CookieAction) -> Result<(), CommandError> {
+    let all = |src: Vec<Src>| {
+        if src.is_empty() {
+            Src::value_variants().to_vec()
+        } else {
+            src
+        }
+    };
+    match action {
+        CookieAction::List { src } => {
+            for src in all(src) {
+                let cookies = open_cookie_jar(src)?.cookies();
+                println!("{} [{} cookies]", config::site_name(src), cookies.len());
+                for cookie in cookies.iter() {
+                    println!("  {}", cookie);
+                }
+            }
+        }
+        CookieAction::Import { file, src } => {
+            let mut cookies = parse_netscape(&std::fs::read_to_string(file)?);
+            for src in all(src) {
+                let info = resource_info(src);
+                let (owned, others) = cookies.into_iter().partition::<Vec<_>, _>(|cookie| {
+                    Url::parse(&format!("https://{}/", cookie.domain))
+                        .is_ok_and(|url| info.owns(&url))
+                });
+                cookies = others;
+                if owned.is_empty() {
+                    continue;
+                }
+                // session cookies end with the browser session, the jar does not keep them
+                let (owned, session) = owned
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|cookie| cookie.expires.is_some());
+                let jar = open_cookie_jar(src)?;
+                let count = owned.len();
+                for cookie in owned {
+                    jar.insert(cookie);
+                }
+                jar.save()?;
+                println!(
+                    "imported {} cookies for {}, skipped {} session cookies",
+                    count,
+                    config::site_name(src),
+                    session.len()
+                );
+            }
+            if !cookies.is_empty() {
+                println!("skipped {} cookies of other sites", cookies.len());
+            }
+        }
+        CookieAction::Clear { src } => {
+            let jar = open_cookie_jar(src)?;
+            jar.clear();
+            jar.save()?;
+            println!("cleared the cookies of {}", config::site_name(src));
+        }
+    }
+    Ok(())
+}
+
+pub fn config(action: ConfigAction) -> Result<(), CommandError> {
+    match action {
+        ConfigAction::Show { json } => {
+            let config = config::get();
+            // login forms hold passwords
+            let shown = |key: &str, value: &serde_json::Value| {
+                if key.contains(".login.form.") {
+                    serde_json::Value::from("***")
+                } else {
+                    value.clone()
+                }
+            };
+            if json {
+                let entries = config
+                    .entries()
+                    .map(|(key, value, origin)| {
+                        serde_json::json!({
+                            "key": key,
+                            "value": shown(key, value),
+                            "origin": origin.to_string(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&entries).map_err(VRSRError::from)?
+                );
+                return Ok(());
+            }
+            println!("profile: {}", config.profile().unwrap_or("-"));
+            for file in config.files() {
+                println!("file: {}", file.display());
+            }
+            for (key, value, origin) in config.entries() {
+                println!("{} = {} ({})", key, shown(key, value), origin);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Make the mirrors users added and the hosts they chose apply to every parser.
+pub fn install_mirrors() -> Result<(), CommandError> {
+    let store = MirrorStore::open(DATA_DIR)?;
+    let mirrors = store
+        .sites()
+        .iter()
+        .map(|(src, site)| (default_host(*src).to_string(), site.clone()))
+        .collect();
+    mirror::install(mirrors);
+    Ok(())
+}
+
+/// The parse api configured under `sites.<name>.parse_apis`.
+fn parse_api(settings: &ParseApiSettings) -> Result<ParseApi, VRSRError> {
+    let from = settings.from.iter().map(String::as_str).collect::<Vec<_>>();
+    let mut api = ParseApi::new(&settings.name, settings.method, &settings.url).from(&from);
+    for (key, value) in settings.params.iter() {
+        api = api.param(key, value);
+    }
+    if let Some(path) = settings.json.as_deref() {
+        api = api.extractor(Extractor::json(path));
+    } else if let Some(pattern) = settings.regex.as_deref() {
+        api = api.extractor(Extractor::regex(pattern)?);
+    }
+    Ok(api)
+}
+
+/// Make the parse apis configured for the sites replace their built-in ones.
+pub fn install_parse_apis() -> Result<(), CommandError> {
+    let mut chains = HashMap::new();
+    for src in Src::value_variants() {
+        let Some(apis) = config::settings().site(*src).parse_apis else {
+            continue;
+        };
+        let mut chain = ParseChain::default();
+        for api in apis.iter() {
+            chain.push(parse_api(api)?);
+        }
+        chains.insert(default_host(*src).to_string(), chain);
+    }
+    parseapi::install(chains);
+    Ok(())
+}
+
+/// Use `host` for `src` from now on, moving the urls saved in the library and the
+/// subscriptions to it.
+fn switch_host(store: &mut MirrorStore, src: Src, host: &str) -> Result<(), CommandError> {
+    let info = resource_info(src);
+    let hosts = info.hosts();
+    store.site_mut(src).active = if host == info.default_host() {
+        None
+    } else {
+        Some(host.to_string())
+    };
+    store.save()?;
+    let library = open_library()?.rewrite_hosts(src, &hosts, host)?;
+    let mut subscriptions = SubscriptionStore::open(DATA_DIR)?;
+    let downloaded = subscriptions.rewrite_hosts(src, &hosts, host);
+    subscriptions.save()?;
+    println!(
+        "{:?} uses {}, moved {} library and {} subscription urls",
+        src, host, library, downloaded
+    );
+    Ok(())
+}
+
+/// Check the hosts of `src`, the one in use first, and switch to the first working one when it
+/// is down. A host redirecting to a new domain is followed there.
+async fn check_mirrors(
+    store: &mut MirrorStore,
+    client: &reqwest::Client,
+    src: Src,
+) -> Result<bool, CommandError> {
+    let info = resource_info(src);
+    let active = info.host().to_string();
+    let mut hosts = vec![active.clone()];
+    for host in info.hosts() {
+        if !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+    println!("{:?} [{}]", src, info.name());
+    let mut working = None;
+    for host in hosts.iter() {
+        let health = mirror::check(client, host, Duration::from_secs(10)).await;
+        println!("    {}", health);
+        if health.ok && working.is_none() {
+            working = Some(health.moved.unwrap_or(health.host));
+        }
+    }
+    match working {
+        Some(host) if host == active => Ok(true),
+        Some(host) => {
+            if !hosts.contains(&host) {
+                store.add(src, &host);
+            }
+            switch_host(store, src, &host)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+pub async fn mirrors(action: MirrorAction) -> Result<(), CommandError> {
+    let mut store = MirrorStore::open(DATA_DIR)?;
+    match action {
+        MirrorAction::List => {
+            for src in Src::value_variants() {
+                let info = resource_info(*src);
+                println!("{:?} [{}]", src, info.name());
+                for host in info.hosts() {
+                    let mark = if host == info.host() { "*" } else { " " };
+                    println!("  {} {}", mark, host);
+                }
+            }
+        }
+        MirrorAction::Check { src } => {
+            let srcs = if src.is_empty() {
+                Src::value_variants().to_vec()
+            } else {
+                src
+            };
+            let client = reqwest::Client::builder()
+                .danger_accept_invalid_certs(true)
+                .build()
+                .map_err(VRSRError::from)?;
+            let mut failed = 0;
+            for src in srcs {
+                if !check_mirrors(&mut store, &client, src).await? {
+                    println!("no working host for {:?}", src);
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                return Err(CommandError::CheckFailed(failed));
+            }
+        }
+        MirrorAction::Add { host, src } => {
+            let host = mirror::origin(&host).ok_or_else(|| CommandError::UnknownSite(host))?;
+            if store.add(src, &host) {
+                store.save()?;
+                println!("added {} to {:?}", host, src);
+            } else {
+                println!("{:?} has {} already", src, host);
+            }
+        }
+        MirrorAction::Remove { host, src } => {
+            let host = mirror::origin(&host).unwrap_or(host);
+            let active = store
+                .sites()
+                .get(&src)
+                .is_some_and(|site| site.active.as_deref() == Some(host.as_str()));
+            if store.remove(src, &host) {
+                println!("removed {} from {:?}", host, src);
+                if active {
+                    // the library and subscriptions move back to the built-in host with it
+                    switch_host(&mut store, src, resource_info(src).default_host())?;
+                } else {
+                    store.save()?;
+                }
+            } else {
+                println!("{:?} has no mirror {}", src, host);
+            }
+        }
+        MirrorAction::Use { host, src } => {
+            let host = mirror::origin(&host).ok_or_else(|| CommandError::UnknownSite(host))?;
+            if !resource_info(src).hosts().contains(&host) {
+                store.add(src, &host);
+            }
+            switch_host(&mut store, src, &host)?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn doctor(
+    srcs: Vec<Src>,
+    keyword: Option<String>,
+    id: Option<TeleplayId>,
+    json: bool,
+) -> Result<(), CommandError> {
+    let srcs = if srcs.is_empty() {
+        vec![Src::ZBKYYY, Src::IJUJITV, Src::JUGOUGOU, Src::XMB]
+    } else {
+        srcs
+    };
+    let keyword = keyword.unwrap_or(DEFAULT_KEYWORD.to_string());
+    let mut reports = Vec::new();
+    for src in srcs {
+        let report = check_src(src, &keyword, id.as_ref()).await?;
+        if !json {
+            println!("{}", report);
+        }
+        reports.push(report);
+    }
+    let failed = reports.iter().filter(|report| !report.ok).count();
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&reports).map_err(VRSRError::from)?
+        );
+    } else {
+        println!("checked {} sites, {} failed", reports.len(), failed);
+    }
+    if failed > 0 {
+        return Err(CommandError::CheckFailed(failed));
+    }
     Ok(())
 }
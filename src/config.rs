@@ -0,0 +1,682 @@
+use crate::args::Src;
+use crate::vrsr::parseapi::ParseMethod;
+use clap::ValueEnum;
+use log::warn;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+pub const CONFIG_FILE: &str = "config.json";
+pub const PROJECT_FILE: &str = "vspider.json";
+const SYSTEM_DIR: &str = "/etc/vspider";
+const ENV_PREFIX: &str = "VSPIDER_";
+const PROFILE_VAR: &str = "VSPIDER_PROFILE";
+/// Env vars of the tool that are not settings, the profile and the fixture recorder of the
+/// tests.
+const RESERVED_VARS: [&str; 2] = [PROFILE_VAR, "VSPIDER_RECORD"];
+
+/// Settings whose env and command line values are numbers.
+const NUMERIC_KEYS: [&str; 5] = [
+    "climit",
+    "timeout",
+    "retries",
+    "download_timeout",
+    "download_retries",
+];
+
+/// Settings whose env and command line values are json lists.
+const LIST_KEYS: [&str; 1] = ["parse_apis"];
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Cannot read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Cannot parse {0}: {1}")]
+    Parse(PathBuf, serde_json::Error),
+    #[error("{0} is not a json object")]
+    NotObject(PathBuf),
+    #[error("No profile named {0}")]
+    UnknownProfile(String),
+    #[error("Expected KEY=VALUE, got {0}")]
+    BadOverride(String),
+    #[error("Invalid {key} from {origin}: {message}")]
+    Invalid {
+        key: String,
+        origin: Origin,
+        message: String,
+    },
+    #[error("Invalid settings: {0}")]
+    Settings(serde_json::Error),
+}
+
+/// Where the effective value of a setting comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Profile(String, PathBuf),
+    Env(String),
+    Cli,
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Profile(name, path) => write!(f, "profile {} in {}", name, path.display()),
+            Origin::Env(var) => write!(f, "env {}", var),
+            Origin::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// The settings of the process once every layer is applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Directory the fetched pages are cached in.
+    pub cache_dir: String,
+    /// Directory the episodes are saved in when a command has no --save-dir.
+    pub download_dir: Option<String>,
+    /// Segments downloaded at the same time.
+    pub climit: usize,
+    /// Seconds to wait for a page.
+    pub timeout: u64,
+    /// Tries of a page request.
+    pub retries: u64,
+    /// Seconds to wait for a segment or a video file.
+    pub download_timeout: u64,
+    /// Tries of a segment, negative to retry until it succeeds.
+    pub download_retries: i64,
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every page request.
+    pub headers: BTreeMap<String, String>,
+    /// Proxy url of the page requests, e.g. `socks5://127.0.0.1:1080`.
+    pub proxy: Option<String>,
+    /// Overrides of a site, keyed by its name, e.g. `jugougou`.
+    pub sites: BTreeMap<String, SiteSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cache_dir: String::from(".cache"),
+            download_dir: None,
+            climit: 32,
+            timeout: 30,
+            retries: 3,
+            download_timeout: 5,
+            download_retries: -1,
+            user_agent: None,
+            headers: BTreeMap::new(),
+            proxy: None,
+            sites: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteSettings {
+    pub timeout: Option<u64>,
+    pub retries: Option<u64>,
+    pub user_agent: Option<String>,
+    pub headers: BTreeMap<String, String>,
+    pub proxy: Option<String>,
+    /// Form posted by `login` to start a session of the site.
+    pub login: Option<LoginSettings>,
+    /// Parse apis resolving the player ids of the site, in the order they are tried, instead
+    /// of the built-in ones.
+    pub parse_apis: Option<Vec<ParseApiSettings>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoginSettings {
+    /// Login url, a path is taken on the host in use.
+    pub url: String,
+    /// Fields of the form, e.g. the user name and password.
+    pub form: BTreeMap<String, String>,
+    /// Text of the answer of a successful login, e.g. the logout link.
+    pub success: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParseApiSettings {
+    pub name: String,
+    /// `get`, `post_form` or `post_json`.
+    pub method: ParseMethod,
+    /// Api url, `{url}` and `{from}` are replaced by the player url and source, a path is taken
+    /// on the host in use.
+    pub url: String,
+    /// Query, form or json fields, with the same replacements as the url.
+    pub params: BTreeMap<String, String>,
+    /// Sources of the player the api is used for, every one when empty.
+    pub from: Vec<String>,
+    /// Dotted path of the video url in a json answer, e.g. `data.url`.
+    pub json: Option<String>,
+    /// Regex whose first group is the video url.
+    pub regex: Option<String>,
+}
+
+impl Settings {
+    /// The settings of `src`, its overrides applied over the global ones.
+    pub fn site(&self, src: Src) -> SiteSettings {
+        let base = self.site_layer();
+        let Some(site) = self.sites.get(&site_name(src)) else {
+            return base;
+        };
+        let mut headers = base.headers;
+        headers.extend(site.headers.clone());
+        SiteSettings {
+            timeout: site.timeout.or(base.timeout),
+            retries: site.retries.or(base.retries),
+            user_agent: site.user_agent.clone().or(base.user_agent),
+            headers,
+            proxy: site.proxy.clone().or(base.proxy),
+            login: site.login.clone(),
+            parse_apis: site.parse_apis.clone(),
+        }
+    }
+
+    /// The global options shared with the site overrides.
+    pub fn site_layer(&self) -> SiteSettings {
+        SiteSettings {
+            timeout: Some(self.timeout),
+            retries: Some(self.retries),
+            user_agent: self.user_agent.clone(),
+            headers: self.headers.clone(),
+            proxy: self.proxy.clone(),
+            login: None,
+            parse_apis: None,
+        }
+    }
+}
+
+/// Whether `key` names a setting or a setting of a site, other vars may share the prefix.
+fn is_setting(key: &str) -> bool {
+    let has_field = |value: Value, field: &str| {
+        value
+            .as_object()
+            .is_some_and(|fields| fields.contains_key(field))
+    };
+    match key.split('.').collect::<Vec<_>>().as_slice() {
+        ["sites", _, field, ..] => {
+            has_field(serde_json::to_value(SiteSettings::default()).unwrap(), field)
+        }
+        ["sites", ..] => false,
+        [field, ..] => has_field(serde_json::to_value(Settings::default()).unwrap(), field),
+        [] => false,
+    }
+}
+
+/// Name of `src` on the command line and in the config, e.g. `jugougou`.
+pub fn site_name(src: Src) -> String {
+    src.to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// The files, env vars and command line options a config is layered from, the later ones
+/// overriding the earlier ones.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    pub files: Vec<PathBuf>,
+    pub env: Vec<(String, String)>,
+    /// Profile picked on the command line.
+    pub profile: Option<String>,
+    /// `KEY=VALUE` overrides of the command line.
+    pub overrides: Vec<String>,
+}
+
+impl ConfigSources {
+    /// The system, user and project config files, then `extra`, with the env of the process.
+    pub fn discover(
+        extra: Option<PathBuf>,
+        profile: Option<String>,
+        overrides: Vec<String>,
+    ) -> Self {
+        let mut files = vec![Path::new(SYSTEM_DIR).join(CONFIG_FILE)];
+        let user_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+        if let Some(dir) = user_dir {
+            files.push(dir.join("vspider").join(CONFIG_FILE));
+        }
+        files.push(PathBuf::from(PROJECT_FILE));
+        files.extend(extra);
+        Self {
+            files,
+            env: std::env::vars().collect(),
+            profile,
+            overrides,
+        }
+    }
+}
+
+/// Every setting keyed by its dotted path, e.g. `sites.xmb.proxy`, with the layer it comes
+/// from.
+#[derive(Debug, Clone)]
+pub struct Config {
+    values: BTreeMap<String, (Value, Origin)>,
+    profile: Option<String>,
+    files: Vec<PathBuf>,
+    settings: Settings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut config = Self {
+            values: BTreeMap::new(),
+            profile: None,
+            files: Vec::new(),
+            settings: Settings::default(),
+        };
+        let defaults = serde_json::to_value(Settings::default()).unwrap();
+        config.merge(&defaults, &Origin::Default);
+        config
+    }
+}
+
+impl Config {
+    pub fn load(sources: &ConfigSources) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        let mut profiles = Vec::new();
+        let mut file_profile = None;
+        for path in sources.files.iter() {
+            if !path.exists() {
+                continue;
+            }
+            let text =
+                std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+            let value: Value =
+                serde_json::from_str(&text).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+            let Value::Object(mut layer) = value else {
+                return Err(ConfigError::NotObject(path.clone()));
+            };
+            if let Some(Value::String(name)) = layer.remove("profile") {
+                file_profile.replace(name);
+            }
+            if let Some(Value::Object(named)) = layer.remove("profiles") {
+                profiles.extend(
+                    named
+                        .into_iter()
+                        .map(|(name, value)| (name, value, path.clone())),
+                );
+            }
+            config.merge(&Value::Object(layer), &Origin::File(path.clone()));
+            config.files.push(path.clone());
+        }
+
+        let profile = sources
+            .profile
+            .clone()
+            .or_else(|| {
+                sources
+                    .env
+                    .iter()
+                    .find(|(name, _)| name == PROFILE_VAR)
+                    .map(|(_, value)| value.clone())
+            })
+            .or(file_profile);
+        if let Some(profile) = profile.as_ref() {
+            let mut found = false;
+            for (name, value, path) in profiles.iter().filter(|(name, ..)| name == profile) {
+                config.merge(value, &Origin::Profile(name.clone(), path.clone()));
+                found = true;
+            }
+            if !found {
+                return Err(ConfigError::UnknownProfile(profile.clone()));
+            }
+        }
+        config.profile = profile;
+
+        for (name, value) in sources.env.iter() {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if RESERVED_VARS.contains(&name.as_str()) {
+                continue;
+            }
+            // VSPIDER_SITES__XMB__PROXY sets sites.xmb.proxy
+            let key = key.to_ascii_lowercase().replace("__", ".");
+            if !is_setting(&key) {
+                warn!("skip env {}, no setting named {}", name, key);
+                continue;
+            }
+            config.set(&key, value, Origin::Env(name.clone()));
+        }
+        for item in sources.overrides.iter() {
+            let Some((key, value)) = item.split_once('=') else {
+                return Err(ConfigError::BadOverride(item.clone()));
+            };
+            config.set(key.trim(), value, Origin::Cli);
+        }
+        config.settings = config.resolve()?;
+        Ok(config)
+    }
+
+    /// Apply the settings of the json object `layer` over the current ones.
+    pub fn merge(&mut self, layer: &Value, origin: &Origin) {
+        let mut flat = Vec::new();
+        flatten("", layer, &mut flat);
+        for (key, value) in flat {
+            self.values.insert(key, (value, origin.clone()));
+        }
+    }
+
+    /// Set `key` to `raw`, read as a number for the numeric settings, as json for the list
+    /// ones and as text otherwise, `null` unsets it.
+    pub fn set(&mut self, key: &str, raw: &str, origin: Origin) {
+        let name = key.rsplit('.').next().unwrap_or_default();
+        let numeric = !key.contains("headers.") && NUMERIC_KEYS.contains(&name);
+        let list = !key.contains("headers.") && LIST_KEYS.contains(&name);
+        let value = match serde_json::from_str::<Value>(raw.trim()) {
+            Ok(Value::Null) => Value::Null,
+            Ok(value @ Value::Number(_)) if numeric => value,
+            Ok(value @ Value::Array(_)) if list => value,
+            _ => Value::String(raw.to_string()),
+        };
+        self.values.insert(key.to_string(), (value, origin));
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// The config files that were read, in the order they apply.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Every effective setting with its origin, by key.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Value, &Origin)> {
+        self.values
+            .iter()
+            .map(|(key, (value, origin))| (key.as_str(), value, origin))
+    }
+
+    fn resolve(&self) -> Result<Settings, ConfigError> {
+        let mut root = Value::Object(Map::new());
+        for (key, (value, _)) in self.values.iter() {
+            unflatten(&mut root, key, value.clone());
+        }
+        let settings: Settings = serde_json::from_value(root).map_err(ConfigError::Settings)?;
+        self.validate(&settings)?;
+        Ok(settings)
+    }
+
+    fn validate(&self, settings: &Settings) -> Result<(), ConfigError> {
+        let invalid = |key: String, message: String| {
+            let origin = self
+                .values
+                .get(&key)
+                .map(|(_, origin)| origin.clone())
+                .unwrap_or(Origin::Default);
+            ConfigError::Invalid {
+                key,
+                origin,
+                message,
+            }
+        };
+        let sites = Src::value_variants()
+            .iter()
+            .map(|src| site_name(*src))
+            .collect::<Vec<_>>();
+        let mut layers = vec![(String::new(), settings.site_layer())];
+        for (name, site) in settings.sites.iter() {
+            if !sites.contains(name) {
+                let key = self
+                    .values
+                    .keys()
+                    .find(|key| key.starts_with(&format!("sites.{}.", name)))
+                    .cloned()
+                    .unwrap_or_else(|| format!("sites.{}", name));
+                return Err(invalid(key, format!("no site named {}", name)));
+            }
+            layers.push((format!("sites.{}.", name), site.clone()));
+        }
+        for (prefix, site) in layers {
+            for (name, value) in site.headers.iter() {
+                let key = format!("{}headers.{}", prefix, name);
+                if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    return Err(invalid(key, "not a header name".to_string()));
+                }
+                if HeaderValue::from_str(value).is_err() {
+                    return Err(invalid(key, "not a header value".to_string()));
+                }
+            }
+            if let Some(user_agent) = site.user_agent.as_ref() {
+                if HeaderValue::from_str(user_agent).is_err() {
+                    let key = format!("{}user_agent", prefix);
+                    return Err(invalid(key, "not a header value".to_string()));
+                }
+            }
+            if let Some(proxy) = site.proxy.as_ref() {
+                if let Err(e) = reqwest::Proxy::all(proxy) {
+                    return Err(invalid(format!("{}proxy", prefix), e.to_string()));
+                }
+            }
+            for api in site.parse_apis.iter().flatten() {
+                let key = format!("{}parse_apis", prefix);
+                if api.url.is_empty() {
+                    return Err(invalid(key, format!("parse api {} has no url", api.name)));
+                }
+                if let Some(Err(e)) = api.regex.as_deref().map(regex::Regex::new) {
+                    return Err(invalid(key, e.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, flat: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map.iter() {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&key, value, flat);
+            }
+        }
+        // an empty object only matters at the top
+        Value::Object(_) if !prefix.is_empty() => {}
+        _ if prefix.is_empty() => {}
+        _ => flat.push((prefix.to_string(), value.clone())),
+    }
+}
+
+fn unflatten(root: &mut Value, key: &str, value: Value) {
+    let mut node = root;
+    let mut parts = key.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        let map = node.as_object_mut().unwrap();
+        if parts.peek().is_none() {
+            map.insert(part.to_string(), value);
+            return;
+        }
+        node = map
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Use `config` for the rest of the process.
+pub fn install(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// The installed config, the defaults when none is.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+pub fn settings() -> &'static Settings {
+    get().settings()
+}
+
+#[test]
+fn test_config_layers() {
+    let dir = std::env::temp_dir().join("vspider-config-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let user = dir.join("user.json");
+    let project = dir.join("project.json");
+    std::fs::write(
+        &user,
+        r#"{
+            "climit": 8,
+            "user_agent": "vspider",
+            "headers": {"Referer": "https://example.com"},
+            "profile": "slow",
+            "profiles": {
+                "slow": {"timeout": 90, "sites": {"xmb": {"proxy": "http://127.0.0.1:8080"}}},
+                "fast": {"climit": 64}
+            }
+        }"#,
+    )
+    .unwrap();
+    std::fs::write(&project, r#"{"cache_dir": "cache", "climit": 16}"#).unwrap();
+
+    let sources = ConfigSources {
+        files: vec![user.clone(), dir.join("missing.json"), project.clone()],
+        env: vec![
+            ("VSPIDER_RETRIES".to_string(), "5".to_string()),
+            ("VSPIDER_USER_AGENT".to_string(), "1.0".to_string()),
+            ("VSPIDER_SITES__XMB__RETRIES".to_string(), "2".to_string()),
+            // vars of other tools on the prefix are left alone
+            ("VSPIDER_HOME".to_string(), "/opt/vspider".to_string()),
+            ("VSPIDER_SITES__XMB__COLOR".to_string(), "1".to_string()),
+            ("HOME".to_string(), "/home/a".to_string()),
+        ],
+        profile: None,
+        overrides: vec!["sites.xmb.timeout=10".to_string()],
+    };
+    let config = Config::load(&sources).unwrap();
+    let settings = config.settings();
+    assert_eq!(config.profile(), Some("slow"));
+    assert_eq!(config.files(), &[user.clone(), project.clone()]);
+    assert_eq!(settings.cache_dir, "cache");
+    assert_eq!(settings.climit, 16);
+    assert_eq!(settings.timeout, 90);
+    assert_eq!(settings.retries, 5);
+    assert_eq!(settings.user_agent.as_deref(), Some("1.0"));
+    let xmb = settings.site(Src::XMB);
+    assert_eq!(xmb.timeout, Some(10));
+    assert_eq!(xmb.retries, Some(2));
+    assert_eq!(xmb.proxy.as_deref(), Some("http://127.0.0.1:8080"));
+    assert_eq!(xmb.headers["Referer"], "https://example.com");
+    assert_eq!(settings.site(Src::ZBKYYY).timeout, Some(90));
+
+    let origin = |key: &str| {
+        config
+            .entries()
+            .find(|(name, ..)| *name == key)
+            .map(|(.., origin)| origin.to_string())
+            .unwrap()
+    };
+    assert_eq!(origin("download_retries"), "default");
+    assert_eq!(origin("download_timeout"), "default");
+    assert_eq!(settings.download_timeout, 5);
+    assert_eq!(origin("climit"), project.display().to_string());
+    assert_eq!(
+        origin("timeout"),
+        format!("profile slow in {}", user.display())
+    );
+    assert_eq!(origin("retries"), "env VSPIDER_RETRIES");
+    assert_eq!(origin("sites.xmb.timeout"), "command line");
+
+    // the command line picks the profile over the files
+    let config = Config::load(&ConfigSources {
+        profile: Some("fast".to_string()),
+        ..sources.clone()
+    })
+    .unwrap();
+    assert_eq!(config.settings().climit, 64);
+    assert_eq!(config.settings().timeout, 30);
+    assert!(matches!(
+        Config::load(&ConfigSources {
+            profile: Some("none".to_string()),
+            ..sources.clone()
+        }),
+        Err(ConfigError::UnknownProfile(_))
+    ));
+    assert!(matches!(
+        Config::load(&ConfigSources {
+            overrides: vec!["sites.nowhere.timeout=1".to_string()],
+            ..sources.clone()
+        }),
+        Err(ConfigError::Invalid { .. })
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_config_parse_apis() {
+    let dir = std::env::temp_dir().join("vspider-config-parse-apis-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("config.json");
+    std::fs::write(
+        &file,
+        r#"{"sites": {"jugougou": {"parse_apis": [
+            {"name": "a", "method": "post_json", "url": "/parse", "params": {"vid": "{url}"},
+             "from": ["vendor"], "json": "data.url"},
+            {"name": "b", "url": "https://parse.example.com/?url={url}"}
+        ]}}}"#,
+    )
+    .unwrap();
+    let sources = ConfigSources {
+        files: vec![file.clone()],
+        ..ConfigSources::default()
+    };
+    let config = Config::load(&sources).unwrap();
+    let apis = config.settings().site(Src::JUGOUGOU).parse_apis.unwrap();
+    assert_eq!(apis.len(), 2);
+    assert_eq!(apis[0].method, ParseMethod::PostJson);
+    assert_eq!(apis[0].from, vec!["vendor".to_string()]);
+    assert_eq!(apis[0].json.as_deref(), Some("data.url"));
+    assert_eq!(apis[1].method, ParseMethod::Get);
+    assert_eq!(config.settings().site(Src::XMB).parse_apis, None);
+
+    let config = Config::load(&ConfigSources {
+        env: vec![(
+            "VSPIDER_SITES__JUGOUGOU__PARSE_APIS".to_string(),
+            r#"[{"name": "c", "url": "/api"}]"#.to_string(),
+        )],
+        ..sources.clone()
+    })
+    .unwrap();
+    let apis = config.settings().site(Src::JUGOUGOU).parse_apis.unwrap();
+    assert_eq!(apis.len(), 1);
+    assert_eq!(apis[0].name, "c");
+
+    assert!(matches!(
+        Config::load(&ConfigSources {
+            overrides: vec![r#"sites.xmb.parse_apis=[{"url": "/api", "regex": "("}]"#.to_string()],
+            ..sources.clone()
+        }),
+        Err(ConfigError::Invalid { .. })
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,258 @@
+use crate::vrsr::error::Error as VRSRError;
+use crate::vrsr::{create_resource, create_teleplay, Episode, Resource, Stage, Teleplay};
+use crate::vrsr::{TeleplayId, URIType};
+use crate::vrsr::{EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
+use futures::FutureExt;
+use serde::Serialize;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Keyword searched when none is given, a title every site carries.
+pub const DEFAULT_KEYWORD: &str = "繁花";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub ok: bool,
+    pub millis: u128,
+    pub url: Option<String>,
+    /// What the stage found, e.g. the number of results.
+    pub summary: String,
+    pub error: Option<String>,
+    /// Selector that matched nothing when the parser failed.
+    pub selector: Option<String>,
+    /// Copy of the page the parser failed on.
+    pub snapshot: Option<String>,
+}
+
+impl std::fmt::Display for StageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if self.ok { "OK" } else { "FAIL" };
+        write!(f, "{:<8} {:<4} {:>6}ms", self.stage, state, self.millis)?;
+        if !self.summary.is_empty() {
+            write!(f, " {}", self.summary)?;
+        }
+        if let Some(url) = self.url.as_ref() {
+            write!(f, " @ {}", url)?;
+        }
+        if let Some(error) = self.error.as_ref() {
+            write!(f, "\n             {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of running a search, a detail fetch and an episode resolution against one site. The
+/// stages after a failed one are not run.
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteReport {
+    pub site: String,
+    pub name: String,
+    pub host: String,
+    pub ok: bool,
+    pub millis: u128,
+    pub stages: Vec<StageReport>,
+}
+
+impl std::fmt::Display for SiteReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if self.ok { "OK" } else { "FAIL" };
+        write!(
+            f,
+            "[{}] {} [{}] {}ms",
+            state, self.site, self.name, self.millis
+        )?;
+        for stage in self.stages.iter() {
+            write!(f, "\n    {}", stage)?;
+        }
+        Ok(())
+    }
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    format!("panicked: {}", message)
+}
+
+/// Run a stage, turning its panics into failures too so one broken site does not stop the
+/// check of the others.
+async fn run_stage<T, F>(stage: Stage, url: Option<String>, future: F) -> (StageReport, Option<T>)
+where
+    F: Future<Output = Result<(String, T), VRSRError>>,
+{
+    let start = Instant::now();
+    let result = AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|panic| Err(VRSRError::ParseError(panic_message(panic))));
+    let mut report = StageReport {
+        stage,
+        ok: result.is_ok(),
+        millis: start.elapsed().as_millis(),
+        url,
+        summary: String::new(),
+        error: None,
+        selector: None,
+        snapshot: None,
+    };
+    match result {
+        Ok((summary, value)) => {
+            report.summary = summary;
+            (report, Some(value))
+        }
+        Err(e) => {
+            if let VRSRError::Diagnosed(diagnostic) = &e {
+                report.selector = diagnostic.selector.clone();
+                report.snapshot = diagnostic
+                    .snapshot
+                    .as_ref()
+                    .map(|snapshot| snapshot.to_string_lossy().to_string());
+            }
+            report.error.replace(e.to_string());
+            (report, None)
+        }
+    }
+}
+
+/// Check a site with a search for `keyword`, the detail page of `id` or else of the first
+/// result, and the first episode of its first source.
+pub async fn check_site<R, P>(
+    requestor: Arc<R>,
+    parser: Arc<P>,
+    site: &str,
+    keyword: &str,
+    id: Option<&TeleplayId>,
+) -> SiteReport
+where
+    R: Request,
+    P: GenerateInfo + ResourceParse + TeleplayParse + EpisodeParse,
+{
+    let start = Instant::now();
+    let mut resource = create_resource(requestor.clone(), parser.clone());
+    let mut report = SiteReport {
+        site: site.to_string(),
+        name: resource.name().to_string(),
+        host: resource.host().to_string(),
+        ok: false,
+        millis: 0,
+        stages: Vec::new(),
+    };
+
+    let (stage, found) = run_stage(Stage::Search, None, async {
+        let teleplays = resource.search(keyword).await?;
+        let Some(first) = teleplays.first() else {
+            return Err(VRSRError::ParseError(format!("No results for {}", keyword)));
+        };
+        let first = first.lock().await.id().clone();
+        Ok((format!("{} results", teleplays.len()), first))
+    })
+    .await;
+    report.stages.push(stage);
+
+    if let Some(id) = id.or(found.as_ref()) {
+        let mut teleplay = create_teleplay(requestor.clone(), parser.clone(), id);
+        let url = Some(teleplay.home_page().to_string());
+        let (stage, episode) = run_stage(Stage::Detail, url, async {
+            let sources = teleplay.request().await?;
+            let episodes = sources
+                .iter()
+                .map(|(_, episodes)| episodes.len())
+                .sum::<usize>();
+            let first = sources
+                .iter()
+                .find_map(|(_, episodes)| episodes.first().cloned())
+                .ok_or_else(|| VRSRError::ParseError("No episodes".to_string()))?;
+            Ok((
+                format!("{} sources, {} episodes", sources.len(), episodes),
+                first,
+            ))
+        })
+        .await;
+        report.stages.push(stage);
+
+        if let Some(episode) = episode {
+            let url = Some(episode.lock().await.url().to_string());
+            let (stage, _) = run_stage(Stage::Episode, url, async {
+                let uri = episode.lock().await.request().await?;
+                if uri.uri.is_empty() || matches!(uri.utype, URIType::UNKNOWN) {
+                    return Err(VRSRError::ParseError(format!(
+                        "Unplayable episode uri {}",
+                        uri.uri
+                    )));
+                }
+                Ok((format!("{:?} {}", uri.utype, uri.uri), ()))
+            })
+            .await;
+            report.stages.push(stage);
+        }
+    }
+
+    report.ok = report.stages.len() == 3 && report.stages.iter().all(|stage| stage.ok);
+    report.millis = start.elapsed().as_millis();
+    report
+}
+
+#[tokio::test()]
+async fn test_check_site() {
+    use crate::vrsr::fixture::{fixture_dir, FixtureRequestor};
+    use crate::vrsr::{JUGOUGOUParser, ZBKYYYParser};
+
+    let requestor = FixtureRequestor::new(fixture_dir("zbkyyy"));
+    let report = check_site(
+        requestor,
+        ZBKYYYParser::new(),
+        "zbkyyy",
+        DEFAULT_KEYWORD,
+        None,
+    )
+    .await;
+    assert!(report.ok, "{}", report);
+    assert_eq!(report.stages[0].summary, "1 results");
+    assert_eq!(report.stages[1].summary, "2 sources, 3 episodes");
+    assert_eq!(
+        report.stages[2].url.as_deref(),
+        Some("https://www.zbkyyy.com/qyvodplay/101-1-1.html")
+    );
+
+    // nothing recorded for the keyword or the teleplay, the detail stage still runs for the id
+    let requestor = FixtureRequestor::new(fixture_dir("jugougou"));
+    let report = check_site(requestor, JUGOUGOUParser::new(), "jugougou", "无", Some(&1.into())).await;
+    assert!(!report.ok);
+    assert_eq!(report.stages.len(), 2);
+    assert!(report.stages.iter().all(|stage| !stage.ok));
+    assert_eq!(report.stages[1].stage, Stage::Detail);
+    assert!(report.stages[1]
+        .error
+        .as_ref()
+        .unwrap()
+        .contains("No fixture"));
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["stages"][1]["stage"], "detail");
+    assert_eq!(json["ok"], false);
+
+    // a detail page whose template changed
+    let requestor = FixtureRequestor::new(fixture_dir("jugougou"));
+    let report = check_site(
+        requestor,
+        JUGOUGOUParser::new(),
+        "jugougou",
+        "无",
+        Some(&405.into()),
+    )
+    .await;
+    let detail = &report.stages[1];
+    assert!(!detail.ok);
+    assert!(detail
+        .selector
+        .as_ref()
+        .unwrap()
+        .contains("div.ewave-content__detail"));
+    assert!(detail.error.as_ref().unwrap().contains("detail page"));
+}
@@ -0,0 +1,308 @@
+use m3u8_rs::MediaSegment;
+use std::collections::HashMap;
+use url::Url;
+
+#[derive(Debug, Clone)]
+pub enum AdReason {
+    Pattern(String),
+    HostChange(String),
+    DurationPattern,
+}
+
+impl std::fmt::Display for AdReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdReason::Pattern(pattern) => write!(f, "match pattern {}", pattern),
+            AdReason::HostChange(host) => write!(f, "host change to {}", host),
+            AdReason::DurationPattern => write!(f, "duration pattern"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AdBlock {
+    pub start: usize,
+    pub end: usize,
+    pub duration: f32,
+    pub reason: AdReason,
+    pub uris: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AdReport {
+    pub total: usize,
+    pub blocks: Vec<AdBlock>,
+}
+
+impl std::fmt::Display for AdReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let dropped = self.blocks.iter().map(|b| b.end - b.start).sum::<usize>();
+        let duration = self.blocks.iter().map(|b| b.duration).sum::<f32>();
+        write!(
+            f,
+            "drop {}/{} segments, {:.1}s in {} blocks",
+            dropped,
+            self.total,
+            duration,
+            self.blocks.len()
+        )?;
+        for block in self.blocks.iter() {
+            write!(
+                f,
+                "\n[{}..{}] {:.1}s {}",
+                block.start, block.end, block.duration, block.reason
+            )?;
+            for uri in block.uris.iter() {
+                write!(f, "\n    {}", uri)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Detects ad blocks spliced into a media playlist, either by configured url patterns, by a
+/// host change or by short discontinuity-bounded groups served from another directory whose
+/// segment durations do not match the main content.
+#[derive(Debug, Clone)]
+pub struct AdFilter {
+    patterns: Vec<String>,
+    max_ad_duration: f32,
+}
+
+impl AdFilter {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self {
+            patterns,
+            max_ad_duration: 120.0,
+        }
+    }
+
+    /// The most frequent value, the first one seen among equally frequent ones so the result
+    /// does not depend on the hashing order.
+    fn most_common<T: std::hash::Hash + Eq + Clone>(values: impl Iterator<Item = T>) -> Option<T> {
+        let mut counter: HashMap<T, (usize, usize)> = HashMap::new();
+        for (index, value) in values.enumerate() {
+            counter.entry(value).or_insert((0, index)).0 += 1;
+        }
+        counter
+            .into_iter()
+            .max_by_key(|(_, (count, first))| (*count, std::cmp::Reverse(*first)))
+            .map(|(value, _)| value)
+    }
+
+    fn duration_key(segment: &MediaSegment) -> i64 {
+        (segment.duration * 10.0).round() as i64
+    }
+
+    /// The segment uri joined to the playlist url, the uri itself when it does not join.
+    fn url(segment: &MediaSegment, base_url: &Url) -> String {
+        base_url
+            .join(&segment.uri)
+            .map(|url| url.to_string())
+            .unwrap_or(segment.uri.clone())
+    }
+
+    /// Origin and directory of the segment url, e.g. `https://cdn.example.com/video/`.
+    fn directory(segment: &MediaSegment, base_url: &Url) -> String {
+        base_url
+            .join(&segment.uri)
+            .ok()
+            .and_then(|url| url.join("./").ok())
+            .map(|url| url.to_string())
+            .unwrap_or_default()
+    }
+
+    fn host(segment: &MediaSegment, base_url: &Url) -> String {
+        base_url
+            .join(&segment.uri)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_default()
+    }
+
+    fn block(
+        segments: &[MediaSegment],
+        start: usize,
+        end: usize,
+        reason: AdReason,
+        base_url: &Url,
+    ) -> AdBlock {
+        let segments = &segments[start..end];
+        AdBlock {
+            start,
+            end,
+            duration: segments.iter().map(|s| s.duration).sum(),
+            reason,
+            uris: segments.iter().map(|s| Self::url(s, base_url)).collect(),
+        }
+    }
+
+    pub fn detect(&self, segments: &[MediaSegment], base_url: &Url) -> Vec<AdBlock> {
+        let mut blocks = Vec::new();
+        let hosts = segments
+            .iter()
+            .map(|segment| Self::host(segment, base_url))
+            .collect::<Vec<_>>();
+        let main_host = Self::most_common(hosts.iter().cloned()).unwrap_or_default();
+        let main_duration = Self::most_common(segments.iter().map(Self::duration_key));
+        let directories = segments
+            .iter()
+            .map(|segment| Self::directory(segment, base_url))
+            .collect::<Vec<_>>();
+        let main_directory = Self::most_common(directories.iter().cloned());
+        // patterns like `/adjump/` are matched on the full urls, relative uris lack the slash
+        let urls = segments
+            .iter()
+            .map(|segment| Self::url(segment, base_url))
+            .collect::<Vec<_>>();
+
+        let mut groups = Vec::new();
+        let mut start = 0;
+        for (index, segment) in segments.iter().enumerate() {
+            if segment.discontinuity && index > start {
+                groups.push((start, index));
+                start = index;
+            }
+        }
+        if start < segments.len() {
+            groups.push((start, segments.len()));
+        }
+
+        for (start, end) in groups.iter().cloned() {
+            let group = &segments[start..end];
+            let pattern = self.patterns.iter().find(|pattern| {
+                urls[start..end]
+                    .iter()
+                    .any(|url| url.contains(pattern.as_str()))
+            });
+            if let Some(pattern) = pattern {
+                let mut index = start;
+                while index < end {
+                    if !urls[index].contains(pattern.as_str()) {
+                        index += 1;
+                        continue;
+                    }
+                    let block_start = index;
+                    while index < end && urls[index].contains(pattern.as_str()) {
+                        index += 1;
+                    }
+                    let reason = AdReason::Pattern(pattern.clone());
+                    blocks.push(Self::block(segments, block_start, index, reason, base_url));
+                }
+                continue;
+            }
+            let group_host =
+                Self::most_common(hosts[start..end].iter().cloned()).unwrap_or_default();
+            if group_host != main_host {
+                let reason = AdReason::HostChange(group_host);
+                blocks.push(Self::block(segments, start, end, reason, base_url));
+                continue;
+            }
+            let group_duration = group.iter().map(|s| s.duration).sum::<f32>();
+            let group_pattern = Self::most_common(group.iter().map(Self::duration_key));
+            // short recaps and previews have their own durations too, but come from the same
+            // directory as the main content
+            let group_directory = Self::most_common(directories[start..end].iter().cloned());
+            if groups.len() > 1
+                && group_duration <= self.max_ad_duration
+                && group_pattern != main_duration
+                && group_directory != main_directory
+            {
+                let reason = AdReason::DurationPattern;
+                blocks.push(Self::block(segments, start, end, reason, base_url));
+            }
+        }
+        blocks
+    }
+
+    /// Drop the detected ad blocks. Keys and maps are made explicit on every segment first, so
+    /// dropping the segment a tag was attached to does not lose it for the following content.
+    pub fn apply(
+        &self,
+        mut segments: Vec<MediaSegment>,
+        base_url: &Url,
+    ) -> (Vec<MediaSegment>, Vec<AdBlock>) {
+        let (mut key, mut map) = (None, None);
+        for segment in segments.iter_mut() {
+            if segment.key.is_some() {
+                key = segment.key.clone();
+            }
+            if segment.map.is_some() {
+                map = segment.map.clone();
+            }
+            segment.key = key.clone();
+            segment.map = map.clone();
+        }
+        let blocks = self.detect(&segments, base_url);
+        let kept = segments
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                !blocks
+                    .iter()
+                    .any(|block| (block.start..block.end).contains(index))
+            })
+            .map(|(_, segment)| segment)
+            .collect();
+        (kept, blocks)
+    }
+}
+
+#[test]
+fn test_detect_ads() {
+    let segment = |uri: &str, duration: f32, discontinuity: bool| MediaSegment {
+        uri: uri.to_string(),
+        duration,
+        discontinuity,
+        ..MediaSegment::default()
+    };
+    let mut segments = Vec::new();
+    for index in 0..20 {
+        segments.push(segment(&format!("main{}.ts", index), 4.0, false));
+    }
+    segments.push(segment("https://ads.example.com/a0.ts", 3.0, true));
+    segments.push(segment("https://ads.example.com/a1.ts", 3.0, false));
+    for index in 20..30 {
+        segments.push(segment(&format!("main{}.ts", index), 4.0, index == 20));
+    }
+    segments.push(segment("ad/short0.ts", 2.5, true));
+    segments.push(segment("ad/short1.ts", 2.5, false));
+    for index in 30..35 {
+        segments.push(segment(&format!("main{}.ts", index), 4.0, index == 30));
+    }
+    // a recap from the same directory is content
+    segments.push(segment("recap0.ts", 2.5, true));
+    segments.push(segment("recap1.ts", 2.5, false));
+    for index in 35..40 {
+        segments.push(segment(&format!("main{}.ts", index), 4.0, index == 35));
+    }
+    segments.push(segment("main40_adv.ts", 4.0, false));
+
+    let base_url = Url::parse("https://cdn.example.com/video/index.m3u8").unwrap();
+    let filter = AdFilter::new(vec!["_adv".to_string()]);
+    let (kept, blocks) = filter.apply(segments, &base_url);
+    assert_eq!(blocks.len(), 3);
+    assert!(
+        matches!(blocks[0].reason, AdReason::HostChange(ref host) if host == "ads.example.com")
+    );
+    assert!(matches!(blocks[1].reason, AdReason::DurationPattern));
+    assert!(matches!(blocks[2].reason, AdReason::Pattern(_)));
+    assert_eq!(kept.len(), 42);
+    assert!(kept.iter().all(|segment| !segment.uri.starts_with("ad")));
+
+    // ties go to the first value seen
+    assert_eq!(AdFilter::most_common([2, 1, 1, 2, 3].into_iter()), Some(2));
+    assert_eq!(AdFilter::most_common(["b", "a"].into_iter()), Some("b"));
+
+    // patterns are matched on the joined urls, the relative uri lacks the leading slash
+    let segments = vec![
+        segment("main0.ts", 4.0, false),
+        segment("adjump/0.ts", 4.0, false),
+        segment("main1.ts", 4.0, false),
+    ];
+    let filter = AdFilter::new(vec!["/adjump/".to_string()]);
+    let (kept, blocks) = filter.apply(segments, &base_url);
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].uris, ["https://cdn.example.com/video/adjump/0.ts"]);
+    assert_eq!(kept.len(), 2);
+}
@@ -0,0 +1,46 @@
+use super::error::DownloadError;
+use log::warn;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// Headers and proxy the downloaders send their requests with.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    headers: HeaderMap,
+    proxy: Option<reqwest::Proxy>,
+}
+
+impl ClientOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send `value` as the `name` header, skipped when either is not valid in a header.
+    pub fn header(&mut self, name: &str, value: &str) -> &mut Self {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                self.headers.insert(name, value);
+            }
+            _ => warn!("skip invalid header {}: {}", name, value),
+        }
+        self
+    }
+
+    /// Download through the proxy at `url`, e.g. `socks5://127.0.0.1:1080`.
+    pub fn proxy(&mut self, url: &str) -> Result<&mut Self, DownloadError> {
+        self.proxy.replace(reqwest::Proxy::all(url)?);
+        Ok(self)
+    }
+
+    pub fn build(&self) -> reqwest::Client {
+        let mut client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .default_headers(self.headers.clone());
+        if let Some(proxy) = self.proxy.clone() {
+            client = client.proxy(proxy);
+        }
+        client.build().unwrap()
+    }
+}
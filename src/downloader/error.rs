@@ -15,4 +15,10 @@ pub enum DownloadError {
     Incomplete,
     #[error("get content size error")]
     GetContentSize,
+    #[error("decrypt segment error")]
+    Decrypt,
+    #[error("verify failed: {0}")]
+    Verify(String),
+    #[error("empty byte range of {0}")]
+    EmptyRange(String),
 }
@@ -1,12 +1,17 @@
+use super::adfilter::{AdBlock, AdFilter, AdReport};
+use super::client::ClientOptions;
 use super::error::DownloadError;
-use bytes::Buf;
+use super::verify::{commit_file, part_file};
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{error, info, warn};
-use m3u8_rs::{MasterPlaylist, MediaPlaylist, Playlist, KeyMethod};
+use m3u8_rs::{
+    AlternativeMedia, AlternativeMediaType, Key, KeyMethod, Map, MasterPlaylist, MediaPlaylist,
+    MediaPlaylistType, MediaSegment, Playlist,
+};
 use std::{collections::HashMap, vec};
 use std::sync::Arc;
 use tokio::fs::File;
-use tokio::io::copy;
+use tokio::io::{copy, AsyncWriteExt};
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 use url::Url;
@@ -15,28 +20,53 @@ use aes::cipher::{block_padding::Pkcs7, generic_array::GenericArray, BlockDecryp
 
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
 
+/// Retries of a failed live playlist refresh before the recording stops.
+const REFRESH_TRIES: u32 = 5;
+const MAX_REFRESH_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[derive(Clone)]
 struct AesKey {
     key: Vec<u8>,
     iv: Vec<u8>,
 }
 
+#[derive(Clone, Copy)]
+struct SegmentRange {
+    offset: u64,
+    length: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Track {
+    Video,
+    Audio,
+    Subtitle(usize),
+}
+
 struct Segment {
     pub uri: String,
     pub save_file: String,
     pub try_count: i64,
     pub success: bool,
     pub key: Option<AesKey>,
+    pub track: Track,
+    pub range: Option<SegmentRange>,
+    pub init: bool,
+    pub duration: f32,
 }
 
 impl Segment {
-    pub fn new(uri: &str, save_file: &str, key: Option<AesKey>) -> Self {
+    pub fn new(uri: &str, save_file: &str, key: Option<AesKey>, track: Track) -> Self {
         Self {
             uri: uri.to_string(),
             save_file: save_file.to_string(),
             try_count: 0,
             success: false,
             key,
+            track,
+            range: None,
+            init: false,
+            duration: 0.0,
         }
     }
 }
@@ -50,65 +80,95 @@ pub struct M3U8Download {
     segments: Vec<Segment>,
     try_count: i64,
     timeout: u64,
-    cache_file: Option<String>,
+    cache_files: Vec<String>,
     ignore_cache: bool,
     pbar: Option<ProgressBar>,
     climit: usize,
     aes_keys: HashMap<String, AesKey>,
+    live: bool,
+    duration: Option<u64>,
+    languages: Vec<String>,
+    with_subtitles: bool,
+    subtitles: Vec<String>,
+    ad_filter: Option<AdFilter>,
+    ad_blocks: Vec<AdBlock>,
+    client: reqwest::Client,
 }
 
 impl M3U8Download {
+    async fn fetch_segment(
+        client: &reqwest::Client,
+        ts_uri: &str,
+        timeout: u64,
+        range: Option<SegmentRange>,
+    ) -> Result<Vec<u8>, DownloadError> {
+        let request = client.get(ts_uri);
+        let request = if timeout > 0 {
+            request.timeout(std::time::Duration::from_secs(timeout))
+        } else {
+            request
+        };
+        let request = if let Some(range) = range {
+            request.header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", range.offset, range.offset + range.length - 1),
+            )
+        } else {
+            request
+        };
+        let response = request.send().await?.error_for_status()?;
+        let status = response.status();
+        let bytes = response.bytes().await?.to_vec();
+        match range {
+            // server ignored the range header and sent the whole resource
+            Some(range) if status == reqwest::StatusCode::OK => {
+                let start = range.offset as usize;
+                let end = start + range.length as usize;
+                bytes
+                    .get(start..end)
+                    .map(|bytes| bytes.to_vec())
+                    .ok_or(DownloadError::Incomplete)
+            }
+            _ => Ok(bytes),
+        }
+    }
+
     async fn download_segment(
+        client: &reqwest::Client,
         ts_uri: &str,
         save_file: &str,
         timeout: u64,
         key: Option<AesKey>,
+        range: Option<SegmentRange>,
     ) -> Result<(), DownloadError> {
         if let Some(key) = key {
-            return Self::download_segment_with_key(ts_uri, save_file, timeout, key).await;
+            return Self::download_segment_with_key(client, ts_uri, save_file, timeout, key, range)
+                .await;
         }
         let mut file = File::create(save_file).await?;
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-        let request = client.get(ts_uri);
-        let request = if timeout > 0 {
-            request.timeout(std::time::Duration::from_secs(timeout))
-        } else {
-            request
-        };
-        let response = request.send().await?;
-        copy(&mut response.bytes().await?.chunk(), &mut file).await?;
+        let bytes = Self::fetch_segment(client, ts_uri, timeout, range).await?;
+        copy(&mut bytes.as_slice(), &mut file).await?;
         Ok(())
     }
 
     async fn download_segment_with_key(
+        client: &reqwest::Client,
         ts_uri: &str,
         save_file: &str,
         timeout: u64,
         key: AesKey,
+        range: Option<SegmentRange>,
     ) -> Result<(), DownloadError> {
         let mut file = File::create(save_file).await?;
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-        let request = client.get(ts_uri);
-        let request = if timeout > 0 {
-            request.timeout(std::time::Duration::from_secs(timeout))
-        } else {
-            request
-        };
-        let response = request.send().await?;
-        let bytes = response.bytes().await?.to_vec();
+        let bytes = Self::fetch_segment(client, ts_uri, timeout, range).await?;
         let mut out_buf = vec![0u8; bytes.len()];
 
         let iv = GenericArray::from_slice(&key.iv);
         let key = GenericArray::from_slice(&key.key);
 
         let mut ct = Aes128CbcDec::new(key, iv)
-            .decrypt_padded_b2b_mut::<Pkcs7>(bytes.as_slice(), out_buf.as_mut_slice()).unwrap();
+            .decrypt_padded_b2b_mut::<Pkcs7>(bytes.as_slice(), out_buf.as_mut_slice())
+            .map_err(|_| DownloadError::Decrypt)?;
 
         copy(&mut ct, &mut file).await?;
         Ok(())
@@ -122,32 +182,138 @@ impl M3U8Download {
             .to_string()
     }
 
+    async fn segment_key(
+        &mut self,
+        key: Option<Key>,
+        base_url: &Url,
+    ) -> Result<Option<AesKey>, DownloadError> {
+        let key = match key {
+            Some(key) => {
+                match key.method {
+                    KeyMethod::AES128 => {
+                        let iv = key.iv.unwrap_or("0x00000000000000000000000000000000".to_string());
+                        let iv = hex::decode(&iv.split("0x").last().unwrap()).unwrap();
+                        let key_uri = base_url.join(key.uri.unwrap().as_str())?;
+                        let key = self.get_aes_key(key_uri.as_str(), iv).await?;
+                        Some(key)
+                    },
+                    _ => None,
+                }
+            },
+            None => None,
+        };
+        Ok(key)
+    }
+
+    fn segment_range(
+        byte_range: Option<&m3u8_rs::ByteRange>,
+        uri: &Url,
+        next_offsets: &mut HashMap<String, u64>,
+    ) -> Result<Option<SegmentRange>, DownloadError> {
+        let Some(byte_range) = byte_range else {
+            return Ok(None);
+        };
+        // a range without bytes has no last byte to request
+        if byte_range.length == 0 {
+            return Err(DownloadError::EmptyRange(uri.to_string()));
+        }
+        // without an offset the sub-range starts where the previous one of the same resource ended
+        let offset = byte_range
+            .offset
+            .unwrap_or(*next_offsets.get(uri.as_str()).unwrap_or(&0));
+        next_offsets.insert(uri.to_string(), offset + byte_range.length);
+        Ok(Some(SegmentRange {
+            offset,
+            length: byte_range.length,
+        }))
+    }
+
+    fn segment_cache_name(uri: &Url, range: Option<SegmentRange>) -> String {
+        match range {
+            Some(range) => sha256::digest(format!("{}@{}-{}", uri, range.offset, range.length)),
+            None => sha256::digest(uri.as_str()),
+        }
+    }
+
+    /// Resolve the segments of a media playlist, carrying `#EXT-X-KEY`, `#EXT-X-MAP` and the end
+    /// of the last byte range of every resource forward, and emitting an init segment whenever
+    /// the map changes.
+    async fn resolve_segments(
+        &mut self,
+        segments: Vec<MediaSegment>,
+        base_url: &Url,
+        track: Track,
+        current_key: &mut Option<Key>,
+        current_map: &mut Option<Map>,
+        next_offsets: &mut HashMap<String, u64>,
+    ) -> Result<Vec<Segment>, DownloadError> {
+        let mut resolved = Vec::new();
+        for segment in segments {
+            if segment.key.is_some() {
+                *current_key = segment.key.clone();
+            }
+            let key = self.segment_key(current_key.clone(), base_url).await?;
+            if let Some(map) = segment.map {
+                if current_map.as_ref() != Some(&map) {
+                    let map_uri = base_url.join(map.uri.as_str())?;
+                    let range =
+                        Self::segment_range(map.byte_range.as_ref(), &map_uri, next_offsets)?;
+                    let cache_name = Self::segment_cache_name(&map_uri, range);
+                    let mut init =
+                        Segment::new(map_uri.as_str(), &self.join_path(&cache_name), key.clone(), track);
+                    init.range = range;
+                    init.init = true;
+                    resolved.push(init);
+                    current_map.replace(map);
+                }
+            }
+            let segment_uri = base_url.join(segment.uri.as_str())?;
+            let range =
+                Self::segment_range(segment.byte_range.as_ref(), &segment_uri, next_offsets)?;
+            let cache_name = Self::segment_cache_name(&segment_uri, range);
+            let mut resolved_segment =
+                Segment::new(segment_uri.as_str(), &self.join_path(&cache_name), key, track);
+            resolved_segment.range = range;
+            resolved_segment.duration = segment.duration;
+            resolved.push(resolved_segment);
+        }
+        Ok(resolved)
+    }
+
     async fn parse_media_playlist(
         &mut self,
         playlist: MediaPlaylist,
         base_url: &Url,
+        track: Track,
     ) -> Result<(), DownloadError> {
-        for segment in playlist.segments {
-            let key = match segment.key {
-                Some(key) => {
-                    match key.method {
-                        KeyMethod::AES128 => {
-                            let iv = key.iv.unwrap_or("0x00000000000000000000000000000000".to_string());
-                            let iv = hex::decode(&iv.split("0x").last().unwrap()).unwrap();
-                            let key_uri = base_url.join(key.uri.unwrap().as_str())?;
-                            let key = self.get_aes_key(key_uri.as_str(), iv).await?;
-                            Some(key)
-                        },
-                        _ => None,
-                    }
-                },
-                None => None,
-            };
-            let segment_uri = base_url.join(segment.uri.as_str())?;
-            let hash_name = sha256::digest(segment_uri.as_str());
-            let segment = Segment::new(segment_uri.as_str(), &self.join_path(&hash_name), key);
-            self.segments.push(segment);
+        if !playlist.end_list && playlist.playlist_type != Some(MediaPlaylistType::Vod) {
+            warn!("playlist {} has no end list, it may be live, use live mode to record it", base_url);
         }
+        let segments = match self.ad_filter.as_ref() {
+            Some(filter) => {
+                let (segments, blocks) = filter.apply(playlist.segments, base_url);
+                for block in blocks.iter() {
+                    info!(
+                        "drop ad segments [{}..{}] {:.1}s {} @ {}",
+                        block.start, block.end, block.duration, block.reason, base_url
+                    );
+                }
+                self.ad_blocks.extend(blocks);
+                segments
+            }
+            None => playlist.segments,
+        };
+        let segments = self
+            .resolve_segments(
+                segments,
+                base_url,
+                track,
+                &mut None,
+                &mut None,
+                &mut HashMap::new(),
+            )
+            .await?;
+        self.segments.extend(segments);
         Ok(())
     }
 
@@ -155,12 +321,8 @@ impl M3U8Download {
         if let Some(key) = self.aes_keys.get(uri) {
             return Ok(key.clone());
         }
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-        let request = client.get(uri);
-        let response = request.send().await?;
+        let request = self.client.get(uri);
+        let response = request.send().await?.error_for_status()?;
         let body = response.bytes().await?;
         let key = body.to_vec();
         let aes_key = AesKey { key, iv: iv.clone() };
@@ -168,42 +330,312 @@ impl M3U8Download {
         Ok(aes_key)
     }
 
+    async fn get_media_playlist(&self, url: &Url) -> Result<MediaPlaylist, DownloadError> {
+        let body = self
+            .client
+            .get(url.as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        let (_i, playlist) =
+            m3u8_rs::parse_media_playlist(&body).map_err(|_| DownloadError::URI)?;
+        Ok(playlist)
+    }
+
+    fn select_rendition<'b>(&self, renditions: &[&'b AlternativeMedia]) -> Option<&'b AlternativeMedia> {
+        for language in self.languages.iter() {
+            let rendition = renditions.iter().find(|rendition| {
+                rendition
+                    .language
+                    .as_ref()
+                    .is_some_and(|lang| lang.eq_ignore_ascii_case(language))
+            });
+            if let Some(rendition) = rendition {
+                return Some(rendition);
+            }
+        }
+        renditions
+            .iter()
+            .find(|rendition| rendition.default)
+            .or(renditions.first())
+            .copied()
+    }
+
     async fn parse_master_playlist(
         &mut self,
         playlist: MasterPlaylist,
         base_url: &Url,
     ) -> Result<(), DownloadError> {
-        let url = base_url.join(playlist.variants[0].uri.as_str())?;
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-        let body = client.get(url.as_str()).send().await?.bytes().await?;
-        let (_i, playlist) =
-            m3u8_rs::parse_media_playlist(&body).map_err(|_| DownloadError::URI)?;
-        self.parse_media_playlist(playlist, &url).await
+        let variant = &playlist.variants[0];
+        let url = base_url.join(variant.uri.as_str())?;
+        let media_playlist = self.get_media_playlist(&url).await?;
+        self.parse_media_playlist(media_playlist, &url, Track::Video)
+            .await?;
+
+        if let Some(group) = variant.audio.as_ref() {
+            let renditions = playlist
+                .alternatives
+                .iter()
+                .filter(|media| {
+                    media.media_type == AlternativeMediaType::Audio && &media.group_id == group
+                })
+                .collect::<Vec<_>>();
+            let uri = self
+                .select_rendition(&renditions)
+                .and_then(|rendition| rendition.uri.clone());
+            // renditions without uri are muxed into the variant stream
+            if let Some(uri) = uri {
+                let url = base_url.join(&uri)?;
+                info!("use audio rendition {}", url);
+                let media_playlist = self.get_media_playlist(&url).await?;
+                self.parse_media_playlist(media_playlist, &url, Track::Audio)
+                    .await?;
+            }
+        }
+
+        if let Some(group) = variant.subtitles.as_ref().filter(|_| self.with_subtitles) {
+            let renditions = playlist.alternatives.iter().filter(|media| {
+                media.media_type == AlternativeMediaType::Subtitles && &media.group_id == group
+            });
+            for rendition in renditions {
+                if let Some(uri) = rendition.uri.as_ref() {
+                    let url = base_url.join(uri)?;
+                    info!("use subtitle rendition {}", url);
+                    let media_playlist = self.get_media_playlist(&url).await?;
+                    let index = self.subtitles.len();
+                    self.subtitles.push(
+                        rendition
+                            .language
+                            .clone()
+                            .unwrap_or(rendition.name.clone())
+                            .replace(['/', '\\'], "_"),
+                    );
+                    self.parse_media_playlist(media_playlist, &url, Track::Subtitle(index))
+                        .await?;
+                }
+            }
+        }
+        Ok(())
     }
 
     async fn parse_playlist(&mut self, base_url: &Url) -> Result<(), DownloadError> {
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-        let body = client.get(base_url.as_str()).send().await?.bytes().await?;
+        let body = self
+            .client
+            .get(base_url.as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
         match m3u8_rs::parse_playlist(&body) {
             Result::Ok((_i, Playlist::MasterPlaylist(playlist))) => {
                 self.parse_master_playlist(playlist, base_url).await
             }
             Result::Ok((_i, Playlist::MediaPlaylist(playlist))) => {
-                self.parse_media_playlist(playlist, base_url).await
+                self.parse_media_playlist(playlist, base_url, Track::Video)
+                    .await
+            }
+            Result::Err(_) => Err(DownloadError::URI),
+        }
+    }
+
+    async fn fetch_media_playlist(&self, url: &Url) -> Result<(Url, MediaPlaylist), DownloadError> {
+        let body = self
+            .client
+            .get(url.as_str())
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        match m3u8_rs::parse_playlist(&body) {
+            Result::Ok((_i, Playlist::MasterPlaylist(playlist))) => {
+                let url = url.join(playlist.variants[0].uri.as_str())?;
+                let playlist = self.get_media_playlist(&url).await?;
+                Ok((url, playlist))
             }
+            Result::Ok((_i, Playlist::MediaPlaylist(playlist))) => Ok((url.clone(), playlist)),
             Result::Err(_) => Err(DownloadError::URI),
         }
     }
 
-    async fn combine_files(&self, dst_file: &str) -> Result<(), DownloadError> {
+    async fn record_segment(&self, segment: &Segment, output: &mut File) -> Result<(), DownloadError> {
+        let mut try_count = 0i64;
+        loop {
+            let result = Self::download_segment(
+                &self.client,
+                &segment.uri,
+                &segment.save_file,
+                self.timeout,
+                segment.key.clone(),
+                segment.range,
+            )
+            .await;
+            match result {
+                Ok(_) => break,
+                Err(e) => {
+                    warn!("record failed try_count={} err={} uri={}", try_count, e, segment.uri);
+                    if self.try_count >= 0 && try_count >= self.try_count {
+                        let _ = std::fs::remove_file(&segment.save_file);
+                        return Err(e);
+                    }
+                    try_count += 1;
+                }
+            }
+        }
+        let mut input = File::open(&segment.save_file).await?;
+        copy(&mut input, output).await?;
+        output.flush().await?;
+        std::fs::remove_file(&segment.save_file)?;
+        Ok(())
+    }
+
+    /// Fetch the media playlist of the next poll, retrying with a doubling delay since the live
+    /// edge of a stream is often unavailable for a moment.
+    async fn refresh_playlist(&self, url: &Url) -> Result<MediaPlaylist, DownloadError> {
+        let mut delay = std::time::Duration::from_secs(1);
+        let mut try_count = 0;
+        loop {
+            match self.get_media_playlist(url).await {
+                Ok(playlist) => return Ok(playlist),
+                Err(e) => {
+                    warn!("refresh failed try_count={} err={} uri={}", try_count, e, url);
+                    if try_count >= REFRESH_TRIES {
+                        return Err(e);
+                    }
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_REFRESH_DELAY);
+                    try_count += 1;
+                }
+            }
+        }
+    }
+
+    /// Record a live or event playlist, polling it every target duration and appending new
+    /// segments to the output until `#EXT-X-ENDLIST` shows up or the duration limit is reached.
+    /// When the stream breaks off, what was recorded until then is still saved.
+    pub async fn record(&mut self) -> Result<(), DownloadError> {
+        std::fs::create_dir_all(&self.cache_dir)?;
+        let url = Url::parse(self.uri.as_str())?;
+        let (media_url, playlist) = self.fetch_media_playlist(&url).await?;
+
+        let is_mp4 = std::path::Path::new(&self.save_file)
+            .extension()
+            .is_some_and(|extension| extension == "mp4");
+        let output_file = if is_mp4 {
+            self.join_path(&sha256::digest(&self.uri))
+        } else {
+            part_file(&self.save_file)
+        };
+        let mut output = File::create(&output_file).await?;
+        if is_mp4 {
+            self.cache_files.push(output_file.clone());
+        }
+        if self.pbar.is_none() {
+            self.pbar = Some(self.default_pbar());
+        }
+
+        let mut recorded = 0f32;
+        let result = self
+            .record_playlist(&media_url, playlist, &mut output, &mut recorded)
+            .await;
+        self.pbar.as_ref().unwrap().finish();
+        drop(output);
+        if let Err(e) = result.as_ref() {
+            if recorded <= 0.0 {
+                if !is_mp4 {
+                    let _ = std::fs::remove_file(&output_file);
+                }
+                return result;
+            }
+            warn!("record stopped after {:.1}s err={}, save what was recorded", recorded, e);
+        }
+        let part_file = part_file(&self.save_file);
+        if is_mp4 {
+            self.convert2mp4(&output_file, None, &part_file)?;
+        }
+        commit_file(&part_file, &self.save_file, Some(recorded as f64))?;
+        result
+    }
+
+    async fn record_playlist(
+        &mut self,
+        media_url: &Url,
+        mut playlist: MediaPlaylist,
+        output: &mut File,
+        recorded: &mut f32,
+    ) -> Result<(), DownloadError> {
+        let mut next_sequence: Option<u64> = None;
+        let mut discontinuity_sequence = playlist.discontinuity_sequence;
+        let (mut current_key, mut current_map) = (None, None);
+        // segments arrive one poll at a time, ranges without offset continue the previous one
+        let mut next_offsets = HashMap::new();
+        loop {
+            let last_sequence = playlist.media_sequence + playlist.segments.len() as u64;
+            if next_sequence.is_some_and(|sequence| last_sequence < sequence) {
+                warn!(
+                    "media sequence went back from {:?} to {}, stream restarted",
+                    next_sequence, playlist.media_sequence
+                );
+                next_sequence = None;
+            }
+            let segments = std::mem::take(&mut playlist.segments);
+            for (offset, segment) in segments.into_iter().enumerate() {
+                let sequence = playlist.media_sequence + offset as u64;
+                if let Some(next) = next_sequence {
+                    if sequence < next {
+                        continue;
+                    }
+                    if sequence > next {
+                        warn!("missed {} segments before sequence {}", sequence - next, sequence);
+                    }
+                }
+                if segment.discontinuity {
+                    discontinuity_sequence += 1;
+                    info!(
+                        "discontinuity @ {} discontinuity_sequence={}",
+                        sequence, discontinuity_sequence
+                    );
+                }
+                let duration = segment.duration;
+                let resolved = self
+                    .resolve_segments(
+                        vec![segment],
+                        media_url,
+                        Track::Video,
+                        &mut current_key,
+                        &mut current_map,
+                        &mut next_offsets,
+                    )
+                    .await?;
+                for segment in resolved.iter() {
+                    self.pbar.as_ref().unwrap().inc_length(1);
+                    self.record_segment(segment, output).await?;
+                    info!("record success @ {} uri={}", sequence, segment.uri);
+                    self.pbar.as_ref().unwrap().inc(1);
+                }
+                next_sequence = Some(sequence + 1);
+                *recorded += duration;
+                if self.duration.is_some_and(|duration| *recorded >= duration as f32) {
+                    info!("record duration {}s reached", recorded);
+                    return Ok(());
+                }
+            }
+            if playlist.end_list {
+                info!("playlist {} ended", media_url);
+                return Ok(());
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(playlist.target_duration.max(1)))
+                .await;
+            playlist = self.refresh_playlist(media_url).await?;
+        }
+    }
+
+    async fn combine_files(&self, dst_file: &str, track: Track) -> Result<(), DownloadError> {
         let mut output = File::create(dst_file).await?;
-        for segment in self.segments.iter() {
+        for segment in self.segments.iter().filter(|segment| segment.track == track) {
             let mut input = File::open(&segment.save_file).await?;
             copy(&mut input, &mut output).await?;
         }
@@ -211,42 +643,112 @@ impl M3U8Download {
         Ok(())
     }
 
-    fn convert2mp4(&self, cache_file: &str) -> Result<(), DownloadError> {
+    fn sibling_file(&self, suffix: &str) -> String {
+        std::path::Path::new(&self.save_file)
+            .with_extension(suffix)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    async fn save_subtitles(&self) -> Result<(), DownloadError> {
+        for (index, name) in self.subtitles.iter().enumerate() {
+            let mut content = String::new();
+            let segments = self
+                .segments
+                .iter()
+                .filter(|segment| segment.track == Track::Subtitle(index));
+            for (part, segment) in segments.enumerate() {
+                // CRLF segments would hide the blank line ending the header
+                let text = tokio::fs::read_to_string(&segment.save_file)
+                    .await?
+                    .replace("\r\n", "\n");
+                if part == 0 {
+                    content.push_str(&text);
+                } else {
+                    // every WebVTT segment repeats the header block, keep only the cues
+                    let cues = text.split_once("\n\n").map(|v| v.1).unwrap_or("");
+                    content.push_str(cues);
+                }
+                if !content.ends_with("\n\n") {
+                    content.push('\n');
+                }
+            }
+            let subtitle_file = self.sibling_file(&format!("{}.vtt", name));
+            tokio::fs::write(&subtitle_file, content).await?;
+            info!("save subtitle to {} success", subtitle_file);
+        }
+        Ok(())
+    }
+
+    fn convert2mp4(
+        &self,
+        cache_file: &str,
+        audio_file: Option<&str>,
+        output_file: &str,
+    ) -> Result<(), DownloadError> {
         let mut cmd = std::process::Command::new("ffmpeg");
+        cmd.arg("-y");
         cmd.arg("-i").arg(cache_file);
+        if let Some(audio_file) = audio_file {
+            cmd.arg("-i").arg(audio_file);
+            cmd.arg("-map").arg("0:v");
+            cmd.arg("-map").arg("1:a");
+        }
         cmd.arg("-c").arg("copy");
-        cmd.arg(self.save_file.as_str());
+        cmd.arg(output_file);
         let output = cmd.output().expect("failed to execute process");
         if !output.status.success() {
-            error!("convert to {} error", self.save_file);
+            error!("convert to {} error", output_file);
             return Err(DownloadError::from(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 format!("ffmpeg error: {}", String::from_utf8_lossy(&output.stderr)),
             )));
         }
-        info!("convert to {} success", self.save_file);
+        info!("convert to {} success", output_file);
         Ok(())
     }
 
-    async fn convert(&mut self) -> Result<(), DownloadError> {
+    async fn convert(&mut self, output_file: &str) -> Result<(), DownloadError> {
+        self.save_subtitles().await?;
+        let has_audio = self
+            .segments
+            .iter()
+            .any(|segment| segment.track == Track::Audio);
         let save_path = std::path::Path::new(&self.save_file);
-        let extension = save_path.extension();
-        if let Some(extension) = extension {
-            if let Some(extension) = extension.to_str() {
-                let hash_name = sha256::digest(&self.uri);
-                let cache_file = self.join_path(&hash_name);
-                match extension {
-                    "mp4" => {
-                        self.combine_files(&cache_file).await?;
-                        self.convert2mp4(&cache_file)?;
-                        self.cache_file.replace(cache_file);
-                        return Ok(());
-                    }
-                    _ => return self.combine_files(&self.save_file).await,
-                }
-            }
+        if save_path.extension().is_some_and(|extension| extension == "mp4") {
+            let hash_name = sha256::digest(&self.uri);
+            let cache_file = self.join_path(&hash_name);
+            self.combine_files(&cache_file, Track::Video).await?;
+            self.cache_files.push(cache_file.clone());
+            let audio_file = if has_audio {
+                let audio_file = self.join_path(&format!("{}.audio", hash_name));
+                self.combine_files(&audio_file, Track::Audio).await?;
+                self.cache_files.push(audio_file.clone());
+                Some(audio_file)
+            } else {
+                None
+            };
+            return self.convert2mp4(&cache_file, audio_file.as_deref(), output_file);
         }
-        self.combine_files(&self.save_file).await
+        self.combine_files(output_file, Track::Video).await?;
+        if has_audio {
+            let fragmented = self
+                .segments
+                .iter()
+                .any(|segment| segment.track == Track::Audio && segment.init);
+            let suffix = if fragmented { "audio.mp4" } else { "audio.ts" };
+            self.combine_files(&self.sibling_file(suffix), Track::Audio)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn expected_duration(&self) -> f64 {
+        self.segments
+            .iter()
+            .filter(|segment| segment.track == Track::Video)
+            .map(|segment| segment.duration as f64)
+            .sum()
     }
 
     fn check_integrity(&self) -> bool {
@@ -269,7 +771,30 @@ impl M3U8Download {
         pbar
     }
 
+    /// Parse the playlist and report the ad segments the filter would drop, without downloading.
+    pub async fn ad_report(&mut self) -> Result<AdReport, DownloadError> {
+        if self.ad_filter.is_none() {
+            self.ad_filter.replace(AdFilter::new(Vec::new()));
+        }
+        std::fs::create_dir_all(&self.cache_dir)?;
+        let url = Url::parse(self.uri.as_str())?;
+        self.parse_playlist(&url).await?;
+        let dropped = self
+            .ad_blocks
+            .iter()
+            .map(|block| block.end - block.start)
+            .sum::<usize>();
+        let kept = self.segments.iter().filter(|segment| !segment.init).count();
+        Ok(AdReport {
+            total: kept + dropped,
+            blocks: self.ad_blocks.clone(),
+        })
+    }
+
     pub async fn download(&mut self) -> Result<(), DownloadError> {
+        if self.live {
+            return self.record().await;
+        }
         std::fs::create_dir_all(&self.cache_dir)?;
         let url = Url::parse(self.uri.as_str())?;
         self.parse_playlist(&url).await?;
@@ -295,11 +820,19 @@ impl M3U8Download {
             };
             if !exists || self.ignore_cache {
                 let semaphore = semaphore.clone();
-                let (uri, file, timeout, key) =
-                    (segment.uri.clone(), segment.save_file.clone(), self.timeout, segment.key.clone());
+                let (client, uri, file, timeout, key, range) = (
+                    self.client.clone(),
+                    segment.uri.clone(),
+                    segment.save_file.clone(),
+                    self.timeout,
+                    segment.key.clone(),
+                    segment.range,
+                );
                 tasks.spawn(async move {
                     let _permit = semaphore.acquire().await.unwrap();
-                    (index, Self::download_segment(&uri, &file, timeout, key).await)
+                    let result =
+                        Self::download_segment(&client, &uri, &file, timeout, key, range).await;
+                    (index, result)
                 });
             } else {
                 info!("use cache file @ {} uri={}", index, segment.uri);
@@ -322,10 +855,19 @@ impl M3U8Download {
                             "try download @ {} try_count={} uri={}",
                             index, segment.try_count, segment.uri
                         );
-                        let (uri, file, timeout , key) =
-                            (segment.uri.clone(), segment.save_file.clone(), self.timeout, segment.key.clone());
+                        let (client, uri, file, timeout, key, range) = (
+                            self.client.clone(),
+                            segment.uri.clone(),
+                            segment.save_file.clone(),
+                            self.timeout,
+                            segment.key.clone(),
+                            segment.range,
+                        );
                         tasks.spawn(async move {
-                            (index, Self::download_segment(&uri, &file, timeout, key).await)
+                            let result =
+                                Self::download_segment(&client, &uri, &file, timeout, key, range)
+                                    .await;
+                            (index, result)
                         });
                         segment.try_count += 1;
                     } else {
@@ -347,12 +889,13 @@ impl M3U8Download {
             }
         }
         self.pbar.as_ref().unwrap().finish();
-        self.convert().await?;
-        if self.check_integrity() {
-            Ok(())
-        } else {
-            Err(DownloadError::Incomplete)
+        if !self.check_integrity() {
+            return Err(DownloadError::Incomplete);
         }
+        let part_file = part_file(&self.save_file);
+        self.convert(&part_file).await?;
+        commit_file(&part_file, &self.save_file, Some(self.expected_duration()))?;
+        Ok(())
     }
 }
 
@@ -365,7 +908,7 @@ impl Drop for M3U8Download {
                 }
             }
         }
-        if let Some(cache_file) = self.cache_file.as_ref() {
+        for cache_file in self.cache_files.iter() {
             if let Err(e) = std::fs::remove_file(cache_file) {
                 warn!("remove tmp file {} err={}", cache_file, e);
             }
@@ -382,6 +925,13 @@ pub struct M3U8DownloadBuilder {
     ignore_cache: bool,
     pbar: Option<ProgressBar>,
     climit: usize,
+    live: bool,
+    duration: Option<u64>,
+    languages: Vec<String>,
+    with_subtitles: bool,
+    filter_ads: bool,
+    ad_patterns: Vec<String>,
+    client: ClientOptions,
 }
 
 impl M3U8DownloadBuilder {
@@ -395,6 +945,13 @@ impl M3U8DownloadBuilder {
             ignore_cache: false,
             pbar: None,
             climit: 32,
+            live: false,
+            duration: None,
+            languages: Vec::new(),
+            with_subtitles: true,
+            filter_ads: false,
+            ad_patterns: Vec::new(),
+            client: ClientOptions::new(),
         }
     }
 
@@ -443,6 +1000,48 @@ impl M3U8DownloadBuilder {
         self
     }
 
+    #[allow(unused)]
+    pub fn live(&mut self, live: bool) -> &mut Self {
+        self.live = live;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn duration(&mut self, second: Option<u64>) -> &mut Self {
+        self.duration = second;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn languages(&mut self, languages: Vec<String>) -> &mut Self {
+        self.languages = languages;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn subtitles(&mut self, with_subtitles: bool) -> &mut Self {
+        self.with_subtitles = with_subtitles;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn filter_ads(&mut self, filter: bool) -> &mut Self {
+        self.filter_ads = filter;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn ad_patterns(&mut self, patterns: Vec<String>) -> &mut Self {
+        self.ad_patterns = patterns;
+        self
+    }
+
+    /// Send the playlist, key and segment requests with the headers and proxy of `options`.
+    pub fn client(&mut self, options: ClientOptions) -> &mut Self {
+        self.client = options;
+        self
+    }
+
     pub fn build(&mut self) -> M3U8Download {
         M3U8Download {
             uri: self.uri.clone(),
@@ -452,10 +1051,22 @@ impl M3U8DownloadBuilder {
             try_count: self.try_count,
             timeout: self.timeout,
             ignore_cache: self.ignore_cache,
-            cache_file: None,
+            cache_files: Vec::new(),
             pbar: self.pbar.take(),
             climit: self.climit,
             aes_keys: HashMap::new(),
+            live: self.live,
+            duration: self.duration,
+            languages: self.languages.clone(),
+            with_subtitles: self.with_subtitles,
+            subtitles: Vec::new(),
+            ad_filter: if self.filter_ads {
+                Some(AdFilter::new(self.ad_patterns.clone()))
+            } else {
+                None
+            },
+            ad_blocks: Vec::new(),
+            client: self.client.build(),
         }
     }
 }
@@ -524,3 +1135,297 @@ async fn test_download_map() -> Result<(), DownloadError> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+fn local_downloader(uri: &str, dir: &std::path::Path, save_file: &str) -> M3U8Download {
+    M3U8DownloadBuilder::new()
+        .uri(uri)
+        .cache_dir(dir.join("cache").to_string_lossy())
+        .save_file(dir.join(save_file).to_string_lossy())
+        .timeout(1)
+        .try_count(3)
+        .pbar(ProgressBar::hidden())
+        .build()
+}
+
+#[tokio::test()]
+async fn test_download_local_faults() {
+    use super::testserver::{media_playlist, test_dir, ts_segment, Fault, TestServer};
+    use std::time::Duration;
+
+    let server = TestServer::start().await;
+    let segments = (0..4).map(|i| ts_segment(i, 60)).collect::<Vec<_>>();
+    for (i, segment) in segments.iter().enumerate() {
+        server.route(&format!("/hls/{}.ts", i), segment.clone());
+    }
+    server
+        .route(
+            "/hls/index.m3u8",
+            media_playlist(&[("0.ts", 1.0), ("1.ts", 1.0), ("2.ts", 1.0), ("3.ts", 1.0)], None),
+        )
+        .fault("/hls/0.ts", Fault::Status(500), 2)
+        .fault("/hls/1.ts", Fault::Truncate(1000), 1)
+        .fault("/hls/2.ts", Fault::Timeout(Duration::from_secs(3)), 1)
+        .fault("/hls/3.ts", Fault::Slow(Duration::from_millis(50)), 1);
+
+    let dir = test_dir("m3u8-faults");
+    let mut downloader = local_downloader(&server.url("/hls/index.m3u8"), &dir, "faults.ts");
+    downloader.download().await.unwrap();
+    assert_eq!(std::fs::read(dir.join("faults.ts")).unwrap(), segments.concat());
+    assert_eq!(server.hits("/hls/0.ts"), 3);
+    assert_eq!(server.hits("/hls/1.ts"), 2);
+    assert_eq!(server.hits("/hls/2.ts"), 2);
+    assert_eq!(server.hits("/hls/3.ts"), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test()]
+async fn test_download_local_partial() {
+    use super::testserver::{media_playlist, test_dir, ts_segment, Fault, TestServer};
+
+    let server = TestServer::start().await;
+    server
+        .route("/hls/index.m3u8", media_playlist(&[("0.ts", 1.0), ("1.ts", 1.0)], None))
+        .route("/hls/0.ts", ts_segment(0, 60))
+        .route("/hls/1.ts", ts_segment(1, 60))
+        .fault("/hls/1.ts", Fault::Status(404), 4);
+
+    let dir = test_dir("m3u8-partial");
+    let mut downloader = local_downloader(&server.url("/hls/index.m3u8"), &dir, "partial.ts");
+    assert!(matches!(
+        downloader.download().await,
+        Err(DownloadError::Incomplete)
+    ));
+    assert_eq!(server.hits("/hls/1.ts"), 4);
+    assert!(!dir.join("partial.ts").exists());
+    assert!(!dir.join("partial.part.ts").exists());
+
+    // a second attempt only fetches the missing segment
+    let mut downloader = local_downloader(&server.url("/hls/index.m3u8"), &dir, "partial.ts");
+    downloader.download().await.unwrap();
+    assert_eq!(server.hits("/hls/0.ts"), 1);
+    assert!(dir.join("partial.ts").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test()]
+async fn test_download_local_encrypted() {
+    use super::testserver::{encrypt, media_playlist, test_dir, ts_segment, TestServer};
+
+    let server = TestServer::start().await;
+    let (key, iv) = ([0x2au8; 16], [0x10u8; 16]);
+    let segments = (0..3).map(|i| ts_segment(i, 60)).collect::<Vec<_>>();
+    for (i, segment) in segments.iter().enumerate() {
+        server.route(&format!("/enc/{}.ts", i), encrypt(segment, &key, &iv));
+    }
+    server
+        .route("/keys/video.key", key.to_vec())
+        .route(
+            "/enc/index.m3u8",
+            media_playlist(
+                &[("0.ts", 1.0), ("1.ts", 1.0), ("2.ts", 1.0)],
+                Some(("../keys/video.key", &iv)),
+            ),
+        );
+
+    let dir = test_dir("m3u8-encrypted");
+    let mut downloader = local_downloader(&server.url("/enc/index.m3u8"), &dir, "encrypted.ts");
+    downloader.download().await.unwrap();
+    assert_eq!(std::fs::read(dir.join("encrypted.ts")).unwrap(), segments.concat());
+    assert_eq!(server.hits("/keys/video.key"), 1);
+
+    // a wrong key must fail the segments instead of writing garbage
+    server.route("/keys/video.key", [0u8; 16].to_vec());
+    let mut downloader = local_downloader(&server.url("/enc/index.m3u8"), &dir, "wrong.ts");
+    downloader.ignore_cache = true;
+    assert!(downloader.download().await.is_err());
+    assert!(!dir.join("wrong.ts").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test()]
+async fn test_download_local_master() {
+    use super::testserver::{media_playlist, test_dir, ts_segment, TestServer};
+
+    let server = TestServer::start().await;
+    let master = r#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aud",NAME="English",LANGUAGE="en",URI="audio/en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aud",NAME="中文",LANGUAGE="zh",DEFAULT=YES,URI="audio/zh.m3u8"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID="subs",NAME="English",LANGUAGE="en",URI="subs/en.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=800000,AUDIO="aud",SUBTITLES="subs"
+video/index.m3u8
+"#;
+    let video = (0..2).map(|i| ts_segment(i, 60)).collect::<Vec<_>>();
+    server
+        .route("/master.m3u8", master)
+        .route("/video/index.m3u8", media_playlist(&[("0.ts", 1.0), ("1.ts", 1.0)], None))
+        .route("/video/0.ts", video[0].clone())
+        .route("/video/1.ts", video[1].clone())
+        .route("/audio/en.m3u8", media_playlist(&[("en.ts", 2.0)], None))
+        .route("/audio/en.ts", ts_segment(0xe, 10))
+        .route("/audio/zh.m3u8", media_playlist(&[("zh.ts", 2.0)], None))
+        .route("/audio/zh.ts", ts_segment(0xc, 10))
+        .route(
+            "/subs/en.m3u8",
+            media_playlist(&[("0.vtt", 1.0), ("1.vtt", 1.0), ("2.vtt", 1.0)], None),
+        )
+        .route("/subs/0.vtt", "WEBVTT\n\n00:00.000 --> 00:01.000\none\n")
+        .route("/subs/1.vtt", "WEBVTT\n\n00:01.000 --> 00:02.000\ntwo\n")
+        .route("/subs/2.vtt", "WEBVTT\r\n\r\n00:02.000 --> 00:03.000\r\nthree\r\n");
+
+    let dir = test_dir("m3u8-master");
+    let mut downloader = local_downloader(&server.url("/master.m3u8"), &dir, "master.ts");
+    downloader.languages = vec!["en".to_string()];
+    downloader.download().await.unwrap();
+    assert_eq!(std::fs::read(dir.join("master.ts")).unwrap(), video.concat());
+    assert_eq!(std::fs::read(dir.join("master.audio.ts")).unwrap(), ts_segment(0xe, 10));
+    assert_eq!(server.hits("/audio/zh.ts"), 0);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("master.en.vtt")).unwrap(),
+        "WEBVTT\n\n00:00.000 --> 00:01.000\none\n\n00:01.000 --> 00:02.000\ntwo\n\n\
+         00:02.000 --> 00:03.000\nthree\n\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test()]
+async fn test_download_local_byterange() {
+    use super::testserver::{mp4_fragment, mp4_init, test_dir, TestServer};
+
+    let init = mp4_init(3);
+    let fragments = (0..3).map(|i| mp4_fragment(i, 10 * 1024)).collect::<Vec<_>>();
+    let file = [init.clone(), fragments.concat()].concat();
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-TARGETDURATION:1\n#EXT-X-MAP:URI=\"video.mp4\",BYTERANGE=\"{}@0\"\n",
+        init.len()
+    );
+    let mut offset = init.len();
+    for fragment in fragments.iter() {
+        playlist.push_str(&format!(
+            "#EXTINF:1.0,\n#EXT-X-BYTERANGE:{}@{}\nvideo.mp4\n",
+            fragment.len(),
+            offset
+        ));
+        offset += fragment.len();
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+
+    // the same playlist from a server honouring ranges and from one sending the whole file
+    for ignore_ranges in [false, true] {
+        let server = TestServer::start().await;
+        server
+            .route("/index.m3u8", playlist.clone())
+            .route("/video.mp4", file.clone());
+        if ignore_ranges {
+            server.ignore_ranges("/video.mp4");
+        }
+        let dir = test_dir(&format!("m3u8-byterange-{}", ignore_ranges));
+        let mut downloader = local_downloader(&server.url("/index.m3u8"), &dir, "ranged.m4s");
+        downloader.download().await.unwrap();
+        assert_eq!(std::fs::read(dir.join("ranged.m4s")).unwrap(), file);
+        assert_eq!(server.hits("/video.mp4"), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // an empty range is refused before anything is requested
+    let server = TestServer::start().await;
+    server
+        .route(
+            "/index.m3u8",
+            "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXTINF:1.0,\n#EXT-X-BYTERANGE:0@10\nvideo.mp4\n\
+             #EXT-X-ENDLIST\n",
+        )
+        .route("/video.mp4", file.clone());
+    let dir = test_dir("m3u8-byterange-empty");
+    let mut downloader = local_downloader(&server.url("/index.m3u8"), &dir, "empty.m4s");
+    assert!(matches!(
+        downloader.download().await,
+        Err(DownloadError::EmptyRange(_))
+    ));
+    assert_eq!(server.hits("/video.mp4"), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test()]
+async fn test_record_local() {
+    use super::testserver::{live_playlist, test_dir, ts_segment, Fault, TestServer};
+    use std::time::Duration;
+
+    let server = TestServer::start().await;
+    let segments = (0..4).map(|i| ts_segment(i, 60)).collect::<Vec<_>>();
+    for (i, segment) in segments.iter().enumerate() {
+        server.route(&format!("/live/{}.ts", i), segment.clone());
+    }
+    // the window grows, slides and then ends
+    server
+        .route(
+            "/live/index.m3u8",
+            live_playlist(0, &[("0.ts", 1.0), ("1.ts", 1.0)], false),
+        )
+        .update(
+            "/live/index.m3u8",
+            live_playlist(0, &[("0.ts", 1.0), ("1.ts", 1.0), ("2.ts", 1.0)], false),
+        )
+        .update(
+            "/live/index.m3u8",
+            live_playlist(1, &[("1.ts", 1.0), ("2.ts", 1.0), ("3.ts", 1.0)], true),
+        );
+
+    let dir = test_dir("m3u8-record");
+    let mut downloader = local_downloader(&server.url("/live/index.m3u8"), &dir, "live.ts");
+    let faults = async {
+        // one failed refresh is retried instead of ending the recording
+        while server.hits("/live/index.m3u8") == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        server.fault("/live/index.m3u8", Fault::Status(503), 1);
+    };
+    let (result, _) = tokio::join!(downloader.record(), faults);
+    result.unwrap();
+    assert_eq!(std::fs::read(dir.join("live.ts")).unwrap(), segments.concat());
+    assert_eq!(server.hits("/live/index.m3u8"), 4);
+    assert_eq!(server.hits("/live/1.ts"), 1);
+
+    // a segment that never arrives ends the recording, keeping what came before
+    server
+        .route(
+            "/broken/index.m3u8",
+            live_playlist(0, &[("../live/0.ts", 1.0)], false),
+        )
+        .update(
+            "/broken/index.m3u8",
+            live_playlist(0, &[("../live/0.ts", 1.0), ("gone.ts", 1.0)], false),
+        );
+    let mut downloader = local_downloader(&server.url("/broken/index.m3u8"), &dir, "broken.ts");
+    assert!(downloader.record().await.is_err());
+    assert_eq!(std::fs::read(dir.join("broken.ts")).unwrap(), segments[0]);
+    assert!(!dir.join("broken.part.ts").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test()]
+async fn test_record_local_byterange() {
+    use super::testserver::{test_dir, ts_segment, TestServer};
+
+    let server = TestServer::start().await;
+    let segments = (0..3).map(|i| ts_segment(i, 60)).collect::<Vec<_>>();
+    let length = segments[0].len();
+    // only the first range has an offset, the others continue it across polls
+    let head = "#EXTM3U\n#EXT-X-VERSION:4\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:0\n";
+    let first = format!("#EXTINF:1.0,\n#EXT-X-BYTERANGE:{}@0\nstream.ts\n", length);
+    let next = format!("#EXTINF:1.0,\n#EXT-X-BYTERANGE:{}\nstream.ts\n", length);
+    server
+        .route("/stream.ts", segments.concat())
+        .route("/index.m3u8", format!("{}{}", head, first))
+        .update("/index.m3u8", format!("{}{}{}", head, first, next))
+        .update(
+            "/index.m3u8",
+            format!("{}{}{}{}#EXT-X-ENDLIST\n", head, first, next, next),
+        );
+
+    let dir = test_dir("m3u8-record-byterange");
+    let mut downloader = local_downloader(&server.url("/index.m3u8"), &dir, "ranged.ts");
+    downloader.record().await.unwrap();
+    assert_eq!(std::fs::read(dir.join("ranged.ts")).unwrap(), segments.concat());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
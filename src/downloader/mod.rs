@@ -1,7 +1,14 @@
+mod adfilter;
+mod client;
 pub mod error;
 mod m3u8;
 mod mp4;
+#[cfg(test)]
+pub mod testserver;
+mod verify;
 
+pub use client::ClientOptions;
 pub use error::DownloadError;
 pub use m3u8::M3U8DownloadBuilder;
 pub use mp4::MP4DownloadBuilder;
+pub use verify::verify_file;
@@ -1,4 +1,6 @@
+use super::client::ClientOptions;
 use super::error::DownloadError;
+use super::verify::{commit_file, part_file};
 use futures::stream::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use tokio::fs::OpenOptions;
@@ -11,6 +13,7 @@ pub struct MP4Download {
     try_count: i64,
     timeout: u64,
     pbar: Option<ProgressBar>,
+    client: reqwest::Client,
 }
 
 impl MP4Download {
@@ -19,9 +22,8 @@ impl MP4Download {
     }
 
     async fn get_total_size(&self) -> Result<Option<u64>, DownloadError> {
-        let client = reqwest::Client::new();
         let total_size = {
-            let resp = client.head(&self.uri).send().await?;
+            let resp = self.client.head(&self.uri).send().await?;
             if resp.status().is_success() {
                 resp.headers()
                     .get(reqwest::header::CONTENT_LENGTH)
@@ -46,19 +48,20 @@ impl MP4Download {
     }
 
     async fn download_task(&mut self, total_size: u64) -> Result<(), DownloadError> {
-        let path = std::path::Path::new(&self.save_file);
+        let part_file = part_file(&self.save_file);
+        let path = std::path::Path::new(&part_file);
         if path.exists() {
             std::fs::remove_file(path).unwrap();
         }
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
-
-        let request = client.get(&self.uri);
+        let request = self.client.get(&self.uri);
+        let request = if self.timeout > 0 {
+            request.timeout(std::time::Duration::from_secs(self.timeout))
+        } else {
+            request
+        };
         let mut download_size = 0u64;
 
-        let source = request.send().await?;
+        let source = request.send().await?.error_for_status()?;
         let mut file = OpenOptions::new()
             .create(true)
             .append(true)
@@ -74,11 +77,15 @@ impl MP4Download {
                 .unwrap()
                 .set_position(Self::byte2mb(download_size));
         }
+        if total_size > 0 && download_size != total_size {
+            return Err(DownloadError::Incomplete);
+        }
         self.pbar
             .as_ref()
             .unwrap()
             .set_position(Self::byte2mb(total_size));
         self.pbar.as_ref().unwrap().finish();
+        commit_file(&part_file, &self.save_file, None)?;
         Ok(())
     }
 
@@ -119,6 +126,7 @@ pub struct MP4DownloadBuilder {
     try_count: i64,
     timeout: u64,
     pbar: Option<ProgressBar>,
+    client: ClientOptions,
 }
 
 impl MP4DownloadBuilder {
@@ -129,6 +137,7 @@ impl MP4DownloadBuilder {
             try_count: -1,
             timeout: 0,
             pbar: None,
+            client: ClientOptions::new(),
         }
     }
 
@@ -160,6 +169,12 @@ impl MP4DownloadBuilder {
         self
     }
 
+    /// Send the requests with the headers and proxy of `options`.
+    pub fn client(&mut self, options: ClientOptions) -> &mut Self {
+        self.client = options;
+        self
+    }
+
     pub fn build(&mut self) -> MP4Download {
         MP4Download {
             uri: self.uri.clone(),
@@ -167,6 +182,61 @@ impl MP4DownloadBuilder {
             try_count: self.try_count,
             timeout: self.timeout,
             pbar: self.pbar.take(),
+            client: self.client.build(),
         }
     }
 }
+
+#[tokio::test()]
+async fn test_download_local() {
+    use super::testserver::{mp4_fragment, mp4_init, test_dir, Fault, TestServer};
+    use std::time::Duration;
+
+    let server = TestServer::start().await;
+    let movie = [mp4_init(2), mp4_fragment(1, 64 * 1024)].concat();
+    server
+        .route("/movie.mp4", movie.clone())
+        .fault("/movie.mp4", Fault::Truncate(100), 1)
+        .fault("/movie.mp4", Fault::Status(503), 1)
+        .fault("/movie.mp4", Fault::Timeout(Duration::from_secs(3)), 1)
+        .fault("/movie.mp4", Fault::Slow(Duration::from_millis(50)), 1);
+
+    let dir = test_dir("mp4-download");
+    let save_file = dir.join("movie.mp4").to_string_lossy().to_string();
+    let mut downloader = MP4DownloadBuilder::new()
+        .uri(server.url("/movie.mp4"))
+        .save_file(&save_file)
+        .timeout(1)
+        .try_count(3)
+        .pbar(ProgressBar::hidden())
+        .build();
+    downloader.download().await.unwrap();
+    assert_eq!(std::fs::read(&save_file).unwrap(), movie);
+    // the size probe and four downloads
+    assert_eq!(server.hits("/movie.mp4"), 5);
+
+    server.fault("/movie.mp4", Fault::Status(500), 2);
+    let save_file = dir.join("failed.mp4").to_string_lossy().to_string();
+    let mut downloader = MP4DownloadBuilder::new()
+        .uri(server.url("/movie.mp4"))
+        .save_file(&save_file)
+        .try_count(1)
+        .pbar(ProgressBar::hidden())
+        .build();
+    assert!(matches!(
+        downloader.download().await,
+        Err(DownloadError::Reqwest(_))
+    ));
+    assert!(!std::path::Path::new(&save_file).exists());
+
+    let mut downloader = MP4DownloadBuilder::new()
+        .uri(server.url("/missing.mp4"))
+        .save_file(&save_file)
+        .pbar(ProgressBar::hidden())
+        .build();
+    assert!(matches!(
+        downloader.download().await,
+        Err(DownloadError::GetContentSize)
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,379 @@
+use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+
+const TS_PACKET_SIZE: usize = 188;
+const SLOW_CHUNKS: usize = 4;
+
+/// A fault injected into the answer of a GET request.
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    /// Answer with this status and an error page.
+    Status(u16),
+    /// Wait this long before answering.
+    Timeout(Duration),
+    /// Announce the full length but close the connection after this many body bytes.
+    Truncate(usize),
+    /// Send the body in a few chunks with this delay between them.
+    Slow(Duration),
+}
+
+struct Route {
+    body: Vec<u8>,
+    content_type: &'static str,
+    ranges: bool,
+}
+
+#[derive(Default)]
+struct State {
+    routes: HashMap<String, Route>,
+    faults: HashMap<String, VecDeque<Fault>>,
+    updates: HashMap<String, VecDeque<Vec<u8>>>,
+    hits: HashMap<String, usize>,
+}
+
+/// In-process HTTP/1.1 server for the downloader tests, serving fixed bodies by path with byte
+/// range support and injected faults. Every connection carries a single request.
+pub struct TestServer {
+    host: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(Self::serve(stream, server_state.clone()));
+            }
+        });
+        Self { host, state }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.host, path)
+    }
+
+    pub fn route<T: Into<Vec<u8>>>(&self, path: &str, body: T) -> &Self {
+        let content_type = match path.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("m3u8") => "application/vnd.apple.mpegurl",
+            Some("ts") => "video/mp2t",
+            Some("mp4") | Some("m4s") => "video/mp4",
+            _ => "application/octet-stream",
+        };
+        let route = Route {
+            body: body.into(),
+            content_type,
+            ranges: true,
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .routes
+            .insert(path.to_string(), route);
+        self
+    }
+
+    /// Answer range requests of `path` with the whole body, like servers without range support.
+    pub fn ignore_ranges(&self, path: &str) -> &Self {
+        if let Some(route) = self.state.lock().unwrap().routes.get_mut(path) {
+            route.ranges = false;
+        }
+        self
+    }
+
+    /// Inject `fault` into the next `times` GET requests of `path`. HEAD requests are answered
+    /// normally so size probes do not use up the faults.
+    pub fn fault(&self, path: &str, fault: Fault, times: usize) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        let faults = state.faults.entry(path.to_string()).or_default();
+        faults.extend(std::iter::repeat_n(fault, times));
+        self
+    }
+
+    /// Serve `body` once the current body of `path` was served, like a live playlist that grows
+    /// between polls. Queued bodies are served in turn, the last one from then on.
+    pub fn update<T: Into<Vec<u8>>>(&self, path: &str, body: T) -> &Self {
+        let mut state = self.state.lock().unwrap();
+        let updates = state.updates.entry(path.to_string()).or_default();
+        updates.push_back(body.into());
+        self
+    }
+
+    /// Number of requests `path` received.
+    pub fn hits(&self, path: &str) -> usize {
+        *self.state.lock().unwrap().hits.get(path).unwrap_or(&0)
+    }
+
+    async fn read_head(stream: &mut TcpStream) -> Option<String> {
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buf).await.ok()?;
+            if read == 0 {
+                return None;
+            }
+            head.extend_from_slice(&buf[..read]);
+        }
+        Some(String::from_utf8_lossy(&head).to_string())
+    }
+
+    fn parse_range(value: &str, len: usize) -> Option<(usize, usize)> {
+        let (start, end) = value.trim().strip_prefix("bytes=")?.split_once('-')?;
+        let start = start.parse::<usize>().ok()?;
+        let end = match end {
+            "" => len - 1,
+            end => end.parse::<usize>().ok()?.min(len - 1),
+        };
+        (start <= end && end < len).then_some((start, end))
+    }
+
+    async fn serve(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+        let Some(head) = Self::read_head(&mut stream).await else {
+            return;
+        };
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let range = lines
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("range"))
+            .map(|(_, value)| value.to_string());
+
+        let (route, fault) = {
+            let mut state = state.lock().unwrap();
+            *state.hits.entry(path.clone()).or_default() += 1;
+            let fault = match method.as_str() {
+                "GET" => state
+                    .faults
+                    .get_mut(&path)
+                    .and_then(|faults| faults.pop_front()),
+                _ => None,
+            };
+            let route = state
+                .routes
+                .get(&path)
+                .map(|route| (route.body.clone(), route.content_type, route.ranges));
+            if route.is_some() && fault.is_none() && method == "GET" {
+                let update = state
+                    .updates
+                    .get_mut(&path)
+                    .and_then(|updates| updates.pop_front());
+                if let (Some(update), Some(route)) = (update, state.routes.get_mut(&path)) {
+                    route.body = update;
+                }
+            }
+            (route, fault)
+        };
+
+        let (status, content_type, body) = match (route, fault) {
+            (_, Some(Fault::Status(status))) => (status, "text/html", b"error".to_vec()),
+            (None, _) => (404, "text/html", b"not found".to_vec()),
+            (Some((body, content_type, ranges)), _) => {
+                match range
+                    .filter(|_| ranges)
+                    .and_then(|r| Self::parse_range(&r, body.len()))
+                {
+                    Some((start, end)) => {
+                        let header = format!(
+                            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n",
+                            start,
+                            end,
+                            body.len()
+                        );
+                        let body = body[start..=end].to_vec();
+                        Self::respond(&mut stream, header, content_type, &method, body, fault)
+                            .await;
+                        return;
+                    }
+                    None => (200, content_type, body),
+                }
+            }
+        };
+        let header = format!("HTTP/1.1 {} Test\r\n", status);
+        Self::respond(&mut stream, header, content_type, &method, body, fault).await;
+    }
+
+    async fn respond(
+        stream: &mut TcpStream,
+        mut header: String,
+        content_type: &str,
+        method: &str,
+        body: Vec<u8>,
+        fault: Option<Fault>,
+    ) {
+        if let Some(Fault::Timeout(delay)) = fault {
+            tokio::time::sleep(delay).await;
+        }
+        header.push_str(&format!(
+            "Content-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n\r\n",
+            content_type,
+            body.len()
+        ));
+        if stream.write_all(header.as_bytes()).await.is_err() || method == "HEAD" {
+            return;
+        }
+        let _ = match fault {
+            Some(Fault::Truncate(length)) => {
+                stream.write_all(&body[..length.min(body.len())]).await
+            }
+            Some(Fault::Slow(delay)) => {
+                let chunk_size = body.len().div_ceil(SLOW_CHUNKS).max(1);
+                for chunk in body.chunks(chunk_size) {
+                    if stream.write_all(chunk).await.is_err() {
+                        return;
+                    }
+                    let _ = stream.flush().await;
+                    tokio::time::sleep(delay).await;
+                }
+                Ok(())
+            }
+            _ => stream.write_all(&body).await,
+        };
+        let _ = stream.shutdown().await;
+    }
+}
+
+/// A fake MPEG-TS segment of `packets` packets whose payload bytes are all `fill`.
+pub fn ts_segment(fill: u8, packets: usize) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(TS_PACKET_SIZE * packets);
+    for _ in 0..packets {
+        let mut packet = vec![fill; TS_PACKET_SIZE];
+        packet[0] = 0x47;
+        segment.extend(packet);
+    }
+    segment
+}
+
+/// A media playlist of `segments` with their durations, encrypted with the key at `key_uri`
+/// when given.
+pub fn media_playlist(segments: &[(&str, f32)], key_uri: Option<(&str, &[u8; 16])>) -> String {
+    let target = segments
+        .iter()
+        .map(|(_, d)| d.ceil() as u64)
+        .max()
+        .unwrap_or(1);
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n",
+        target
+    );
+    if let Some((uri, iv)) = key_uri {
+        playlist.push_str(&format!(
+            "#EXT-X-KEY:METHOD=AES-128,URI=\"{}\",IV=0x{}\n",
+            uri,
+            hex::encode(iv)
+        ));
+    }
+    for (uri, duration) in segments {
+        playlist.push_str(&format!("#EXTINF:{:.3},\n{}\n", duration, uri));
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+/// A live media playlist starting at `sequence`, ended when `end` is set.
+pub fn live_playlist(sequence: u64, segments: &[(&str, f32)], end: bool) -> String {
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:{}\n",
+        sequence
+    );
+    for (uri, duration) in segments {
+        playlist.push_str(&format!("#EXTINF:{:.3},\n{}\n", duration, uri));
+    }
+    if end {
+        playlist.push_str("#EXT-X-ENDLIST\n");
+    }
+    playlist
+}
+
+/// AES-128-CBC with PKCS7 padding, as HLS encrypts segments.
+pub fn encrypt(data: &[u8], key: &[u8; 16], iv: &[u8; 16]) -> Vec<u8> {
+    let mut buf = vec![0u8; data.len() + 16];
+    let length = Aes128CbcEnc::new(key.into(), iv.into())
+        .encrypt_padded_b2b_mut::<Pkcs7>(data, &mut buf)
+        .unwrap()
+        .len();
+    buf.truncate(length);
+    buf
+}
+
+fn mp4_box(name: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut mp4_box = Vec::with_capacity(8 + content.len());
+    mp4_box.extend((8 + content.len() as u32).to_be_bytes());
+    mp4_box.extend(name);
+    mp4_box.extend(content);
+    mp4_box
+}
+
+/// `ftyp` and a `moov` whose `mvhd` says `duration` seconds, the init section of a fragmented
+/// mp4 or the head of a plain one.
+pub fn mp4_init(duration: u32) -> Vec<u8> {
+    let mut mvhd = vec![0u8; 4 + 16];
+    mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+    mvhd[16..20].copy_from_slice(&(duration * 1000).to_be_bytes());
+    let mut init = mp4_box(b"ftyp", b"isom\0\0\0\0");
+    init.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+    init
+}
+
+/// A `moof` and `mdat` pair with `size` media bytes of `fill`.
+pub fn mp4_fragment(fill: u8, size: usize) -> Vec<u8> {
+    let mut fragment = mp4_box(b"moof", &[]);
+    fragment.extend(mp4_box(b"mdat", &vec![fill; size]));
+    fragment
+}
+
+/// A temporary directory for one test, emptied first.
+pub fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("vspider-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[tokio::test()]
+async fn test_server() {
+    let server = TestServer::start().await;
+    server
+        .route("/a.ts", ts_segment(1, 2))
+        .fault("/a.ts", Fault::Status(503), 1);
+    let client = reqwest::Client::new();
+
+    let response = client.get(server.url("/a.ts")).send().await.unwrap();
+    assert_eq!(response.status(), 503);
+    let response = client
+        .get(server.url("/a.ts"))
+        .header(reqwest::header::RANGE, "bytes=188-")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 206);
+    assert_eq!(response.bytes().await.unwrap().len(), TS_PACKET_SIZE);
+    let response = client.get(server.url("/b.ts")).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+    assert_eq!(server.hits("/a.ts"), 2);
+
+    server.fault("/a.ts", Fault::Truncate(10), 1);
+    assert!(client
+        .get(server.url("/a.ts"))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .is_err());
+
+    let key = [7u8; 16];
+    let data = ts_segment(2, 1);
+    let encrypted = encrypt(&data, &key, &[0u8; 16]);
+    assert_eq!(encrypted.len(), TS_PACKET_SIZE + 4);
+}
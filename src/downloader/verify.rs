@@ -0,0 +1,373 @@
+use super::error::DownloadError;
+use log::{info, warn};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const TS_PACKET_SIZE: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaFormat {
+    TS,
+    MP4,
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    pub path: String,
+    pub size: u64,
+    pub format: Option<MediaFormat>,
+    pub duration: Option<f64>,
+    pub problems: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl std::fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if self.is_ok() { "OK" } else { "FAIL" };
+        write!(
+            f,
+            "[{}] {} {:.1}MB",
+            state,
+            self.path,
+            self.size as f64 / 1024.0 / 1024.0
+        )?;
+        if let Some(format) = self.format {
+            write!(f, " {:?}", format)?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, " {:.1}s", duration)?;
+        }
+        for problem in self.problems.iter() {
+            write!(f, "\n    {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+fn detect_format(file: &mut File) -> Result<Option<MediaFormat>, DownloadError> {
+    let mut head = [0u8; TS_PACKET_SIZE + 1];
+    let read = file.read(&mut head)?;
+    file.seek(SeekFrom::Start(0))?;
+    if read > TS_PACKET_SIZE && head[0] == TS_SYNC_BYTE && head[TS_PACKET_SIZE] == TS_SYNC_BYTE {
+        return Ok(Some(MediaFormat::TS));
+    }
+    if read >= 8 && matches!(&head[4..8], b"ftyp" | b"styp" | b"moov" | b"moof" | b"free") {
+        return Ok(Some(MediaFormat::MP4));
+    }
+    Ok(None)
+}
+
+fn check_ts(file: &mut File, size: u64, problems: &mut Vec<String>) -> Result<(), DownloadError> {
+    if !size.is_multiple_of(TS_PACKET_SIZE as u64) {
+        problems.push(format!(
+            "size {} is not a multiple of {} byte packets",
+            size, TS_PACKET_SIZE
+        ));
+    }
+    let mut reader = BufReader::new(file);
+    let mut packet = [0u8; TS_PACKET_SIZE];
+    let mut offset = 0u64;
+    while offset + TS_PACKET_SIZE as u64 <= size {
+        reader.read_exact(&mut packet)?;
+        if packet[0] != TS_SYNC_BYTE {
+            problems.push(format!("lost sync byte at offset {}", offset));
+            break;
+        }
+        offset += TS_PACKET_SIZE as u64;
+    }
+    Ok(())
+}
+
+fn read_box_header(
+    file: &mut File,
+    end: u64,
+) -> Result<Option<(u64, [u8; 4], u64)>, DownloadError> {
+    let start = file.stream_position()?;
+    if start + 8 > end {
+        return Ok(None);
+    }
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)?;
+    let mut size = u32::from_be_bytes(header[0..4].try_into().unwrap()) as u64;
+    let name: [u8; 4] = header[4..8].try_into().unwrap();
+    let mut header_size = 8;
+    if size == 1 {
+        let mut large = [0u8; 8];
+        file.read_exact(&mut large)?;
+        size = u64::from_be_bytes(large);
+        header_size = 16;
+    } else if size == 0 {
+        size = end - start;
+    }
+    if size < header_size {
+        return Err(DownloadError::Verify(format!(
+            "invalid box size {} at offset {}",
+            size, start
+        )));
+    }
+    Ok(Some((start, name, size)))
+}
+
+/// Read part of the mvhd box, an early end of file is a problem of the video.
+fn read_mvhd(file: &mut File, buf: &mut [u8]) -> Result<(), DownloadError> {
+    file.read_exact(buf).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => DownloadError::Verify("mvhd box is truncated".into()),
+        _ => e.into(),
+    })
+}
+
+fn read_mvhd_duration(file: &mut File) -> Result<Option<f64>, DownloadError> {
+    let mut version = [0u8; 4];
+    read_mvhd(file, &mut version)?;
+    let (timescale, duration) = if version[0] == 1 {
+        let mut body = [0u8; 28];
+        read_mvhd(file, &mut body)?;
+        (
+            u32::from_be_bytes(body[16..20].try_into().unwrap()),
+            u64::from_be_bytes(body[20..28].try_into().unwrap()),
+        )
+    } else {
+        let mut body = [0u8; 16];
+        read_mvhd(file, &mut body)?;
+        (
+            u32::from_be_bytes(body[8..12].try_into().unwrap()),
+            u32::from_be_bytes(body[12..16].try_into().unwrap()) as u64,
+        )
+    };
+    if timescale == 0 || duration == 0 {
+        return Ok(None);
+    }
+    Ok(Some(duration as f64 / timescale as f64))
+}
+
+fn check_mp4(
+    file: &mut File,
+    size: u64,
+    problems: &mut Vec<String>,
+) -> Result<Option<f64>, DownloadError> {
+    let mut names = Vec::new();
+    let mut duration = None;
+    loop {
+        let header = match read_box_header(file, size) {
+            Ok(Some(header)) => header,
+            Ok(None) => break,
+            Err(DownloadError::Verify(problem)) => {
+                problems.push(problem);
+                return Ok(duration);
+            }
+            Err(e) => return Err(e),
+        };
+        let (start, name, box_size) = header;
+        if start + box_size > size {
+            problems.push(format!(
+                "box {} at offset {} is truncated",
+                String::from_utf8_lossy(&name),
+                start
+            ));
+            return Ok(duration);
+        }
+        if &name == b"moov" {
+            while let Ok(Some((child_start, child_name, child_size))) =
+                read_box_header(file, start + box_size)
+            {
+                if &child_name == b"mvhd" {
+                    match read_mvhd_duration(file) {
+                        Ok(mvhd) => duration = mvhd,
+                        Err(DownloadError::Verify(problem)) => {
+                            problems.push(problem);
+                            return Ok(duration);
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                file.seek(SeekFrom::Start(child_start + child_size))?;
+            }
+        }
+        names.push(name);
+        file.seek(SeekFrom::Start(start + box_size))?;
+    }
+    for required in [b"moov", b"mdat"] {
+        if !names.iter().any(|name| name == required) {
+            problems.push(format!("missing {} box", String::from_utf8_lossy(required)));
+        }
+    }
+    Ok(duration)
+}
+
+fn probe_duration(path: &Path) -> Option<f64> {
+    let output = std::process::Command::new("ffprobe")
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration")
+        .arg("-of")
+        .arg("csv=p=0")
+        .arg(path)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Check the container structure of a produced file, and compare its duration with the expected
+/// one (the `EXTINF` sum of the playlist) when known.
+pub fn verify_file<P: AsRef<Path>>(
+    path: P,
+    expected_duration: Option<f64>,
+) -> Result<VerifyReport, DownloadError> {
+    let path = path.as_ref();
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut report = VerifyReport {
+        path: path.to_string_lossy().to_string(),
+        size,
+        format: None,
+        duration: None,
+        problems: Vec::new(),
+    };
+    if size == 0 {
+        report.problems.push("file is empty".to_string());
+        return Ok(report);
+    }
+    report.format = detect_format(&mut file)?;
+    match report.format {
+        Some(MediaFormat::TS) => check_ts(&mut file, size, &mut report.problems)?,
+        Some(MediaFormat::MP4) => {
+            report.duration = check_mp4(&mut file, size, &mut report.problems)?
+        }
+        None => report.problems.push("unknown container format".to_string()),
+    }
+    if report.duration.is_none() {
+        report.duration = probe_duration(path);
+    }
+    if let Some(expected) = expected_duration.filter(|expected| *expected > 0.0) {
+        if let Some(duration) = report.duration {
+            let tolerance = (expected * 0.02).max(2.0);
+            if (duration - expected).abs() > tolerance {
+                report.problems.push(format!(
+                    "duration {:.1}s does not match playlist duration {:.1}s",
+                    duration, expected
+                ));
+            }
+        }
+        // below 8KB/s there can hardly be any video in it
+        if (size as f64) < expected * 8.0 * 1024.0 {
+            report.problems.push(format!(
+                "size {} bytes is too small for {:.1}s of video",
+                size, expected
+            ));
+        }
+    }
+    Ok(report)
+}
+
+/// Temporary name a download is written to before it passes verification, keeping the extension
+/// so ffmpeg still picks the right muxer.
+pub fn part_file(save_file: &str) -> String {
+    let path = Path::new(save_file);
+    match path.extension() {
+        Some(extension) => path
+            .with_extension(format!("part.{}", extension.to_string_lossy()))
+            .to_string_lossy()
+            .to_string(),
+        None => format!("{}.part", save_file),
+    }
+}
+
+/// Verify the temporary file and atomically rename it to its final name, or remove it when the
+/// verification fails.
+pub fn commit_file(
+    part_file: &str,
+    save_file: &str,
+    expected_duration: Option<f64>,
+) -> Result<VerifyReport, DownloadError> {
+    let report = verify_file(part_file, expected_duration)?;
+    if !report.is_ok() {
+        if let Err(e) = std::fs::remove_file(part_file) {
+            warn!("remove part file {} err={}", part_file, e);
+        }
+        return Err(DownloadError::Verify(report.problems.join("; ")));
+    }
+    std::fs::rename(part_file, save_file)?;
+    info!("verify {} success", save_file);
+    Ok(report)
+}
+
+#[test]
+fn test_verify_file() {
+    let dir = std::env::temp_dir().join("vspider-verify-test");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let ts_file = dir.join("ok.ts");
+    let mut ts = Vec::new();
+    for _ in 0..10 {
+        let mut packet = vec![0xffu8; TS_PACKET_SIZE];
+        packet[0] = TS_SYNC_BYTE;
+        ts.extend(packet);
+    }
+    std::fs::write(&ts_file, &ts).unwrap();
+    let report = verify_file(&ts_file, None).unwrap();
+    assert_eq!(report.format, Some(MediaFormat::TS));
+    assert!(report.is_ok(), "{}", report);
+
+    ts[TS_PACKET_SIZE * 5] = 0;
+    std::fs::write(&ts_file, &ts[..ts.len() - 10]).unwrap();
+    let report = verify_file(&ts_file, None).unwrap();
+    assert_eq!(report.problems.len(), 2, "{}", report);
+
+    let mp4_file = dir.join("ok.mp4");
+    let mut mp4 = Vec::new();
+    mp4.extend(16u32.to_be_bytes());
+    mp4.extend(b"ftypisom");
+    mp4.extend(0u32.to_be_bytes());
+    let mut mvhd = vec![0u8; 4 + 16];
+    mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+    mvhd[16..20].copy_from_slice(&60000u32.to_be_bytes());
+    mp4.extend((8 + 8 + mvhd.len() as u32).to_be_bytes());
+    mp4.extend(b"moov");
+    mp4.extend((8 + mvhd.len() as u32).to_be_bytes());
+    mp4.extend(b"mvhd");
+    mp4.extend(&mvhd);
+    mp4.extend((8 + 1024 * 1024u32).to_be_bytes());
+    mp4.extend(b"mdat");
+    mp4.extend(vec![0u8; 1024 * 1024]);
+    std::fs::write(&mp4_file, &mp4).unwrap();
+    let report = verify_file(&mp4_file, Some(60.0)).unwrap();
+    assert_eq!(report.format, Some(MediaFormat::MP4));
+    assert_eq!(report.duration, Some(60.0));
+    assert!(report.is_ok(), "{}", report);
+
+    let report = verify_file(&mp4_file, Some(120.0)).unwrap();
+    assert!(!report.is_ok());
+
+    std::fs::write(&mp4_file, &mp4[..mp4.len() - 100]).unwrap();
+    let report = verify_file(&mp4_file, None).unwrap();
+    assert!(!report.is_ok());
+
+    let mut truncated = mp4[..16].to_vec();
+    truncated.extend((8 + 8 + 4u32).to_be_bytes());
+    truncated.extend(b"moov");
+    truncated.extend((8 + 4u32).to_be_bytes());
+    truncated.extend(b"mvhd");
+    truncated.extend(&mvhd[..4]);
+    std::fs::write(&mp4_file, &truncated).unwrap();
+    let report = verify_file(&mp4_file, None).unwrap();
+    assert!(
+        report
+            .problems
+            .contains(&"mvhd box is truncated".to_string()),
+        "{}",
+        report
+    );
+
+    assert_eq!(part_file("a/b.mp4"), "a/b.part.mp4");
+    assert_eq!(part_file("a/b"), "a/b.part");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,509 @@
+use crate::args::Src;
+use crate::subscribe::{format_time, now};
+use crate::vrsr::mirror;
+use crate::vrsr::{TeleplayId, TeleplayInfo};
+use clap::ValueEnum;
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+
+pub const LIBRARY_FILE: &str = "library.db";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS teleplays (
+    src TEXT NOT NULL,
+    id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    home_page TEXT NOT NULL,
+    release_time TEXT,
+    language TEXT,
+    times TEXT,
+    director TEXT,
+    starring TEXT,
+    introduction TEXT,
+    genre TEXT,
+    region TEXT,
+    update_time TEXT,
+    score TEXT,
+    plot TEXT,
+    cover TEXT,
+    status TEXT,
+    updated INTEGER NOT NULL,
+    poster TEXT,
+    PRIMARY KEY (src, id)
+);
+CREATE TABLE IF NOT EXISTS episodes (
+    src TEXT NOT NULL,
+    teleplay_id TEXT NOT NULL,
+    source TEXT,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    uri TEXT,
+    uri_type TEXT,
+    file TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    duration REAL,
+    downloaded INTEGER NOT NULL,
+    PRIMARY KEY (src, teleplay_id, url)
+);
+";
+
+fn src_name(src: Src) -> String {
+    src.to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn parse_src(name: &str) -> rusqlite::Result<Src> {
+    Src::from_str(name, true).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(
+            0,
+            rusqlite::types::Type::Text,
+            Box::<dyn std::error::Error + Send + Sync>::from(e),
+        )
+    })
+}
+
+impl ToSql for TeleplayId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for TeleplayId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        String::column_result(value).map(TeleplayId::from)
+    }
+}
+
+fn join_names(names: Option<&Vec<String>>) -> Option<String> {
+    names.map(|names| names.join("/"))
+}
+
+fn split_names(names: Option<String>) -> Option<Vec<String>> {
+    names.map(|names| names.split('/').map(|name| name.to_string()).collect())
+}
+
+/// `H:MM:SS` of a duration in seconds.
+fn format_duration(duration: f64) -> String {
+    let seconds = duration.round() as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+pub struct TeleplayRecord {
+    pub src: Src,
+    pub info: TeleplayInfo,
+    pub updated: u64,
+    /// Path of the saved cover.
+    pub poster: Option<String>,
+    pub episodes: usize,
+    pub size: u64,
+}
+
+impl TeleplayRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            src: parse_src(&row.get::<_, String>("src")?)?,
+            info: TeleplayInfo {
+                title: row.get("title")?,
+                home_page: row.get("home_page")?,
+                id: row.get("id")?,
+                release_time: row.get("release_time")?,
+                language: row.get("language")?,
+                times: row.get("times")?,
+                director: split_names(row.get("director")?),
+                starring: split_names(row.get("starring")?),
+                introduction: row.get("introduction")?,
+                genre: row.get("genre")?,
+                region: row.get("region")?,
+                update_time: row.get("update_time")?,
+                score: row.get("score")?,
+                plot: row.get("plot")?,
+                cover: row.get("cover")?,
+                status: row.get("status")?,
+                artwork: Vec::new(),
+            },
+            updated: row.get("updated")?,
+            poster: row.get("poster")?,
+            episodes: row.get("episodes")?,
+            size: row.get("size")?,
+        })
+    }
+}
+
+impl std::fmt::Display for TeleplayRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {} {} {} episodes {:.1}MB, updated {}",
+            self.src,
+            self.info.id,
+            self.info.title,
+            self.episodes,
+            self.size as f64 / 1024.0 / 1024.0,
+            format_time(self.updated)
+        )
+    }
+}
+
+/// A downloaded episode file and where it came from.
+#[derive(Debug, Clone)]
+pub struct EpisodeRecord {
+    pub src: Src,
+    pub teleplay_id: TeleplayId,
+    /// Name of the play source the episode was taken from.
+    pub source: Option<String>,
+    pub name: String,
+    pub url: String,
+    /// Resolved video uri, unknown for files found from an earlier run.
+    pub uri: Option<String>,
+    pub uri_type: Option<String>,
+    pub file: String,
+    pub size: u64,
+    pub duration: Option<f64>,
+    pub downloaded: u64,
+}
+
+impl EpisodeRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            src: parse_src(&row.get::<_, String>("src")?)?,
+            teleplay_id: row.get("teleplay_id")?,
+            source: row.get("source")?,
+            name: row.get("name")?,
+            url: row.get("url")?,
+            uri: row.get("uri")?,
+            uri_type: row.get("uri_type")?,
+            file: row.get("file")?,
+            size: row.get("size")?,
+            duration: row.get("duration")?,
+            downloaded: row.get("downloaded")?,
+        })
+    }
+}
+
+impl std::fmt::Display for EpisodeRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> {} {:.1}MB",
+            self.name,
+            self.file,
+            self.size as f64 / 1024.0 / 1024.0
+        )?;
+        if let Some(duration) = self.duration {
+            write!(f, " {}", format_duration(duration))?;
+        }
+        write!(f, " {}", format_time(self.downloaded))?;
+        if let Some(uri) = self.uri.as_ref() {
+            write!(f, "\n    {}", uri)?;
+        }
+        Ok(())
+    }
+}
+
+const TELEPLAY_QUERY: &str = "
+SELECT t.*, COUNT(e.url) AS episodes, COALESCE(SUM(e.size), 0) AS size
+FROM teleplays t LEFT JOIN episodes e ON e.src = t.src AND e.teleplay_id = t.id
+";
+
+/// The downloaded teleplays and episode files, kept in a sqlite database in the data directory.
+pub struct Library {
+    connection: Connection,
+}
+
+impl Library {
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        // libraries created before covers were saved lack the poster column
+        let has_poster = connection
+            .prepare("SELECT 1 FROM pragma_table_info('teleplays') WHERE name = 'poster'")?
+            .exists([])?;
+        if !has_poster {
+            connection.execute("ALTER TABLE teleplays ADD COLUMN poster TEXT", [])?;
+        }
+        // libraries created before slug ids kept the ids in INTEGER columns, copying the rows
+        // into the TEXT columns of the current schema stores them as text
+        let id_type: String = connection.query_row(
+            "SELECT type FROM pragma_table_info('teleplays') WHERE name = 'id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if id_type == "INTEGER" {
+            connection.execute_batch(&format!(
+                "BEGIN;
+                ALTER TABLE teleplays RENAME TO teleplays_old;
+                ALTER TABLE episodes RENAME TO episodes_old;
+                {}
+                INSERT INTO teleplays SELECT * FROM teleplays_old;
+                INSERT INTO episodes SELECT * FROM episodes_old;
+                DROP TABLE teleplays_old;
+                DROP TABLE episodes_old;
+                COMMIT;",
+                SCHEMA
+            ))?;
+        }
+        Ok(Self { connection })
+    }
+
+    pub fn set_poster(&self, src: Src, id: &TeleplayId, poster: &str) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "UPDATE teleplays SET poster = ?3 WHERE src = ?1 AND id = ?2",
+            params![src_name(src), id, poster],
+        )?;
+        Ok(())
+    }
+
+    pub fn add_teleplay(&self, src: Src, info: &TeleplayInfo) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO teleplays VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+                (SELECT poster FROM teleplays WHERE src = ?1 AND id = ?2))",
+            params![
+                src_name(src),
+                info.id,
+                info.title,
+                info.home_page,
+                info.release_time,
+                info.language,
+                info.times,
+                join_names(info.director.as_ref()),
+                join_names(info.starring.as_ref()),
+                info.introduction,
+                info.genre,
+                info.region,
+                info.update_time,
+                info.score,
+                info.plot,
+                info.cover,
+                info.status,
+                now(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Insert or update an episode; a record without uri, i.e. a file found from an earlier run,
+    /// keeps what was known from the download.
+    pub fn add_episode(&self, episode: &EpisodeRecord) -> rusqlite::Result<()> {
+        self.connection.execute(
+            "INSERT INTO episodes VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            ON CONFLICT (src, teleplay_id, url) DO UPDATE SET
+                source = COALESCE(excluded.source, source),
+                name = excluded.name,
+                uri = COALESCE(excluded.uri, uri),
+                uri_type = COALESCE(excluded.uri_type, uri_type),
+                file = excluded.file,
+                size = excluded.size,
+                duration = COALESCE(excluded.duration, duration),
+                downloaded = CASE WHEN excluded.uri IS NULL THEN downloaded
+                    ELSE excluded.downloaded END",
+            params![
+                src_name(episode.src),
+                episode.teleplay_id,
+                episode.source,
+                episode.name,
+                episode.url,
+                episode.uri,
+                episode.uri_type,
+                episode.file,
+                episode.size,
+                episode.duration,
+                episode.downloaded,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn query_teleplays(
+        &self,
+        condition: &str,
+        params: impl rusqlite::Params,
+    ) -> rusqlite::Result<Vec<TeleplayRecord>> {
+        let sql = format!(
+            "{} {} GROUP BY t.src, t.id ORDER BY t.updated DESC",
+            TELEPLAY_QUERY, condition
+        );
+        let mut statement = self.connection.prepare(&sql)?;
+        let records = statement
+            .query_map(params, TeleplayRecord::from_row)?
+            .collect();
+        records
+    }
+
+    /// Move the page urls of `src` from its other `hosts` to the `active` one after the site
+    /// changed its domain. Returns the number of rows changed.
+    pub fn rewrite_hosts(&self, src: Src, hosts: &[String], active: &str) -> rusqlite::Result<usize> {
+        let Some(active) = mirror::origin(active) else {
+            return Ok(0);
+        };
+        let mut changed = 0;
+        for host in hosts.iter().filter_map(|host| mirror::origin(host)) {
+            if host == active {
+                continue;
+            }
+            for (table, column) in [
+                ("teleplays", "home_page"),
+                ("teleplays", "cover"),
+                ("episodes", "url"),
+            ] {
+                // urls rewritten onto an existing episode leave the old row be
+                changed += self.connection.execute(
+                    &format!(
+                        "UPDATE OR IGNORE {table} SET {column} = ?3 || substr({column}, length(?2) + 1)
+                        WHERE src = ?1 AND substr({column}, 1, length(?2) + 1) = ?2 || '/'"
+                    ),
+                    params![src_name(src), host, active],
+                )?;
+            }
+        }
+        Ok(changed)
+    }
+
+    pub fn teleplays(&self) -> rusqlite::Result<Vec<TeleplayRecord>> {
+        self.query_teleplays("", [])
+    }
+
+    pub fn teleplay(&self, src: Src, id: &TeleplayId) -> rusqlite::Result<Option<TeleplayRecord>> {
+        let mut records =
+            self.query_teleplays("WHERE t.src = ?1 AND t.id = ?2", params![src_name(src), id])?;
+        Ok(records.pop())
+    }
+
+    /// Teleplays whose title, actors, directors or genre contain the keyword.
+    pub fn search(&self, keyword: &str) -> rusqlite::Result<Vec<TeleplayRecord>> {
+        self.query_teleplays(
+            "WHERE t.title LIKE ?1 OR t.starring LIKE ?1 OR t.director LIKE ?1 OR t.genre LIKE ?1",
+            [format!("%{}%", keyword)],
+        )
+    }
+
+    pub fn episodes(&self, src: Src, id: &TeleplayId) -> rusqlite::Result<Vec<EpisodeRecord>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT * FROM episodes WHERE src = ?1 AND teleplay_id = ?2 ORDER BY file")?;
+        let records = statement
+            .query_map(params![src_name(src), id], EpisodeRecord::from_row)?
+            .collect();
+        records
+    }
+
+    /// Forget a teleplay, returning its episodes so the caller can delete the files.
+    pub fn remove(&self, src: Src, id: &TeleplayId) -> rusqlite::Result<Option<Vec<EpisodeRecord>>> {
+        if self.teleplay(src, id)?.is_none() {
+            return Ok(None);
+        }
+        let episodes = self.episodes(src, id)?;
+        self.connection.execute(
+            "DELETE FROM episodes WHERE src = ?1 AND teleplay_id = ?2",
+            params![src_name(src), id],
+        )?;
+        self.connection.execute(
+            "DELETE FROM teleplays WHERE src = ?1 AND id = ?2",
+            params![src_name(src), id],
+        )?;
+        Ok(Some(episodes))
+    }
+}
+
+#[test]
+fn test_library() {
+    let dir = std::env::temp_dir().join("vspider-library-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let library = Library::open(dir.join(LIBRARY_FILE)).unwrap();
+    let id = TeleplayId::from(7);
+    let info = TeleplayInfo {
+        title: "繁花".to_string(),
+        home_page: "https://a.com/detail/7.html".to_string(),
+        id: id.clone(),
+        starring: Some(vec!["胡歌".to_string(), "马伊琍".to_string()]),
+        ..TeleplayInfo::default()
+    };
+    library.add_teleplay(Src::XMB, &info).unwrap();
+    library.set_poster(Src::XMB, &id, "繁花/poster.jpg").unwrap();
+    library.add_teleplay(Src::XMB, &info).unwrap();
+    let mut episode = EpisodeRecord {
+        src: Src::XMB,
+        teleplay_id: id.clone(),
+        source: Some("ffm3u8".to_string()),
+        name: "第01集".to_string(),
+        url: "https://a.com/play/7-1-1.html".to_string(),
+        uri: Some("https://cdn.a.com/index.m3u8".to_string()),
+        uri_type: Some("M3U8".to_string()),
+        file: "繁花/第01集.mp4".to_string(),
+        size: 1024,
+        duration: Some(2700.0),
+        downloaded: 100,
+    };
+    library.add_episode(&episode).unwrap();
+    // found again on a later run, the download details stay
+    episode.uri = None;
+    episode.size = 2048;
+    episode.duration = None;
+    episode.downloaded = 200;
+    library.add_episode(&episode).unwrap();
+
+    let library = Library::open(dir.join(LIBRARY_FILE)).unwrap();
+    let hosts = vec!["https://a.com".to_string(), "https://b.com".to_string()];
+    assert_eq!(library.rewrite_hosts(Src::XMB, &hosts, "https://b.com").unwrap(), 2);
+    let record = library.teleplay(Src::XMB, &id).unwrap().unwrap();
+    assert_eq!(record.info.home_page, "https://b.com/detail/7.html");
+    assert_eq!(record.info.title, "繁花");
+    assert_eq!(record.poster.as_deref(), Some("繁花/poster.jpg"));
+    assert_eq!(record.info.starring.as_ref().unwrap().len(), 2);
+    assert_eq!((record.episodes, record.size), (1, 2048));
+    let stored = library.episodes(Src::XMB, &id).unwrap().remove(0);
+    assert_eq!(stored.url, "https://b.com/play/7-1-1.html");
+    assert_eq!(stored.uri.as_deref(), Some("https://cdn.a.com/index.m3u8"));
+    assert_eq!((stored.duration, stored.downloaded), (Some(2700.0), 100));
+    assert_eq!(format_duration(2700.4), "0:45:00");
+
+    assert_eq!(library.search("胡歌").unwrap().len(), 1);
+    assert!(library.search("庆余年").unwrap().is_empty());
+    assert!(library.teleplay(Src::ZBKYYY, &id).unwrap().is_none());
+    assert_eq!(library.remove(Src::XMB, &id).unwrap().unwrap().len(), 1);
+    assert!(library.remove(Src::XMB, &id).unwrap().is_none());
+    assert!(library.teleplays().unwrap().is_empty());
+
+    // slugs are kept as text next to the numeric ids
+    let id = TeleplayId::from("fan-hua");
+    let info = TeleplayInfo { id: id.clone(), ..info };
+    library.add_teleplay(Src::XMB, &info).unwrap();
+    let record = library.teleplay(Src::XMB, &id).unwrap().unwrap();
+    assert_eq!(record.info.id.as_str(), "fan-hua");
+
+    // the INTEGER id columns of older libraries are migrated to text
+    let old = dir.join("old.db");
+    let connection = Connection::open(&old).unwrap();
+    connection
+        .execute_batch(
+            &SCHEMA
+                .replace("id TEXT NOT NULL", "id INTEGER NOT NULL")
+                .replace("poster TEXT,", ""),
+        )
+        .unwrap();
+    connection
+        .execute(
+            "INSERT INTO teleplays (src, id, title, home_page, updated) \
+            VALUES ('xmb', 7, '繁花', '', 1)",
+            [],
+        )
+        .unwrap();
+    drop(connection);
+    let library = Library::open(&old).unwrap();
+    let record = library.teleplay(Src::XMB, &TeleplayId::from(7)).unwrap().unwrap();
+    assert_eq!(record.info.title, "繁花");
+    let id_type: String = library
+        .connection
+        .query_row("SELECT typeof(id) FROM teleplays", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(id_type, "text");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
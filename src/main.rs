@@ -1,19 +1,37 @@
 mod args;
+mod artwork;
 mod commands;
+mod config;
+mod doctor;
 mod downloader;
+mod library;
+mod mirror;
+mod naming;
+mod subscribe;
 mod vrsr;
 
-use args::{Cli, Mode};
+use args::{BrowserArgs, Cli, Mode};
 use clap::Parser;
-use commands::{download, m3u8_download, search, CommandError};
+use commands::{
+    browse, download, m3u8_download, search, sniff, verify, BrowseOptions, CommandError, DownloadOptions, SearchOptions,
+    SniffOptions,
+};
+use commands::{config, cookies, doctor, get, install_config, install_mirrors, install_parse_apis, library, login, mirrors};
+use commands::{subscribe, sync};
 use downloader::{DownloadError, M3U8DownloadBuilder};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use std::collections::HashMap;
+use vrsr::{BrowserPool, BrowserPoolBuilder};
 
 #[tokio::main]
 async fn main() -> Result<(), CommandError> {
     env_logger::init();
+    vrsr::diagnostics::install(std::path::Path::new(subscribe::DATA_DIR).join("diagnostics"));
     let cli = Cli::parse();
+    install_config(&cli)?;
+    install_mirrors()?;
+    install_parse_apis()?;
+    let settings = config::settings();
     if let Some(mode) = cli.mode {
         match mode {
             Mode::Search {
@@ -21,8 +39,39 @@ async fn main() -> Result<(), CommandError> {
                 src,
                 all,
                 nocache,
+                page,
+                all_pages,
+                max_pages,
+                filter,
+                preview,
             } => {
-                search(&keyword, src, all, nocache).await?;
+                let options = SearchOptions {
+                    page: page as usize,
+                    pages: if all_pages { max_pages.max(1) } else { 1 },
+                    filter: filter.into(),
+                    preview,
+                };
+                search(&keyword, src, all, nocache, &options).await?;
+            }
+            Mode::Browse {
+                src,
+                category,
+                sort,
+                page,
+                pages,
+                hot,
+                nocache,
+                preview,
+            } => {
+                let options = BrowseOptions {
+                    category,
+                    sort: sort.into(),
+                    page: page as usize,
+                    pages: pages.max(1),
+                    hot,
+                    preview,
+                };
+                browse(src, nocache, &options).await?;
             }
             Mode::Download {
                 id,
@@ -32,21 +81,159 @@ async fn main() -> Result<(), CommandError> {
                 save_dir,
                 print,
                 climit,
+                naming,
+                nfo,
+                artwork,
+                hls,
+                browser,
             } => {
-                download(id, src, index, nocache, &save_dir, print, climit).await?;
+                install_browser_pool(&browser);
+                let save_dir = save_dir.or_else(|| settings.download_dir.clone());
+                let naming = naming
+                    .unwrap_or_else(|| naming::default_template(save_dir.is_some()).to_string());
+                let options = DownloadOptions {
+                    index,
+                    save_dir,
+                    print,
+                    climit: climit.unwrap_or(settings.climit),
+                    hls,
+                    naming,
+                    nfo,
+                    artwork,
+                    skip: Vec::new(),
+                    dry_run: false,
+                    episode: None,
+                };
+                let result = download(&id, src, nocache, &options).await;
+                BrowserPool::shutdown_shared().await;
+                result?;
+            }
+            Mode::Get {
+                url,
+                output,
+                nocache,
+                save_dir,
+                print,
+                index,
+                climit,
+                naming,
+                nfo,
+                artwork,
+                hls,
+                browser,
+            } => {
+                install_browser_pool(&browser);
+                let save_dir = save_dir.or_else(|| settings.download_dir.clone());
+                let naming = naming
+                    .unwrap_or_else(|| naming::default_template(save_dir.is_some()).to_string());
+                let options = DownloadOptions {
+                    index,
+                    save_dir,
+                    print,
+                    climit: climit.unwrap_or(settings.climit),
+                    hls,
+                    naming,
+                    nfo,
+                    artwork,
+                    skip: Vec::new(),
+                    dry_run: false,
+                    episode: None,
+                };
+                let result = get(&url, &output, nocache, options).await;
+                BrowserPool::shutdown_shared().await;
+                result?;
             }
             Mode::M3U8 {
                 url,
                 output,
                 climit,
+                live,
+                duration,
+                hls,
+                dry_run,
+            } => {
+                let climit = climit.unwrap_or(settings.climit);
+                m3u8_download(&url, &output, climit, live, duration, &hls, dry_run).await?;
+            }
+            Mode::Sniff {
+                url,
+                url_pattern,
+                content_type,
+                resource_type,
+                click,
+                all,
+                browser,
+            } => {
+                install_browser_pool(&browser);
+                let options = SniffOptions {
+                    url_patterns: url_pattern,
+                    content_types: content_type,
+                    resource_types: resource_type,
+                    click,
+                    all,
+                };
+                let result = sniff(&url, &options).await;
+                BrowserPool::shutdown_shared().await;
+                result?;
+            }
+            Mode::Subscribe { action } => {
+                subscribe(action)?;
+            }
+            Mode::Sync {
+                climit,
+                hls,
+                browser,
+                dry_run,
             } => {
-                m3u8_download(&url, &output, climit).await?;
+                install_browser_pool(&browser);
+                let result = sync(climit.unwrap_or(settings.climit), &hls, dry_run).await;
+                BrowserPool::shutdown_shared().await;
+                result?;
+            }
+            Mode::Library { action } => {
+                library(action)?;
+            }
+            Mode::Mirrors { action } => {
+                mirrors(action).await?;
+            }
+            Mode::Doctor {
+                src,
+                keyword,
+                id,
+                json,
+                browser,
+            } => {
+                install_browser_pool(&browser);
+                let result = doctor(src, keyword, id, json).await;
+                BrowserPool::shutdown_shared().await;
+                result?;
+            }
+            Mode::Login { src } => {
+                login(src).await?;
+            }
+            Mode::Cookies { action } => {
+                cookies(action)?;
+            }
+            Mode::Config { action } => {
+                config(action)?;
+            }
+            Mode::Verify { path, recursive } => {
+                verify(&path, recursive)?;
             }
         }
     }
     Ok(())
 }
 
+/// Install the shared browser pool the parsers and the sniffer use with the browser arguments.
+fn install_browser_pool(browser: &BrowserArgs) {
+    BrowserPoolBuilder::new()
+        .headless(browser.headless)
+        .max_tabs(browser.max_tabs)
+        .timeout(browser.browser_timeout)
+        .install();
+}
+
 #[allow(unused)]
 async fn download_map(
     m3u8_map: &HashMap<String, String>,
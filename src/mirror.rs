@@ -0,0 +1,83 @@
+use crate::args::Src;
+use crate::vrsr::mirror::SiteMirrors;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const MIRRORS_FILE: &str = "mirrors.json";
+
+/// The mirror hosts users added and the host each site is used on, kept as json in the data
+/// directory.
+pub struct MirrorStore {
+    path: PathBuf,
+    sites: BTreeMap<Src, SiteMirrors>,
+}
+
+impl MirrorStore {
+    pub fn open<P: AsRef<Path>>(dir: P) -> std::io::Result<Self> {
+        let path = dir.as_ref().join(MIRRORS_FILE);
+        let sites = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { path, sites })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(&self.sites)?)?;
+        std::fs::rename(temp, &self.path)
+    }
+
+    pub fn sites(&self) -> &BTreeMap<Src, SiteMirrors> {
+        &self.sites
+    }
+
+    pub fn site_mut(&mut self, src: Src) -> &mut SiteMirrors {
+        self.sites.entry(src).or_default()
+    }
+
+    /// Add `host` to the mirrors of `src`, false when it was known already.
+    pub fn add(&mut self, src: Src, host: &str) -> bool {
+        let site = self.site_mut(src);
+        if site.hosts.iter().any(|known| known == host) {
+            return false;
+        }
+        site.hosts.push(host.to_string());
+        true
+    }
+
+    /// Forget `host`, using the built-in host again when it was the active one.
+    pub fn remove(&mut self, src: Src, host: &str) -> bool {
+        let site = self.site_mut(src);
+        let count = site.hosts.len();
+        site.hosts.retain(|known| known != host);
+        let removed = count != site.hosts.len() || site.active.as_deref() == Some(host);
+        if site.active.as_deref() == Some(host) {
+            site.active = None;
+        }
+        removed
+    }
+}
+
+#[test]
+fn test_mirror_store() {
+    let dir = std::env::temp_dir().join("vspider-mirror-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut store = MirrorStore::open(&dir).unwrap();
+    assert!(store.add(Src::JUGOUGOU, "https://jgg.example.com"));
+    assert!(!store.add(Src::JUGOUGOU, "https://jgg.example.com"));
+    store.site_mut(Src::JUGOUGOU).active = Some("https://jgg.example.com".to_string());
+    store.save().unwrap();
+
+    let mut store = MirrorStore::open(&dir).unwrap();
+    let site = &store.sites()[&Src::JUGOUGOU];
+    assert_eq!(site.hosts.len(), 1);
+    assert_eq!(site.active.as_deref(), Some("https://jgg.example.com"));
+    assert!(store.remove(Src::JUGOUGOU, "https://jgg.example.com"));
+    assert_eq!(store.sites()[&Src::JUGOUGOU], SiteMirrors::default());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,321 @@
+use crate::artwork::Artwork;
+use crate::vrsr::TeleplayInfo;
+use regex::Regex;
+use std::path::{Component, Path, PathBuf};
+
+/// Path of an episode when no template is given, `{name}` alone with a save directory.
+pub const DEFAULT_TEMPLATE: &str = "{title}/{name}";
+
+pub fn default_template(save_dir: bool) -> &'static str {
+    if save_dir {
+        "{name}"
+    } else {
+        DEFAULT_TEMPLATE
+    }
+}
+
+/// Value of a number written in chinese numerals, e.g. `十二` or `一百零五`.
+fn chinese_number(text: &str) -> Option<u32> {
+    let (mut total, mut current) = (0u32, None);
+    for c in text.chars() {
+        let digit = match c {
+            '0'..='9' => c.to_digit(10),
+            '零' | '〇' => Some(0),
+            '一' => Some(1),
+            '二' | '两' => Some(2),
+            '三' => Some(3),
+            '四' => Some(4),
+            '五' => Some(5),
+            '六' => Some(6),
+            '七' => Some(7),
+            '八' => Some(8),
+            '九' => Some(9),
+            _ => None,
+        };
+        match (digit, c) {
+            (Some(digit), _) => current = Some(current.unwrap_or(0) * 10 + digit),
+            (None, '十') => total += current.take().unwrap_or(1) * 10,
+            (None, '百') => total += current.take().unwrap_or(1) * 100,
+            _ => return None,
+        }
+    }
+    let number = total + current.unwrap_or(0);
+    (number > 0 || text.contains(['0', '零', '〇'])).then_some(number)
+}
+
+/// Episode number of names like `第08集`, `第十二集`, `EP8`, `08` or `上`/`下`.
+pub fn episode_number(name: &str) -> Option<u32> {
+    let name = name.trim();
+    let patterns = [
+        r"第\s*([0-9零〇一二两三四五六七八九十百]+)\s*[集话話期回]",
+        r"(?i)(?:^|[^a-z])e(?:p|pisode)?\s*\.?\s*(\d+)",
+        r"^(\d{1,4})\s*[集话話期]?$",
+    ];
+    for pattern in patterns {
+        let regex = Regex::new(pattern).unwrap();
+        if let Some(number) = regex
+            .captures(name)
+            .and_then(|captures| chinese_number(&captures[1]))
+        {
+            return Some(number);
+        }
+    }
+    let regex = Regex::new(r"^(?:.*[(（\s])?([上下])[集部篇]?[)）]?$").unwrap();
+    regex
+        .captures(name)
+        .map(|captures| if &captures[1] == "上" { 1 } else { 2 })
+}
+
+/// Season of titles like `xx 第二季`, `xx Season 2` or `xx S02`.
+pub fn season_number(title: &str) -> Option<u32> {
+    let patterns = [
+        r"第\s*([0-9零一二两三四五六七八九十]+)\s*[季部]",
+        r"(?i)season\s*(\d+)",
+        r"(?i)(?:^|[^a-z0-9])s(\d{1,2})(?:$|[^0-9])",
+    ];
+    patterns.iter().find_map(|pattern| {
+        Regex::new(pattern)
+            .unwrap()
+            .captures(title)
+            .and_then(|captures| chinese_number(&captures[1]))
+    })
+}
+
+pub fn year(info: &TeleplayInfo) -> Option<String> {
+    let regex = Regex::new(r"(?:19|20)\d{2}").unwrap();
+    [info.times.as_deref(), info.release_time.as_deref()]
+        .into_iter()
+        .flatten()
+        .find_map(|text| regex.find(text).map(|m| m.as_str().to_string()))
+}
+
+/// Replace the characters file systems reject and trim what media servers trip over.
+pub fn sanitize(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = name.trim_matches(|c: char| c == '.' || c == ' ');
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// The values a path template is filled with for one episode.
+pub struct NamingContext {
+    pub title: String,
+    pub year: Option<String>,
+    pub season: u32,
+    pub episode: u32,
+    /// Episode name as shown on the site, e.g. `第08集`.
+    pub name: String,
+    /// Position in the episode list, from 1.
+    pub index: usize,
+}
+
+impl NamingContext {
+    /// Episode and season numbers fall back to the list position and season 1.
+    pub fn new(info: &TeleplayInfo, name: &str, index: usize) -> Self {
+        Self {
+            title: info.title.clone(),
+            year: year(info),
+            season: season_number(&info.title).unwrap_or(1),
+            episode: episode_number(name).unwrap_or(index as u32),
+            name: name.to_string(),
+            index,
+        }
+    }
+
+    /// Relative `.mp4` path of the episode. Placeholders are `{title}`, `{year}`, `{season}`,
+    /// `{episode}`, `{name}` and `{index}`; numbers take a zero padded width like `{episode:02}`.
+    /// Brackets left empty by a missing `{year}` are dropped.
+    pub fn render(&self, template: &str) -> PathBuf {
+        let placeholder = Regex::new(r"\{(\w+)(?::0?(\d+))?\}").unwrap();
+        let rendered = placeholder.replace_all(template, |captures: &regex::Captures| {
+            let width = captures
+                .get(2)
+                .and_then(|width| width.as_str().parse::<usize>().ok())
+                .unwrap_or(0);
+            let number = |number: u64| format!("{:0width$}", number, width = width);
+            match &captures[1] {
+                "title" => sanitize(&self.title),
+                "year" => self.year.clone().unwrap_or_default(),
+                "season" => number(self.season as u64),
+                "episode" => number(self.episode as u64),
+                "name" => sanitize(&self.name),
+                "index" => number(self.index as u64),
+                _ => captures[0].to_string(),
+            }
+        });
+        let empty_brackets = Regex::new(r"\s*(?:\(\s*\)|\[\s*\]|（\s*）)").unwrap();
+        let rendered = empty_brackets.replace_all(&rendered, "");
+        let mut path = rendered
+            .split(['/', '\\'])
+            .map(|component| component.trim())
+            .filter(|component| !component.is_empty() && *component != "." && *component != "..")
+            .collect::<PathBuf>();
+        if path.as_os_str().is_empty() {
+            path.push(sanitize(&self.name));
+        }
+        if path.extension().is_none_or(|extension| extension != "mp4") {
+            let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+            file_name.push(".mp4");
+            path.set_file_name(file_name);
+        }
+        path
+    }
+}
+
+/// Directory the teleplay metadata goes to: the first directory of the episode path, or the save
+/// directory itself for flat templates.
+pub fn show_dir(save_dir: &Path, relative: &Path) -> PathBuf {
+    let mut components = relative.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(first)), Some(_)) => save_dir.join(first),
+        _ => save_dir.to_path_buf(),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn push_element(nfo: &mut String, tag: &str, value: Option<&str>) {
+    if let Some(value) = value
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+    {
+        nfo.push_str(&format!("  <{}>{}</{}>\n", tag, escape_xml(value), tag));
+    }
+}
+
+/// Kodi/Jellyfin `tvshow.nfo` of a teleplay, pointing at the saved artwork or else the cover url.
+pub fn tvshow_nfo(info: &TeleplayInfo, artwork: &Artwork) -> String {
+    let mut nfo = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    nfo.push_str("<tvshow>\n");
+    push_element(&mut nfo, "title", Some(&info.title));
+    push_element(&mut nfo, "year", year(info).as_deref());
+    push_element(&mut nfo, "premiered", info.release_time.as_deref());
+    push_element(
+        &mut nfo,
+        "plot",
+        info.introduction.as_deref().or(info.plot.as_deref()),
+    );
+    push_element(&mut nfo, "rating", info.score.as_deref());
+    push_element(&mut nfo, "country", info.region.as_deref());
+    push_element(&mut nfo, "status", info.status.as_deref());
+    if let Some(genre) = info.genre.as_ref() {
+        for genre in genre.split(['/', ',', '，', ' ']) {
+            push_element(&mut nfo, "genre", Some(genre));
+        }
+    }
+    for director in info.director.iter().flatten() {
+        push_element(&mut nfo, "director", Some(director));
+    }
+    for actor in info.starring.iter().flatten() {
+        if !actor.trim().is_empty() {
+            nfo.push_str(&format!(
+                "  <actor>\n    <name>{}</name>\n  </actor>\n",
+                escape_xml(actor.trim())
+            ));
+        }
+    }
+    if let Some(poster) = artwork.poster.as_deref().or(info.cover.as_deref()) {
+        nfo.push_str(&format!(
+            "  <thumb aspect=\"poster\">{}</thumb>\n",
+            escape_xml(poster)
+        ));
+    }
+    if !artwork.fanart.is_empty() {
+        nfo.push_str("  <fanart>\n");
+        for fanart in artwork.fanart.iter() {
+            nfo.push_str(&format!("    <thumb>{}</thumb>\n", escape_xml(fanart)));
+        }
+        nfo.push_str("  </fanart>\n");
+    }
+    nfo.push_str("</tvshow>\n");
+    nfo
+}
+
+/// Kodi/Jellyfin `.nfo` written next to an episode file.
+pub fn episode_nfo(context: &NamingContext) -> String {
+    let mut nfo = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+    nfo.push_str("<episodedetails>\n");
+    push_element(&mut nfo, "title", Some(&context.name));
+    push_element(&mut nfo, "showtitle", Some(&context.title));
+    push_element(&mut nfo, "season", Some(&context.season.to_string()));
+    push_element(&mut nfo, "episode", Some(&context.episode.to_string()));
+    nfo.push_str("</episodedetails>\n");
+    nfo
+}
+
+#[test]
+fn test_naming() {
+    assert_eq!(episode_number("第08集"), Some(8));
+    assert_eq!(episode_number("第十二集"), Some(12));
+    assert_eq!(episode_number("第一百零五话"), Some(105));
+    assert_eq!(episode_number("EP3"), Some(3));
+    assert_eq!(episode_number("ep 10 完"), Some(10));
+    assert_eq!(episode_number("S01E02"), Some(2));
+    assert_eq!(episode_number("08"), Some(8));
+    assert_eq!(episode_number("上"), Some(1));
+    assert_eq!(episode_number("繁花（下）"), Some(2));
+    assert_eq!(episode_number("HD国语"), None);
+    assert_eq!(episode_number("上海滩"), None);
+    assert_eq!(season_number("庆余年第二季"), Some(2));
+    assert_eq!(season_number("The Boys Season 3"), Some(3));
+    assert_eq!(season_number("繁花"), None);
+    assert_eq!(sanitize(" a/b: c?.. "), "a_b_ c_");
+
+    let info = TeleplayInfo {
+        title: "庆余年 第二季".to_string(),
+        times: Some("2024".to_string()),
+        ..TeleplayInfo::default()
+    };
+    let context = NamingContext::new(&info, "第08集", 8);
+    assert_eq!(
+        context.render("{title} ({year})/Season {season}/{title} - S{season:02}E{episode:02}"),
+        Path::new("庆余年 第二季 (2024)/Season 2/庆余年 第二季 - S02E08.mp4")
+    );
+    assert_eq!(
+        context.render(DEFAULT_TEMPLATE),
+        Path::new("庆余年 第二季/第08集.mp4")
+    );
+    let info = TeleplayInfo {
+        title: "a/b".to_string(),
+        ..TeleplayInfo::default()
+    };
+    let context = NamingContext::new(&info, "HD", 1);
+    assert_eq!(
+        context.render("{title} ({year})/{index:03}.mp4"),
+        Path::new("a_b/001.mp4")
+    );
+    assert_eq!(
+        show_dir(Path::new("video"), &context.render(DEFAULT_TEMPLATE)),
+        Path::new("video/a_b")
+    );
+    assert_eq!(
+        show_dir(Path::new("video"), &context.render("{name}")),
+        Path::new("video")
+    );
+    assert!(episode_nfo(&context).contains("<episode>1</episode>"));
+    let artwork = Artwork {
+        poster: Some("poster.jpg".to_string()),
+        fanart: vec!["fanart.jpg".to_string()],
+    };
+    let nfo = tvshow_nfo(&info, &artwork);
+    assert!(nfo.contains("<title>a/b</title>"));
+    assert!(nfo.contains("<thumb aspect=\"poster\">poster.jpg</thumb>"));
+    assert!(nfo.contains("<fanart>\n    <thumb>fanart.jpg</thumb>"));
+}
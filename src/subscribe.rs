@@ -0,0 +1,271 @@
+use crate::args::Src;
+use crate::vrsr::mirror;
+use crate::vrsr::TeleplayId;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const DATA_DIR: &str = ".vspider";
+const SUBSCRIPTIONS_FILE: &str = "subscriptions.json";
+const HISTORY_FILE: &str = "history.jsonl";
+
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC of a unix timestamp.
+pub fn format_time(time: u64) -> String {
+    let (days, seconds) = ((time / 86400) as i64, time % 86400);
+    // civil from days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    pub src: Src,
+    pub id: TeleplayId,
+    pub index: usize,
+    pub save_dir: Option<String>,
+    pub naming: String,
+    #[serde(default)]
+    pub nfo: bool,
+    #[serde(default)]
+    pub artwork: bool,
+    pub title: Option<String>,
+    /// Urls of the episodes fetched so far.
+    pub downloaded: Vec<String>,
+    pub created: u64,
+    pub last_sync: Option<u64>,
+}
+
+impl Subscription {
+    pub fn new(src: Src, id: TeleplayId, index: usize, save_dir: Option<String>, naming: String) -> Self {
+        Self {
+            src,
+            id,
+            index,
+            save_dir,
+            naming,
+            nfo: false,
+            artwork: false,
+            title: None,
+            downloaded: Vec::new(),
+            created: now(),
+            last_sync: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} {} [{}]", self.src, self.id, self.index)?;
+        if let Some(title) = self.title.as_ref() {
+            write!(f, " {}", title)?;
+        }
+        write!(f, " {} episodes", self.downloaded.len())?;
+        match self.last_sync {
+            Some(time) => write!(f, ", synced {}", format_time(time)),
+            None => write!(f, ", never synced"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time: u64,
+    pub src: Src,
+    pub id: TeleplayId,
+    pub title: Option<String>,
+    pub episode: String,
+    pub file: String,
+}
+
+impl HistoryEntry {
+    pub fn new(subscription: &Subscription, episode: &str, file: &str) -> Self {
+        Self {
+            time: now(),
+            src: subscription.src,
+            id: subscription.id.clone(),
+            title: subscription.title.clone(),
+            episode: episode.to_string(),
+            file: file.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:?} {} {} -> {}",
+            format_time(self.time),
+            self.src,
+            self.id,
+            self.episode,
+            self.file
+        )
+    }
+}
+
+/// The subscriptions kept as json in the data directory, with an append only history of the
+/// fetched episodes next to it.
+pub struct SubscriptionStore {
+    dir: PathBuf,
+    subscriptions: Vec<Subscription>,
+}
+
+impl SubscriptionStore {
+    pub fn open<P: AsRef<Path>>(dir: P) -> std::io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(SUBSCRIPTIONS_FILE);
+        let subscriptions = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { dir, subscriptions })
+    }
+
+    /// Write to a temporary file first so an interrupted save keeps the old subscriptions.
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = self.dir.join(SUBSCRIPTIONS_FILE);
+        let temp = self.dir.join(format!("{}.tmp", SUBSCRIPTIONS_FILE));
+        std::fs::write(&temp, serde_json::to_string_pretty(&self.subscriptions)?)?;
+        std::fs::rename(temp, path)
+    }
+
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    pub fn find(&self, src: Src, id: &TeleplayId) -> Option<&Subscription> {
+        self.subscriptions
+            .iter()
+            .find(|subscription| subscription.src == src && &subscription.id == id)
+    }
+
+    pub fn get_mut(&mut self, position: usize) -> &mut Subscription {
+        &mut self.subscriptions[position]
+    }
+
+    pub fn add(&mut self, subscription: Subscription) {
+        self.subscriptions.push(subscription);
+    }
+
+    pub fn remove(&mut self, src: Src, id: &TeleplayId) -> bool {
+        let count = self.subscriptions.len();
+        self.subscriptions
+            .retain(|subscription| subscription.src != src || &subscription.id != id);
+        count != self.subscriptions.len()
+    }
+
+    /// Move the fetched episode urls of `src` from its other `hosts` to the `active` one.
+    /// Returns the number of urls changed.
+    pub fn rewrite_hosts(&mut self, src: Src, hosts: &[String], active: &str) -> usize {
+        let mut changed = 0;
+        for subscription in self.subscriptions.iter_mut().filter(|s| s.src == src) {
+            for url in subscription.downloaded.iter_mut() {
+                let rewritten = mirror::rewrite_url(url, hosts, active);
+                if &rewritten != url {
+                    *url = rewritten;
+                    changed += 1;
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn append_history(&self, entries: &[HistoryEntry]) -> std::io::Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(HISTORY_FILE))?;
+        for entry in entries {
+            writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn history(&self) -> std::io::Result<Vec<HistoryEntry>> {
+        let path = self.dir.join(HISTORY_FILE);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for line in std::fs::read_to_string(path)?.lines() {
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(line)?);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[test]
+fn test_subscription_store() {
+    let dir = std::env::temp_dir().join("vspider-subscribe-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut store = SubscriptionStore::open(&dir).unwrap();
+    store.add(Subscription::new(
+        Src::XMB,
+        7.into(),
+        1,
+        None,
+        "{name}".to_string(),
+    ));
+    store
+        .get_mut(0)
+        .downloaded
+        .push("https://a.com/play/7-1-1.html".to_string());
+    store.save().unwrap();
+    store
+        .append_history(&[HistoryEntry::new(
+            &store.subscriptions()[0],
+            "第01集",
+            "a/第01集.mp4",
+        )])
+        .unwrap();
+
+    let mut store = SubscriptionStore::open(&dir).unwrap();
+    assert_eq!(store.subscriptions().len(), 1);
+    let hosts = vec!["https://a.com".to_string()];
+    assert_eq!(store.rewrite_hosts(Src::XMB, &hosts, "https://b.com"), 1);
+    assert_eq!(store.subscriptions()[0].downloaded[0], "https://b.com/play/7-1-1.html");
+    assert_eq!(store.find(Src::XMB, &7.into()).unwrap().downloaded.len(), 1);
+    assert!(store.find(Src::ZBKYYY, &7.into()).is_none());
+    assert_eq!(store.history().unwrap()[0].episode, "第01集");
+    assert!(store.remove(Src::XMB, &7.into()));
+    assert!(!store.remove(Src::XMB, &7.into()));
+
+    // ids saved as numbers before they became strings
+    let entry: HistoryEntry = serde_json::from_str(
+        r#"{"time":0,"src":"XMB","id":7,"title":null,"episode":"第01集","file":"a"}"#,
+    )
+    .unwrap();
+    assert_eq!(entry.id.as_str(), "7");
+
+    assert_eq!(format_time(0), "1970-01-01 00:00:00");
+    assert_eq!(format_time(1709251199), "2024-02-29 23:59:59");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
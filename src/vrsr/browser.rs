@@ -0,0 +1,172 @@
+use super::error::Error;
+use headless_chrome::browser::tab::Tab;
+use headless_chrome::{Browser, LaunchOptions};
+use log::{info, warn};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+static SHARED_POOL: OnceLock<Arc<BrowserPool>> = OnceLock::new();
+
+/// A tab borrowed from the pool, closed and given back when dropped.
+pub struct PooledTab {
+    tab: Arc<Tab>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledTab {
+    pub fn tab(&self) -> Arc<Tab> {
+        self.tab.clone()
+    }
+}
+
+impl Drop for PooledTab {
+    fn drop(&mut self) {
+        let _ = self.tab.close(false);
+    }
+}
+
+/// One browser process shared by every parser, launched on first use, with a cap on the number
+/// of tabs open at the same time.
+pub struct BrowserPool {
+    headless: bool,
+    timeout: Duration,
+    max_tabs: usize,
+    browser: Mutex<Option<Browser>>,
+    tabs: Arc<Semaphore>,
+}
+
+impl std::fmt::Debug for BrowserPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BrowserPool")
+            .field("headless", &self.headless)
+            .field("timeout", &self.timeout)
+            .field("max_tabs", &self.max_tabs)
+            .finish()
+    }
+}
+
+impl BrowserPool {
+    /// The process wide pool, created with the default options unless one was installed before.
+    pub fn shared() -> Arc<BrowserPool> {
+        SHARED_POOL
+            .get_or_init(|| BrowserPoolBuilder::new().build())
+            .clone()
+    }
+
+    pub async fn shutdown_shared() {
+        if let Some(pool) = SHARED_POOL.get() {
+            pool.shutdown().await;
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    async fn browser(&self) -> Result<Browser, Error> {
+        let mut browser = self.browser.lock().await;
+        if let Some(current) = browser.as_ref() {
+            if current.get_version().is_ok() {
+                return Ok(current.clone());
+            }
+            warn!("browser is gone, launch a new one");
+        }
+        let launch_options = LaunchOptions::default_builder()
+            .headless(self.headless)
+            .idle_browser_timeout(Duration::from_secs(24 * 60 * 60))
+            .build()
+            .map_err(|_| Error::BrowserError)?;
+        let launched = Browser::new(launch_options).map_err(|_| Error::BrowserError)?;
+        info!("launch browser headless={}", self.headless);
+        browser.replace(launched.clone());
+        Ok(launched)
+    }
+
+    pub async fn borrow_tab(&self) -> Result<PooledTab, Error> {
+        let permit = self
+            .tabs
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| Error::BrowserError)?;
+        let tab = self
+            .browser()
+            .await?
+            .new_tab()
+            .map_err(|_| Error::BrowserError)?;
+        tab.set_default_timeout(self.timeout);
+        Ok(PooledTab {
+            tab,
+            _permit: permit,
+        })
+    }
+
+    /// Close every tab and the browser process; later borrows fail.
+    pub async fn shutdown(&self) {
+        self.tabs.close();
+        if let Some(browser) = self.browser.lock().await.take() {
+            if let Ok(tabs) = browser.get_tabs().lock() {
+                for tab in tabs.iter() {
+                    let _ = tab.close(false);
+                }
+            }
+            info!("browser shutdown");
+        }
+    }
+}
+
+pub struct BrowserPoolBuilder {
+    headless: bool,
+    timeout: u64,
+    max_tabs: usize,
+}
+
+impl Default for BrowserPoolBuilder {
+    fn default() -> Self {
+        Self {
+            headless: false,
+            timeout: 60,
+            max_tabs: 2,
+        }
+    }
+}
+
+impl BrowserPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(unused)]
+    pub fn headless(&mut self, headless: bool) -> &mut Self {
+        self.headless = headless;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn timeout(&mut self, timeout: u64) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn max_tabs(&mut self, max_tabs: usize) -> &mut Self {
+        self.max_tabs = max_tabs.max(1);
+        self
+    }
+
+    pub fn build(&self) -> Arc<BrowserPool> {
+        Arc::new(BrowserPool {
+            headless: self.headless,
+            timeout: Duration::from_secs(self.timeout),
+            max_tabs: self.max_tabs,
+            browser: Mutex::new(None),
+            tabs: Arc::new(Semaphore::new(self.max_tabs)),
+        })
+    }
+
+    /// Build the pool and make it the shared one, unless a shared pool is already in use.
+    pub fn install(&self) -> Arc<BrowserPool> {
+        SHARED_POOL.get_or_init(|| self.build()).clone()
+    }
+}
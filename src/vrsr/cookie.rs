@@ -0,0 +1,397 @@
+use log::warn;
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use url::Url;
+
+/// Directory of the data directory the jars of the sites are kept in.
+pub const COOKIES_DIR: &str = "cookies";
+
+/// A cookie a site set or a browser exported, kept with the scope it is sent in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Host the cookie belongs to, without a leading dot.
+    pub domain: String,
+    /// Sent to the subdomains of `domain` too.
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Unix time it expires at, a session cookie when missing.
+    pub expires: Option<u64>,
+}
+
+impl Cookie {
+    /// Read a `Set-Cookie` header of a response of `url`.
+    pub fn parse(header: &str, url: &Url) -> Option<Self> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            include_subdomains: false,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    // a site only sets cookies of its own domain, never of a whole suffix
+                    // like `com`
+                    if !domain.contains('.') || !domain_matches(&host, &domain, true) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.include_subdomains = true;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "expires" => cookie.expires = parse_http_date(value),
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                _ => {}
+            }
+        }
+        if let Some(max_age) = max_age {
+            cookie.expires = Some((now() as i64 + max_age).max(0) as u64);
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Whether the cookie is sent with a request of `url`.
+    pub fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        if self.secure && url.scheme() != "https" {
+            return false;
+        }
+        let path = url.path();
+        let path_matches = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+        path_matches
+            && domain_matches(
+                &host.to_ascii_lowercase(),
+                &self.domain,
+                self.include_subdomains,
+            )
+    }
+}
+
+impl std::fmt::Display for Cookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let domain = if self.include_subdomains { "." } else { "" };
+        write!(f, "{}{}{} {}", domain, self.domain, self.path, self.name)?;
+        match self.expires {
+            Some(expires) => write!(f, " expires {}", expires),
+            None => write!(f, " session"),
+        }
+    }
+}
+
+fn domain_matches(host: &str, domain: &str, include_subdomains: bool) -> bool {
+    host == domain
+        || (include_subdomains
+            && host.len() > domain.len()
+            && host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.'))
+}
+
+/// Directory of the path of `url`, the path a cookie without one is sent to.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => url.path()[..end].to_string(),
+    }
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Unix time of a cookie date, e.g. `Wed, 21 Oct 2015 07:28:00 GMT` or
+/// `Wednesday, 21-Oct-15 07:28:00 GMT`.
+fn parse_http_date(date: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let (mut day, mut month, mut year, mut time) = (None, None, None, None);
+    for token in date
+        .split([' ', '-', ','])
+        .filter(|token| !token.is_empty())
+    {
+        let lower = token.to_ascii_lowercase();
+        if token.contains(':') {
+            let mut fields = token.split(':').map(|field| field.parse::<u64>().ok());
+            let (h, m, s) = (
+                fields.next()??,
+                fields.next()??,
+                fields.next().flatten().unwrap_or(0),
+            );
+            time = Some(h * 3600 + m * 60 + s);
+        } else if let Some(index) = MONTHS.iter().position(|name| lower.starts_with(name)) {
+            month = Some(index as i64 + 1);
+        } else if let Ok(number) = token.parse::<i64>() {
+            if day.is_none() && token.len() <= 2 && year.is_none() {
+                day = Some(number);
+            } else {
+                year = Some(match number {
+                    0..=69 => number + 2000,
+                    70..=99 => number + 1900,
+                    _ => number,
+                });
+            }
+        }
+    }
+    let (day, month, year, time) = (day?, month?, year?, time?);
+    // days from civil, http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    u64::try_from(days * 86400 + time as i64).ok()
+}
+
+/// Cookies of a Netscape `cookies.txt`, as exported by browsers and curl.
+pub fn parse_netscape(text: &str) -> Vec<Cookie> {
+    let mut cookies = Vec::new();
+    for line in text.lines() {
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() < 7 {
+            warn!("skip cookie line: {}", line);
+            continue;
+        }
+        let expires = fields[4].trim().parse::<u64>().unwrap_or(0);
+        cookies.push(Cookie {
+            name: fields[5].to_string(),
+            value: fields[6].trim_end_matches('\r').to_string(),
+            domain: fields[0].trim_start_matches('.').to_ascii_lowercase(),
+            include_subdomains: fields[1].eq_ignore_ascii_case("TRUE")
+                || fields[0].starts_with('.'),
+            path: fields[2].to_string(),
+            secure: fields[3].eq_ignore_ascii_case("TRUE"),
+            expires: (expires > 0).then_some(expires),
+        });
+    }
+    cookies
+}
+
+/// Cookies of a site, saved as json when the jar has a file. The ones responses set are saved
+/// once the jar is dropped, session cookies only live as long as the jar.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    path: Option<PathBuf>,
+    cookies: Mutex<Vec<Cookie>>,
+    /// Responses changed the cookies since the last save.
+    changed: AtomicBool,
+}
+
+impl CookieJar {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let cookies = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: Some(path),
+            cookies: Mutex::new(cookies),
+            changed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let now = now();
+        let cookies = self
+            .cookies()
+            .into_iter()
+            .filter(|cookie| cookie.expires.is_some() && !cookie.is_expired(now))
+            .collect::<Vec<_>>();
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(&cookies)?)?;
+        std::fs::rename(temp, path)?;
+        self.changed.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Add `cookie`, replacing the one of the same name and scope.
+    pub fn insert(&self, cookie: Cookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|known| {
+            (&known.name, &known.domain, &known.path)
+                != (&cookie.name, &cookie.domain, &cookie.path)
+        });
+        cookies.push(cookie);
+    }
+
+    pub fn cookies(&self) -> Vec<Cookie> {
+        self.cookies.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// `Cookie` header of a request of `url`, the longer paths first.
+    pub fn header(&self, url: &Url) -> Option<String> {
+        let now = now();
+        let mut cookies = self
+            .cookies
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now) && cookie.matches(url))
+            .cloned()
+            .collect::<Vec<_>>();
+        if cookies.is_empty() {
+            return None;
+        }
+        cookies.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs = cookies
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>();
+        Some(pairs.join("; "))
+    }
+}
+
+impl Drop for CookieJar {
+    fn drop(&mut self) {
+        if self.changed.load(Ordering::Relaxed) {
+            if let Err(e) = self.save() {
+                warn!("save cookies failed: {}", e);
+            }
+        }
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        for header in cookie_headers {
+            if let Some(cookie) = header
+                .to_str()
+                .ok()
+                .and_then(|header| Cookie::parse(header, url))
+            {
+                self.insert(cookie);
+                self.changed.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        self.header(url)
+            .and_then(|header| HeaderValue::from_str(&header).ok())
+    }
+}
+
+#[test]
+fn test_cookie_jar() {
+    let url = Url::parse("https://www.jugougou.me/user/login.html").unwrap();
+    let cookie = Cookie::parse(
+        "PHPSESSID=abc; path=/; domain=.jugougou.me; Expires=Wed, 21 Oct 2037 07:28:00 GMT",
+        &url,
+    )
+    .unwrap();
+    assert_eq!(cookie.domain, "jugougou.me");
+    assert!(cookie.include_subdomains);
+    assert_eq!(cookie.expires, Some(2139722880));
+    assert!(cookie.matches(&Url::parse("http://m.jugougou.me/vodplay/1-1-1.html").unwrap()));
+    assert!(!cookie.matches(&Url::parse("https://jugougou.me.cn/").unwrap()));
+    // other domains and expired cookies
+    assert!(Cookie::parse("a=1; domain=example.com", &url).is_none());
+    assert!(Cookie::parse("a=1; domain=.me", &url).is_none());
+    let expired = Cookie::parse("b=2; Max-Age=0", &url).unwrap();
+    assert!(expired.is_expired(now()));
+    assert_eq!(expired.path, "/user");
+
+    let cookies = parse_netscape(
+        "# Netscape HTTP Cookie File\n\
+         #HttpOnly_.xmb.app\tTRUE\t/\tFALSE\t0\tsid\t42\n\
+         tv.xmb.app\tFALSE\t/vod\tTRUE\t2139722880\tvip\t1\n\
+         broken line\n",
+    );
+    assert_eq!(cookies.len(), 2);
+    assert!(cookies[0].matches(&Url::parse("http://tv.xmb.app/").unwrap()));
+    assert_eq!(cookies[0].expires, None);
+    assert!(cookies[1].secure);
+
+    let path = std::env::temp_dir()
+        .join("vspider-cookie-test")
+        .join("xmb.json");
+    let _ = std::fs::remove_file(&path);
+    let jar = CookieJar::open(&path).unwrap();
+    for cookie in cookies {
+        jar.insert(cookie);
+    }
+    jar.insert(expired);
+    jar.save().unwrap();
+    assert_eq!(
+        jar.header(&Url::parse("https://tv.xmb.app/vod/1.html").unwrap())
+            .as_deref(),
+        Some("vip=1; sid=42")
+    );
+    assert_eq!(
+        jar.header(&Url::parse("http://tv.xmb.app/vod/1.html").unwrap())
+            .as_deref(),
+        Some("sid=42")
+    );
+    // the session cookie is not saved
+    drop(jar);
+    let jar = CookieJar::open(&path).unwrap();
+    assert_eq!(jar.cookies().len(), 1);
+
+    // the cookies responses set are saved when the jar is dropped
+    use reqwest::cookie::CookieStore;
+    let headers = [
+        HeaderValue::from_static("token=7; Max-Age=3600"),
+        HeaderValue::from_static("PHPSESSID=abc"),
+    ];
+    let url = Url::parse("https://tv.xmb.app/user/login.html").unwrap();
+    jar.set_cookies(&mut headers.iter(), &url);
+    assert_eq!(jar.cookies().len(), 3);
+    drop(jar);
+    let names = CookieJar::open(&path)
+        .unwrap()
+        .cookies()
+        .into_iter()
+        .map(|cookie| cookie.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["vip", "token"]);
+    std::fs::remove_file(&path).unwrap();
+}
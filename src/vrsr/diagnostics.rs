@@ -0,0 +1,173 @@
+use super::error::Error;
+use log::{info, warn};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static SNAPSHOT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Step of the scraping a page belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Search,
+    Detail,
+    Episode,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Stage::Search => "search",
+            Stage::Detail => "detail",
+            Stage::Episode => "episode",
+        };
+        // honour the width so reports can align stages
+        f.pad(name)
+    }
+}
+
+/// Where and on which page a parser failed, with a copy of the page when snapshots are enabled.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseDiagnostic {
+    pub site: String,
+    pub url: String,
+    pub stage: Stage,
+    pub selector: Option<String>,
+    pub message: String,
+    pub snapshot: Option<PathBuf>,
+}
+
+impl std::fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (site {}, {} page {}",
+            self.message, self.site, self.stage, self.url
+        )?;
+        if let Some(snapshot) = self.snapshot.as_ref() {
+            write!(f, ", snapshot {}", snapshot.display())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Save the pages parsers fail on to `dir`.
+pub fn install<P: AsRef<Path>>(dir: P) {
+    let _ = SNAPSHOT_DIR.set(dir.as_ref().to_path_buf());
+}
+
+fn save_snapshot(dir: &Path, diagnostic: &ParseDiagnostic, html: &str) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let name = format!(
+        "{}-{}-{}-{}.html",
+        diagnostic.site.replace([':', '/'], "_"),
+        diagnostic.stage,
+        secs,
+        &sha256::digest(&diagnostic.url)[..8]
+    );
+    let path = dir.join(name);
+    let header = format!(
+        "<!-- {} {}\n     {} -->\n",
+        diagnostic.stage,
+        diagnostic.url,
+        diagnostic.message.replace("--", "- -")
+    );
+    std::fs::write(&path, header + html)?;
+    Ok(path)
+}
+
+fn diagnose_in(dir: Option<&Path>, error: Error, stage: Stage, url: &str, html: &str) -> Error {
+    let selector = match &error {
+        Error::NotFound { selector, .. } => Some(selector.clone()),
+        Error::ParseError(_) | Error::SerdeJsonError(_) => None,
+        _ => return error,
+    };
+    let mut diagnostic = ParseDiagnostic {
+        site: url::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_default(),
+        url: url.to_string(),
+        stage,
+        selector,
+        message: error.to_string(),
+        snapshot: None,
+    };
+    if let Some(dir) = dir {
+        match save_snapshot(dir, &diagnostic, html) {
+            Ok(path) => {
+                info!("saved {} page snapshot to {}", stage, path.display());
+                diagnostic.snapshot.replace(path);
+            }
+            Err(e) => warn!("save page snapshot of {} error: {}", url, e),
+        }
+    }
+    Error::Diagnosed(Box::new(diagnostic))
+}
+
+/// Attach the site, page and stage to a parse failure on `html`, saving the page to the
+/// installed directory. Other errors, e.g. of requests, are returned unchanged.
+pub fn diagnose(error: Error, stage: Stage, url: &str, html: &str) -> Error {
+    diagnose_in(
+        SNAPSHOT_DIR.get().map(|dir| dir.as_path()),
+        error,
+        stage,
+        url,
+        html,
+    )
+}
+
+#[test]
+fn test_diagnose() {
+    let dir = std::env::temp_dir().join("vspider-diagnostics-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let selector = scraper::Selector::parse("div.detail > h1.title").unwrap();
+    let url = "https://www.example.com/detail/1.html";
+    let html = "<html><body><h1>changed</h1></body></html>";
+
+    let error = diagnose_in(
+        Some(&dir),
+        Error::not_found("title", &selector),
+        Stage::Detail,
+        url,
+        html,
+    );
+    let Error::Diagnosed(diagnostic) = &error else {
+        panic!("not diagnosed: {}", error);
+    };
+    assert_eq!(diagnostic.site, "www.example.com");
+    assert_eq!(
+        diagnostic.selector.as_deref(),
+        Some("div.detail > h1.title")
+    );
+    assert_eq!(
+        diagnostic.message,
+        "Failed to find title by `div.detail > h1.title`"
+    );
+    let snapshot = diagnostic.snapshot.as_ref().unwrap();
+    assert!(snapshot
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("www.example.com-detail-"));
+    assert!(std::fs::read_to_string(snapshot).unwrap().ends_with(html));
+    assert!(error
+        .to_string()
+        .contains("detail page https://www.example.com/detail/1.html"));
+
+    let error = diagnose_in(
+        Some(&dir),
+        Error::RequestOutOfTry(3),
+        Stage::Search,
+        url,
+        html,
+    );
+    assert!(matches!(error, Error::RequestOutOfTry(3)));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,4 +1,7 @@
+use super::diagnostics::ParseDiagnostic;
 use scraper::error::SelectorErrorKind;
+use scraper::selector::ToCss;
+use scraper::Selector;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -8,14 +11,29 @@ pub enum Error {
     RequestOutOfTry(u64),
     #[error("Response failed")]
     ResponseFailed(u16),
+    #[error("Url error: {0}")]
+    UrlError(#[from] url::ParseError),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     #[error("Parser error: {0}")]
     ParseError(String),
+    #[error("Failed to find {what} by `{selector}`")]
+    NotFound { what: String, selector: String },
+    #[error("{0}")]
+    Diagnosed(Box<ParseDiagnostic>),
     #[error("serde json error: {0}")]
     SerdeJsonError(#[from] serde_json::Error),
+    #[error("No teleplay id in {0}")]
+    InvalidId(String),
     #[error("browser error")]
     BrowserError,
+    #[error("browser timeout: {0}")]
+    BrowserTimeout(String),
+    #[error("Regex error: {0}")]
+    RegexError(#[from] regex::Error),
+    #[allow(unused)]
+    #[error("No fixture recorded for {0}")]
+    FixtureNotFound(String),
 }
 
 impl<'a> From<SelectorErrorKind<'a>> for Error {
@@ -23,3 +41,21 @@ impl<'a> From<SelectorErrorKind<'a>> for Error {
         Error::ParseError(format!("Selector error: {}", e))
     }
 }
+
+impl Error {
+    /// `selector` matched nothing where `what` was expected.
+    pub fn not_found(what: &str, selector: &Selector) -> Self {
+        Error::NotFound {
+            what: what.to_string(),
+            selector: selector.to_css_string(),
+        }
+    }
+
+    /// The element matched by `selector` lacks the attribute `attr` holding `what`.
+    pub fn attr_not_found(what: &str, selector: &Selector, attr: &str) -> Self {
+        Error::NotFound {
+            what: what.to_string(),
+            selector: format!("{}[{}]", selector.to_css_string(), attr),
+        }
+    }
+}
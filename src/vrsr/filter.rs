@@ -0,0 +1,141 @@
+use super::TeleplayInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TeleplayStatus {
+    Completed,
+    Updating,
+}
+
+impl TeleplayStatus {
+    /// Read the status from the free text sites show, e.g. `更新至第10集`, `全40集` or `HD`.
+    pub fn detect(status: &str) -> Option<Self> {
+        let status = status.trim();
+        if ["更新", "更至", "连载", "連載"]
+            .iter()
+            .any(|keyword| status.contains(keyword))
+            || (status.starts_with('第') && status.ends_with('集'))
+        {
+            return Some(TeleplayStatus::Updating);
+        }
+        if ["完结", "完結", "全", "完", "正片", "HD", "BD", "TC"]
+            .iter()
+            .any(|keyword| status.contains(keyword))
+        {
+            return Some(TeleplayStatus::Completed);
+        }
+        None
+    }
+}
+
+/// Filters applied to search results on our side, for sites whose search can not filter.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    pub year: Option<String>,
+    pub region: Option<String>,
+    pub genre: Option<String>,
+    pub status: Option<TeleplayStatus>,
+}
+
+impl SearchFilter {
+    pub fn is_empty(&self) -> bool {
+        self.year.is_none()
+            && self.region.is_none()
+            && self.genre.is_none()
+            && self.status.is_none()
+    }
+
+    fn check_field(expected: Option<&String>, value: Option<&str>) -> Option<bool> {
+        match (expected, value) {
+            (None, _) => Some(true),
+            (Some(expected), Some(value)) => Some(value.contains(expected.as_str())),
+            (Some(_), None) => None,
+        }
+    }
+
+    /// Whether the teleplay passes, `None` when a filtered field is not known yet and the detail
+    /// page has to be requested first.
+    pub fn check(&self, info: &TeleplayInfo) -> Option<bool> {
+        let year = info.times.as_deref().or(info.release_time.as_deref());
+        let checks = [
+            Self::check_field(self.year.as_ref(), year),
+            Self::check_field(self.region.as_ref(), info.region.as_deref()),
+            Self::check_field(self.genre.as_ref(), info.genre.as_deref()),
+            match (self.status, info.status.as_deref()) {
+                (None, _) => Some(true),
+                (Some(expected), Some(status)) => {
+                    Some(TeleplayStatus::detect(status) == Some(expected))
+                }
+                (Some(_), None) => None,
+            },
+        ];
+        if checks.contains(&Some(false)) {
+            return Some(false);
+        }
+        if checks.contains(&None) {
+            return None;
+        }
+        Some(true)
+    }
+
+    /// Like [`SearchFilter::check`] once everything known has been requested, unknown fields
+    /// reject the teleplay.
+    pub fn accepts(&self, info: &TeleplayInfo) -> bool {
+        self.check(info).unwrap_or(false)
+    }
+}
+
+#[test]
+fn test_search_filter() {
+    assert_eq!(
+        TeleplayStatus::detect("更新至第10集"),
+        Some(TeleplayStatus::Updating)
+    );
+    assert_eq!(
+        TeleplayStatus::detect("第12集"),
+        Some(TeleplayStatus::Updating)
+    );
+    assert_eq!(
+        TeleplayStatus::detect("全40集"),
+        Some(TeleplayStatus::Completed)
+    );
+    assert_eq!(
+        TeleplayStatus::detect("已完结"),
+        Some(TeleplayStatus::Completed)
+    );
+    assert_eq!(
+        TeleplayStatus::detect("HD国语"),
+        Some(TeleplayStatus::Completed)
+    );
+    assert_eq!(TeleplayStatus::detect("预告"), None);
+
+    let info = TeleplayInfo {
+        times: Some("2023".to_string()),
+        status: Some("全40集".to_string()),
+        ..TeleplayInfo::default()
+    };
+    let filter = SearchFilter::default();
+    assert!(filter.is_empty());
+    assert_eq!(filter.check(&info), Some(true));
+
+    let filter = SearchFilter {
+        year: Some("2023".to_string()),
+        status: Some(TeleplayStatus::Completed),
+        ..SearchFilter::default()
+    };
+    assert_eq!(filter.check(&info), Some(true));
+
+    let filter = SearchFilter {
+        year: Some("2023".to_string()),
+        region: Some("大陆".to_string()),
+        ..SearchFilter::default()
+    };
+    assert_eq!(filter.check(&info), None);
+    assert!(!filter.accepts(&info));
+
+    let filter = SearchFilter {
+        year: Some("2022".to_string()),
+        region: Some("大陆".to_string()),
+        ..SearchFilter::default()
+    };
+    assert_eq!(filter.check(&info), Some(false));
+}
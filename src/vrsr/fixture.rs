@@ -0,0 +1,268 @@
+use super::error::Error;
+use super::request::Requestor;
+use super::{create_resource, create_teleplay, Episode, Resource, Teleplay};
+use super::{EpisodeParse, GenerateInfo, ResourceParse, TeleplayParse};
+use super::{Request, RequestorBuilder, TeleplayId, TeleplayInfo, Uri};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const INDEX_FILE: &str = "index.json";
+/// Set to record the responses of the live sites into the fixtures instead of replaying them.
+pub const RECORD_VAR: &str = "VSPIDER_RECORD";
+
+/// A [`Request`] answering from recorded responses, so parsers can be tested offline. The
+/// fixture directory holds the response files and an `index.json` from request key, e.g.
+/// `GET https://…`, to file name.
+pub struct FixtureRequestor {
+    dir: PathBuf,
+    index: Mutex<BTreeMap<String, String>>,
+    recorder: Option<Arc<Requestor>>,
+}
+
+impl FixtureRequestor {
+    fn open(dir: &Path, recorder: Option<Arc<Requestor>>) -> Arc<Self> {
+        let index = std::fs::read_to_string(dir.join(INDEX_FILE))
+            .ok()
+            .and_then(|index| serde_json::from_str(&index).ok())
+            .unwrap_or_default();
+        Arc::new(Self {
+            dir: dir.to_path_buf(),
+            index: Mutex::new(index),
+            recorder,
+        })
+    }
+
+    /// Replay the fixtures in `dir`, or record them when [`RECORD_VAR`] is set.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Arc<Self> {
+        let recorder = std::env::var_os(RECORD_VAR)
+            .map(|_| RequestorBuilder::new().ignore_cache(true).build());
+        Self::open(dir.as_ref(), recorder)
+    }
+
+    fn key(method: &str, url: &str, body: Option<&str>) -> String {
+        match body {
+            Some(body) => format!("{} {} {}", method, url, body),
+            None => format!("{} {}", method, url),
+        }
+    }
+
+    fn form_body(form_data: &HashMap<String, String>) -> String {
+        let form_data = form_data.iter().collect::<BTreeMap<_, _>>();
+        url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(form_data)
+            .finish()
+    }
+
+    /// Readable file name of a recorded url, `host_path.ext`, numbered when taken.
+    fn file_name(&self, url: &str, content: &[u8]) -> String {
+        let stem = url::Url::parse(url)
+            .map(|url| format!("{}{}", url.host_str().unwrap_or_default(), url.path()))
+            .unwrap_or_else(|_| url.to_string())
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        let stem = stem.trim_matches('_').to_string();
+        let extension = match content.iter().find(|c| !c.is_ascii_whitespace()) {
+            Some(b'{') | Some(b'[') => "json",
+            Some(b'<') => "html",
+            Some(b'#') => "m3u8",
+            _ => "bin",
+        };
+        let index = self.index.lock().unwrap();
+        let mut name = format!("{}.{}", stem, extension);
+        let mut count = 1;
+        while index.values().any(|file| file == &name) {
+            count += 1;
+            name = format!("{}_{}.{}", stem, count, extension);
+        }
+        name
+    }
+
+    fn save(&self, key: String, content: &[u8]) -> Result<(), Error> {
+        let name = self.file_name(key.split(' ').nth(1).unwrap_or_default(), content);
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join(&name), content)?;
+        let mut index = self.index.lock().unwrap();
+        index.insert(key, name);
+        std::fs::write(
+            self.dir.join(INDEX_FILE),
+            serde_json::to_string_pretty(&*index)?,
+        )?;
+        Ok(())
+    }
+
+    fn load(&self, key: &str) -> Result<Vec<u8>, Error> {
+        let name = self
+            .index
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Error::FixtureNotFound(key.to_string()))?;
+        Ok(std::fs::read(self.dir.join(name))?)
+    }
+
+    fn load_text(&self, key: &str) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.load(key)?).to_string())
+    }
+}
+
+impl Request for FixtureRequestor {
+    async fn request(&self, url: &str) -> Result<String, Error> {
+        let key = Self::key("GET", url, None);
+        if let Some(recorder) = self.recorder.as_ref() {
+            let content = recorder.request(url).await?;
+            self.save(key, content.as_bytes())?;
+            return Ok(content);
+        }
+        self.load_text(&key)
+    }
+
+    async fn request_with_cache(&self, url: &str, _cache_time: Duration) -> Result<String, Error> {
+        self.request(url).await
+    }
+
+    async fn request_bytes_with_cache(
+        &self,
+        url: &str,
+        cache_time: Duration,
+    ) -> Result<Vec<u8>, Error> {
+        let key = Self::key("GET", url, None);
+        if let Some(recorder) = self.recorder.as_ref() {
+            let content = recorder.request_bytes_with_cache(url, cache_time).await?;
+            self.save(key, &content)?;
+            return Ok(content);
+        }
+        self.load(&key)
+    }
+
+    async fn post_request(
+        &self,
+        url: &str,
+        form_data: HashMap<String, String>,
+    ) -> Result<String, Error> {
+        let key = Self::key("POST", url, Some(&Self::form_body(&form_data)));
+        if let Some(recorder) = self.recorder.as_ref() {
+            let content = recorder.post_request(url, form_data).await?;
+            self.save(key, content.as_bytes())?;
+            return Ok(content);
+        }
+        self.load_text(&key)
+    }
+
+    async fn post_json_request(&self, url: &str, body: serde_json::Value) -> Result<String, Error> {
+        let key = Self::key("POST", url, Some(&body.to_string()));
+        if let Some(recorder) = self.recorder.as_ref() {
+            let content = recorder.post_json_request(url, body).await?;
+            self.save(key, content.as_bytes())?;
+            return Ok(content);
+        }
+        self.load_text(&key)
+    }
+}
+
+/// Fixture directory of a site, `fixtures/<site>` in the crate.
+pub fn fixture_dir(site: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(site)
+}
+
+/// What a parser made of the fixtures of its site.
+pub struct Replay {
+    pub results: Vec<TeleplayInfo>,
+    pub info: TeleplayInfo,
+    /// Source names with their episode urls.
+    pub sources: Vec<(Option<String>, Vec<String>)>,
+    /// Uri of the first episode.
+    pub uri: Uri,
+}
+
+/// Run a search for `keyword`, the detail page of `id` and its first episode through `parser`
+/// against the fixtures of `site`.
+pub async fn replay<P, I>(site: &str, parser: Arc<P>, keyword: &str, id: I) -> Result<Replay, Error>
+where
+    P: GenerateInfo + ResourceParse + TeleplayParse + EpisodeParse,
+    I: Into<TeleplayId>,
+{
+    let requestor = FixtureRequestor::new(fixture_dir(site));
+    let mut resource = create_resource(requestor.clone(), parser.clone());
+    let mut results = Vec::new();
+    for teleplay in resource.search(keyword).await? {
+        results.push(teleplay.lock().await.info().clone());
+    }
+
+    let mut teleplay = create_teleplay(requestor, parser, &id.into());
+    let mut sources = Vec::new();
+    for (name, episodes) in teleplay.request().await? {
+        let mut urls = Vec::new();
+        for episode in episodes {
+            urls.push(episode.lock().await.url().to_string());
+        }
+        sources.push((name.clone(), urls));
+    }
+    let episode = teleplay
+        .episodes()
+        .first()
+        .and_then(|(_, episodes)| episodes.first())
+        .cloned()
+        .ok_or_else(|| Error::ParseError("No episode parsed".to_string()))?;
+    let uri = episode.lock().await.request().await?;
+    Ok(Replay {
+        results,
+        info: teleplay.info().clone(),
+        sources,
+        uri,
+    })
+}
+
+#[tokio::test()]
+async fn test_fixture_requestor() {
+    let dir = std::env::temp_dir().join("vspider-fixture-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let requestor = FixtureRequestor::open(&dir, None);
+    requestor
+        .save(
+            "GET https://www.example.com/vod/1.html".to_string(),
+            b"<html></html>",
+        )
+        .unwrap();
+    let form_data = HashMap::from([
+        ("vid".to_string(), "a b".to_string()),
+        ("from".to_string(), "x".to_string()),
+    ]);
+    requestor
+        .save(
+            FixtureRequestor::key(
+                "POST",
+                "https://www.example.com/api.php",
+                Some(&FixtureRequestor::form_body(&form_data)),
+            ),
+            br#"{"url":"https://cdn.example.com/1.m3u8"}"#,
+        )
+        .unwrap();
+
+    let requestor = FixtureRequestor::open(&dir, None);
+    assert_eq!(
+        requestor
+            .request("https://www.example.com/vod/1.html")
+            .await
+            .unwrap(),
+        "<html></html>"
+    );
+    assert!(requestor
+        .post_request("https://www.example.com/api.php", form_data)
+        .await
+        .unwrap()
+        .contains("1.m3u8"));
+    assert!(matches!(
+        requestor
+            .request("https://www.example.com/vod/2.html")
+            .await,
+        Err(Error::FixtureNotFound(_))
+    ));
+    assert!(dir.join("www_example_com_api_php.json").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use url::Url;
+
+static MIRRORS: OnceLock<HashMap<String, SiteMirrors>> = OnceLock::new();
+
+/// Hosts a user added for a site and the one in use, overriding the built-in host.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SiteMirrors {
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    pub active: Option<String>,
+}
+
+/// Use the mirrors of the sites, keyed by their built-in host, for every parser of the process.
+pub fn install(mirrors: HashMap<String, SiteMirrors>) {
+    let _ = MIRRORS.set(mirrors);
+}
+
+/// The installed mirrors of the site whose built-in host is `host`.
+pub fn installed(host: &str) -> Option<&'static SiteMirrors> {
+    MIRRORS.get().and_then(|mirrors| mirrors.get(host))
+}
+
+/// `scheme://host[:port]` of a host url, which urls are rewritten by.
+pub fn origin(host: &str) -> Option<String> {
+    let url = Url::parse(host).ok()?;
+    url.host_str()?;
+    Some(url.origin().ascii_serialization())
+}
+
+/// `url` moved to the origin of `active` when it is on one of `hosts`, unchanged otherwise.
+pub fn rewrite_url(url: &str, hosts: &[String], active: &str) -> String {
+    let Some(active) = origin(active) else {
+        return url.to_string();
+    };
+    for host in hosts.iter().filter_map(|host| origin(host)) {
+        if host == active {
+            continue;
+        }
+        if let Some(rest) = url.strip_prefix(&host) {
+            if rest.is_empty() || rest.starts_with(['/', '?', '#']) {
+                return format!("{}{}", active, rest);
+            }
+        }
+    }
+    url.to_string()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HostHealth {
+    pub host: String,
+    pub ok: bool,
+    pub millis: u128,
+    pub status: Option<u16>,
+    /// Host the home page redirected to, the new domain of a moved site.
+    pub moved: Option<String>,
+    pub error: Option<String>,
+}
+
+impl std::fmt::Display for HostHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = if self.ok { "OK" } else { "FAIL" };
+        write!(f, "[{}] {} {}ms", state, self.host, self.millis)?;
+        if let Some(status) = self.status {
+            write!(f, " {}", status)?;
+        }
+        if let Some(moved) = self.moved.as_ref() {
+            write!(f, " moved to {}", moved)?;
+        }
+        if let Some(error) = self.error.as_ref() {
+            write!(f, " {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Request the home page of `host`, healthy when it answers with a success status in time.
+pub async fn check(client: &reqwest::Client, host: &str, timeout: Duration) -> HostHealth {
+    let start = Instant::now();
+    let mut health = HostHealth {
+        host: host.to_string(),
+        ok: false,
+        millis: 0,
+        status: None,
+        moved: None,
+        error: None,
+    };
+    match client.get(host).timeout(timeout).send().await {
+        Ok(response) => {
+            health.ok = response.status().is_success();
+            health.status.replace(response.status().as_u16());
+            let final_origin = response.url().origin().ascii_serialization();
+            if origin(host).is_some_and(|origin| origin != final_origin) {
+                health.moved.replace(final_origin);
+            }
+        }
+        Err(e) => {
+            health.error.replace(e.to_string());
+        }
+    }
+    health.millis = start.elapsed().as_millis();
+    health
+}
+
+#[test]
+fn test_rewrite_url() {
+    let hosts = vec![
+        "https://www.jugougou.me".to_string(),
+        "https://jgg.example.com".to_string(),
+    ];
+    assert_eq!(
+        rewrite_url(
+            "https://www.jugougou.me/vodplay/404-1-1.html",
+            &hosts,
+            "https://jgg.example.com"
+        ),
+        "https://jgg.example.com/vodplay/404-1-1.html"
+    );
+    // other sites and hosts that only share a prefix stay
+    assert_eq!(
+        rewrite_url(
+            "https://www.jugougou.me.cn/a.html",
+            &hosts,
+            "https://jgg.example.com"
+        ),
+        "https://www.jugougou.me.cn/a.html"
+    );
+    assert_eq!(
+        rewrite_url(
+            "https://cdn.example.com/a.m3u8",
+            &hosts,
+            "https://jgg.example.com"
+        ),
+        "https://cdn.example.com/a.m3u8"
+    );
+    assert_eq!(
+        origin("https://tv.xmb.app/index.php").as_deref(),
+        Some("https://tv.xmb.app")
+    );
+}
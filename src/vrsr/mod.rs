@@ -3,17 +3,31 @@ use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 
+use serde::{Deserialize, Serialize};
 use url::Url;
 
+pub mod browser;
+pub mod cookie;
+pub mod diagnostics;
 pub mod error;
+pub mod filter;
+pub mod mirror;
+#[cfg(test)]
+pub mod fixture;
 mod parser;
 pub mod request;
+pub mod sniff;
 
 pub use self::parser::ijujitv::IJUJITVParser;
 pub use self::parser::jugougou::JUGOUGOUParser;
 pub use self::parser::zbkyyy::ZBKYYYParser;
 pub use self::parser::xmb::XMBParser;
+pub use self::parser::parseapi;
+pub use self::browser::{BrowserPool, BrowserPoolBuilder};
+pub use self::diagnostics::{diagnose, Stage};
+pub use self::filter::{SearchFilter, TeleplayStatus};
 pub use self::request::RequestorBuilder;
+pub use self::sniff::{SniffRule, Sniffer};
 
 pub trait Request {
     async fn request(&self, url: &str) -> Result<String, self::error::Error>;
@@ -23,12 +37,26 @@ pub trait Request {
         cache_time: Duration,
     ) -> Result<String, self::error::Error>;
 
+    /// Binary body of a url, e.g. a cover image, cached like [`Request::request_with_cache`].
+    async fn request_bytes_with_cache(
+        &self,
+        url: &str,
+        cache_time: Duration,
+    ) -> Result<Vec<u8>, self::error::Error>;
+
     #[allow(unused)]
     async fn post_request(
         &self,
         url: &str,
         form_data: std::collections::HashMap<String, String>,
     ) -> Result<String, self::error::Error>;
+
+    #[allow(unused)]
+    async fn post_json_request(
+        &self,
+        url: &str,
+        body: serde_json::Value,
+    ) -> Result<String, self::error::Error>;
 }
 
 #[allow(unused)]
@@ -138,16 +166,76 @@ where
         self.uri = self
             .parser
             .parse(&body, &self.info.url, self.requestor.clone())
-            .await?;
+            .await
+            .map_err(|e| diagnose(e, Stage::Episode, &self.info.url, &body))?;
         return Ok(self.uri.clone());
     }
 }
 
+/// Opaque id of a teleplay on its site, a number on most sites but a slug or hash on others.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct TeleplayId(String);
+
+impl TeleplayId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for TeleplayId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(&self.0)
+    }
+}
+
+impl std::str::FromStr for TeleplayId {
+    type Err = std::convert::Infallible;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        Ok(Self(id.to_string()))
+    }
+}
+
+impl From<&str> for TeleplayId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl From<String> for TeleplayId {
+    fn from(id: String) -> Self {
+        Self(id)
+    }
+}
+
+impl From<u64> for TeleplayId {
+    fn from(id: u64) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TeleplayId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // subscriptions saved before ids became strings hold numbers
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Id {
+            Number(u64),
+            Text(String),
+        }
+        Ok(match Id::deserialize(deserializer)? {
+            Id::Number(id) => id.into(),
+            Id::Text(id) => id.into(),
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TeleplayInfo {
     pub title: String,
     pub home_page: String,
-    pub id: u64,
+    pub id: TeleplayId,
     pub release_time: Option<String>,
     pub language: Option<String>,
     pub times: Option<String>,
@@ -161,6 +249,8 @@ pub struct TeleplayInfo {
     pub plot: Option<String>,
     pub cover: Option<String>,
     pub status: Option<String>,
+    /// Posters and stills of the detail page besides the cover.
+    pub artwork: Vec<String>,
 }
 
 impl Default for TeleplayInfo {
@@ -168,7 +258,7 @@ impl Default for TeleplayInfo {
         Self {
             title: String::new(),
             home_page: String::new(),
-            id: 0u64,
+            id: TeleplayId::default(),
             release_time: None,
             language: None,
             times: None,
@@ -182,6 +272,7 @@ impl Default for TeleplayInfo {
             plot: None,
             cover: None,
             status: None,
+            artwork: Vec::new(),
         }
     }
 }
@@ -239,6 +330,30 @@ impl TeleplaySrc {
     }
 }
 
+/// Absolute url of a link found on a page, e.g. `/upload/1.jpg` or `//img.example.com/1.jpg`.
+pub fn resolve_url(base: &str, url: &str) -> String {
+    Url::parse(base)
+        .and_then(|base| base.join(url.trim()))
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| url.to_string())
+}
+
+/// Id of a detail page url, the last path segment without its extension, e.g. `101` of
+/// `/voddetail/101.html` or `fan-hua` of `/show/fan-hua/`.
+pub fn parse_id(home_page: &str) -> Result<TeleplayId, self::error::Error> {
+    let path = match Url::parse(home_page) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => home_page.split(['?', '#']).next().unwrap_or_default().to_string(),
+    };
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.split('.').next())
+        .filter(|id| !id.is_empty())
+        .map(TeleplayId::from)
+        .ok_or_else(|| self::error::Error::InvalidId(home_page.to_string()))
+}
+
 pub trait TeleplayParse {
     async fn parse(
         &self,
@@ -247,6 +362,42 @@ pub trait TeleplayParse {
         _teleplay_info: &mut TeleplayInfo,
         _requestor: Arc<impl Request>,
     ) -> Result<Vec<TeleplaySrc>, self::error::Error>;
+
+    /// Poster and still images of a detail page, the `og:image` first.
+    fn parse_artwork(&self, html: &str, org_rul: &str) -> Vec<String> {
+        let html = scraper::Html::parse_document(html);
+        let meta_selector = scraper::Selector::parse(
+            "meta[property='og:image'], meta[name='image'], meta[itemprop='image']",
+        )
+        .unwrap();
+        let img_selector = scraper::Selector::parse(
+            "[class*=detail] img, [class*=poster] img, [class*=still] img, [class*=photo] img",
+        )
+        .unwrap();
+        let meta_images = html
+            .select(&meta_selector)
+            .filter_map(|meta| meta.value().attr("content"));
+        let images = html.select(&img_selector).filter_map(|img| {
+            ["data-original", "data-src", "src"]
+                .iter()
+                .find_map(|attr| img.value().attr(attr))
+        });
+        let mut artwork: Vec<String> = Vec::new();
+        for image in meta_images.chain(images) {
+            let image = resolve_url(org_rul, image);
+            let path = image.split(['?', '#']).next().unwrap_or_default();
+            if image.starts_with("http")
+                && !path.ends_with(".gif")
+                && !path.ends_with(".svg")
+                && !artwork.contains(&image)
+            {
+                artwork.push(image);
+            }
+        }
+        // recommendation lists further down the page repeat other teleplays' covers
+        artwork.truncate(10);
+        artwork
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -275,7 +426,7 @@ where
     fn new(info: TeleplayInfo, requester: Arc<R>, parser: Arc<P>, eparser: Arc<EP>) -> Self;
     fn title(&self) -> &str;
     fn home_page(&self) -> &str;
-    fn id(&self) -> u64;
+    fn id(&self) -> &TeleplayId;
     fn release_time(&self) -> Option<&str>;
     fn language(&self) -> Option<&str>;
     fn times(&self) -> Option<&str>;
@@ -321,8 +472,8 @@ where
     fn home_page(&self) -> &str {
         return self.info.home_page.as_str();
     }
-    fn id(&self) -> u64 {
-        self.info.id
+    fn id(&self) -> &TeleplayId {
+        &self.info.id
     }
     fn release_time(&self) -> Option<&str> {
         self.info.release_time.as_ref().map(|s| s.as_str())
@@ -379,7 +530,7 @@ where
             .requestor
             .request_with_cache(&self.info.home_page, Duration::new(24 * 60 * 60 * 30, 0))
             .await?;
-        let mut hub_url = Url::parse(&self.info.home_page).unwrap();
+        let mut hub_url = Url::parse(&self.info.home_page)?;
         let teleplay_srcs = self
             .parser
             .parse(
@@ -388,7 +539,16 @@ where
                 &mut self.info,
                 self.requestor.clone(),
             )
-            .await?;
+            .await
+            .map_err(|e| diagnose(e, Stage::Detail, &self.info.home_page, &response))?;
+        self.info.artwork = self.parser.parse_artwork(&response, &self.info.home_page);
+        if let Some(cover) = self.info.cover.as_ref() {
+            self.info.cover.replace(resolve_url(&self.info.home_page, cover));
+        } else if !self.info.artwork.is_empty() {
+            self.info.cover.replace(self.info.artwork.remove(0));
+        }
+        let cover = self.info.cover.clone();
+        self.info.artwork.retain(|image| Some(image) != cover.as_ref());
         for teleplay_src in teleplay_srcs {
             let mut episodes_list = Vec::new();
             for mut episode_info in teleplay_src.episodes {
@@ -403,6 +563,49 @@ where
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+    pub id: String,
+    pub name: String,
+}
+
+impl Category {
+    /// The type ids a fresh MacCMS install uses, for sites whose navigation can not be parsed.
+    pub fn defaults() -> Vec<Category> {
+        [("1", "电影"), ("2", "连续剧"), ("3", "综艺"), ("4", "动漫")]
+            .iter()
+            .map(|(id, name)| Category {
+                id: id.to_string(),
+                name: name.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.id, self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Time,
+    Hits,
+    Score,
+}
+
+impl SortOrder {
+    /// The MacCMS `by` value of the order.
+    pub fn by(&self) -> &'static str {
+        match self {
+            SortOrder::Time => "time",
+            SortOrder::Hits => "hits",
+            SortOrder::Score => "score",
+        }
+    }
+}
+
 pub trait ResourceParse {
     async fn parse(
         &self,
@@ -410,30 +613,254 @@ pub trait ResourceParse {
         _org_rul: &str,
         _requestor: Arc<impl Request>,
     ) -> Result<Vec<TeleplayInfo>, self::error::Error>;
+
+    /// Url of the next search results page, from the pager of the usual MacCMS templates.
+    fn next_page(&self, html: &str, org_rul: &str) -> Option<String> {
+        let html = scraper::Html::parse_document(html);
+        let link_selector = scraper::Selector::parse("a").ok()?;
+        let next = html.select(&link_selector).find(|link| {
+            let text = link.text().collect::<String>();
+            let class = link.value().attr("class").unwrap_or_default();
+            let title = link.value().attr("title").unwrap_or_default();
+            matches!(text.trim(), "下一页" | "下页" | "下一頁" | "›" | "»")
+                || title == "下一页"
+                || class.split_whitespace().any(|c| c == "page-next" || c == "next")
+        })?;
+        let href = next.value().attr("href")?.trim();
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript") {
+            return None;
+        }
+        let next_url = Url::parse(org_rul).ok()?.join(href).ok()?.to_string();
+        Some(next_url).filter(|next_url| next_url != org_rul)
+    }
+
+    /// Categories linked from the site navigation.
+    fn parse_categories(&self, html: &str) -> Vec<Category> {
+        let html = scraper::Html::parse_document(html);
+        let link_selector = scraper::Selector::parse("a[href]").unwrap();
+        let id_regex = regex::Regex::new(r"(?:type|show)/(?:id/)?([^/.-]+)[-./]").unwrap();
+        let mut categories: Vec<Category> = Vec::new();
+        for link in html.select(&link_selector) {
+            let Some(captures) = id_regex.captures(link.value().attr("href").unwrap_or_default())
+            else {
+                continue;
+            };
+            let name = link.text().collect::<String>().trim().to_string();
+            if name.is_empty() || categories.iter().any(|c| c.id == captures[1]) {
+                continue;
+            }
+            categories.push(Category {
+                id: captures[1].to_string(),
+                name,
+            });
+        }
+        categories
+    }
+
+    /// Teleplays linked from a category or home page, by their detail page links.
+    fn parse_list(&self, html: &str, org_rul: &str) -> Vec<TeleplayInfo> {
+        let html = scraper::Html::parse_document(html);
+        let link_selector = scraper::Selector::parse("a[href]").unwrap();
+        let img_selector = scraper::Selector::parse("img").unwrap();
+        let note_selector =
+            scraper::Selector::parse("[class*=remarks], [class*=note], [class*=tips]").unwrap();
+        let id_regex = regex::Regex::new(r"detail/(?:id/)?([^/.]+)\.html").unwrap();
+        let Ok(base_url) = Url::parse(org_rul) else {
+            return Vec::new();
+        };
+        let mut infos: Vec<TeleplayInfo> = Vec::new();
+        for link in html.select(&link_selector) {
+            let href = link.value().attr("href").unwrap_or_default();
+            let Some(captures) = id_regex.captures(href) else {
+                continue;
+            };
+            let Ok(home_page) = base_url.join(href) else {
+                continue;
+            };
+            let title = link
+                .value()
+                .attr("title")
+                .map(|title| title.trim().to_string())
+                .unwrap_or_else(|| link.text().collect::<String>().trim().to_string());
+            let img = link.select(&img_selector).next();
+            if let Some(info) = infos
+                .iter_mut()
+                .find(|info| info.home_page == home_page.as_str())
+            {
+                // the cover link and the title link of a card point to the same page
+                if info.title.is_empty() {
+                    info.title = title;
+                }
+                if info.cover.is_none() {
+                    info.cover = img.and_then(|img| {
+                        ["data-original", "data-src", "src"]
+                            .iter()
+                            .find_map(|attr| img.value().attr(attr))
+                            .map(|cover| cover.to_string())
+                    });
+                }
+                continue;
+            }
+            let mut info = TeleplayInfo {
+                title,
+                home_page: home_page.to_string(),
+                id: captures[1].parse().unwrap_or_default(),
+                ..TeleplayInfo::default()
+            };
+            if let Some(img) = img {
+                info.cover = ["data-original", "data-src", "src"]
+                    .iter()
+                    .find_map(|attr| img.value().attr(attr))
+                    .map(|cover| cover.to_string());
+                if info.title.is_empty() {
+                    info.title = img.value().attr("alt").unwrap_or_default().to_string();
+                }
+            }
+            if let Some(note) = link.select(&note_selector).next() {
+                let note = note.text().collect::<String>().trim().to_string();
+                if !note.is_empty() {
+                    info.status.replace(note);
+                }
+            }
+            infos.push(info);
+        }
+        infos.retain(|info| !info.title.is_empty());
+        infos
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ResourceInfo {
     host: String,
+    /// Other domains the site is served on.
+    mirrors: Vec<String>,
     name: String,
     search_path: String,
     search_key: String,
+    show_path: String,
+    /// Path of a detail page, `{id}` is the teleplay id.
+    detail_path: String,
+    /// Path of a play page, `{id}`, `{sid}` and `{nid}` are the teleplay id and the numbers of
+    /// the source and the episode.
+    play_path: String,
+    ad_patterns: Vec<String>,
 }
 
 impl Default for ResourceInfo {
     fn default() -> Self {
         Self {
             host: String::new(),
+            mirrors: Vec::new(),
             name: String::new(),
             search_path: String::new(),
             search_key: String::new(),
+            show_path: String::new(),
+            detail_path: String::new(),
+            play_path: String::new(),
+            ad_patterns: Vec::new(),
+        }
+    }
+}
+
+impl ResourceInfo {
+    pub fn ad_patterns(&self) -> &[String] {
+        &self.ad_patterns
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The host in use, the built-in one unless a mirror was made active.
+    pub fn host(&self) -> &str {
+        mirror::installed(&self.host)
+            .and_then(|mirrors| mirrors.active.as_deref())
+            .unwrap_or(&self.host)
+    }
+
+    /// The built-in host, which identifies the site whatever domain it moved to.
+    pub fn default_host(&self) -> &str {
+        &self.host
+    }
+
+    /// Every known host of the site, the built-in one first, then the mirrors and the ones a
+    /// user added.
+    pub fn hosts(&self) -> Vec<String> {
+        let mut hosts = vec![self.host.clone()];
+        let installed = mirror::installed(&self.host);
+        let others = self.mirrors.iter().chain(
+            installed
+                .into_iter()
+                .flat_map(|mirrors| mirrors.hosts.iter().chain(mirrors.active.iter())),
+        );
+        for host in others {
+            if !hosts.contains(host) {
+                hosts.push(host.clone());
+            }
+        }
+        hosts
+    }
+
+    /// Whether `url` is on one of the hosts of the site, with or without `www.`.
+    pub fn owns(&self, url: &Url) -> bool {
+        let bare = |host: &str| host.trim_start_matches("www.").to_ascii_lowercase();
+        let Some(other) = url.host_str() else {
+            return false;
+        };
+        self.hosts().iter().any(|host| {
+            Url::parse(host)
+                .ok()
+                .and_then(|host| host.host_str().map(bare))
+                == Some(bare(other))
+        })
+    }
+
+    /// Which page of the site `url` is, told by its path.
+    pub fn page(&self, url: &Url) -> Option<Page> {
+        let path = url.path().trim_start_matches('/');
+        if !self.search_path.is_empty() && path == self.search_path.trim_start_matches('/') {
+            let keyword = url
+                .query_pairs()
+                .find(|(key, _)| key == &self.search_key)
+                .map(|(_, value)| value.to_string())?;
+            return Some(Page::Search(keyword));
+        }
+        if let Some(id) = Self::match_path(&self.detail_path, path) {
+            return Some(Page::Detail(id));
         }
+        Self::match_path(&self.play_path, path).map(Page::Play)
     }
+
+    fn match_path(template: &str, path: &str) -> Option<TeleplayId> {
+        if template.is_empty() {
+            return None;
+        }
+        let pattern = regex::escape(template.trim_start_matches('/'))
+            .replace(r"\{id\}", "(?P<id>[^/]+?)")
+            .replace(r"\{sid\}", r"\d+")
+            .replace(r"\{nid\}", r"\d+");
+        let regex = regex::Regex::new(&format!("^{}$", pattern)).ok()?;
+        regex
+            .captures(path)
+            .and_then(|captures| captures.name("id"))
+            .map(|id| TeleplayId::from(id.as_str()))
+    }
+}
+
+/// Kind of a page of a site, with what it is about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Page {
+    /// Results of searching the keyword.
+    Search(String),
+    /// Detail page of a teleplay, listing its sources.
+    Detail(TeleplayId),
+    /// Play page of an episode of the teleplay.
+    Play(TeleplayId),
 }
 
 pub trait GenerateInfo {
     fn generate_resource_info(&self) -> ResourceInfo;
-    fn generate_teleplay_info(&self, id: u64) -> TeleplayInfo;
+    fn generate_teleplay_info(&self, id: &TeleplayId) -> TeleplayInfo;
 }
 
 #[derive(Debug, Clone)]
@@ -477,6 +904,21 @@ where
         &'a mut self,
         keyword: &str,
     ) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error>;
+    async fn search_pages(
+        &'a mut self,
+        keyword: &str,
+        page: usize,
+        pages: usize,
+    ) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error>;
+    async fn categories(&self) -> Result<Vec<Category>, self::error::Error>;
+    async fn browse(
+        &'a mut self,
+        category: &Category,
+        sort: SortOrder,
+        page: usize,
+        pages: usize,
+    ) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error>;
+    async fn hot(&'a mut self) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error>;
 }
 
 impl<'a, R, P, WP, EP, W> Resource<'a, R, P, WP, EP> for BaseResource<'a, R, P, WP, EP, W>
@@ -507,7 +949,7 @@ where
     }
 
     fn host(&self) -> &str {
-        return self.info.host.as_str();
+        return self.info.host();
     }
 
     fn name(&self) -> &str {
@@ -522,23 +964,128 @@ where
         &'a mut self,
         keyword: &str,
     ) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error> {
-        let mut host = Url::parse(&self.info.host).unwrap();
-        let mut search_url = host.join(&self.info.search_path).unwrap();
+        self.search_pages(keyword, 1, 1).await
+    }
+
+    /// Search `pages` result pages starting at the 1-based `page`, following the pager links
+    /// of the site since the page urls differ between templates.
+    async fn search_pages(
+        &'a mut self,
+        keyword: &str,
+        page: usize,
+        pages: usize,
+    ) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error> {
+        let host = Url::parse(self.info.host())?;
+        let mut search_url = host.join(&self.info.search_path)?;
         search_url
             .query_pairs_mut()
             .append_pair(&self.info.search_key, keyword);
-        let search_url = search_url.to_string();
-        let respose = self
+        let mut search_url = search_url.to_string();
+        let mut home_pages = Vec::new();
+        let mut current = 1;
+        while current < page + pages {
+            let respose = self
+                .requestor
+                .request_with_cache(&search_url, Duration::new(24 * 60 * 60 * 30, 0))
+                .await?;
+            if current >= page {
+                let teleplay_infos = self
+                    .parser
+                    .parse(&respose, &search_url, self.requestor.clone())
+                    .await
+                    .map_err(|e| diagnose(e, Stage::Search, &search_url, &respose))?;
+                self.push_teleplays(teleplay_infos, &mut home_pages)?;
+            }
+            match self.parser.next_page(&respose, &search_url) {
+                Some(next_url) => search_url = next_url,
+                None => break,
+            }
+            current += 1;
+        }
+        Ok(self.teleplays.as_ref())
+    }
+
+    async fn categories(&self) -> Result<Vec<Category>, self::error::Error> {
+        let response = self
             .requestor
-            .request_with_cache(&search_url, Duration::new(24 * 60 * 60 * 30, 0))
+            .request_with_cache(self.info.host(), Duration::new(24 * 60 * 60, 0))
             .await?;
-        let teleplay_infos = self
-            .parser
-            .parse(&respose, &search_url, self.requestor.clone())
+        let categories = self.parser.parse_categories(&response);
+        if categories.is_empty() {
+            return Ok(Category::defaults());
+        }
+        Ok(categories)
+    }
+
+    /// Walk `pages` pages of a category from the 1-based `page`, stopping at the first empty one.
+    async fn browse(
+        &'a mut self,
+        category: &Category,
+        sort: SortOrder,
+        page: usize,
+        pages: usize,
+    ) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error> {
+        let host = Url::parse(self.info.host())?;
+        let mut home_pages = Vec::new();
+        for current in page..page + pages {
+            let show_path = self
+                .info
+                .show_path
+                .replace("{id}", &category.id)
+                .replace("{by}", sort.by())
+                .replace("{page}", &current.to_string());
+            let show_url = host.join(&show_path)?.to_string();
+            let response = self
+                .requestor
+                .request_with_cache(&show_url, Duration::new(60 * 60, 0))
+                .await?;
+            let teleplay_infos = self.parser.parse_list(&response, &show_url);
+            if teleplay_infos.is_empty() {
+                break;
+            }
+            self.push_teleplays(teleplay_infos, &mut home_pages)?;
+        }
+        Ok(self.teleplays.as_ref())
+    }
+
+    /// The teleplays recommended on the home page.
+    async fn hot(&'a mut self) -> Result<&'a Vec<Arc<Mutex<Self::TeleplayType>>>, self::error::Error> {
+        let response = self
+            .requestor
+            .request_with_cache(self.info.host(), Duration::new(60 * 60, 0))
             .await?;
-        for mut info in teleplay_infos {
-            host.set_path(&info.home_page);
-            info.home_page = host.to_string();
+        let teleplay_infos = self.parser.parse_list(&response, self.info.host());
+        self.push_teleplays(teleplay_infos, &mut Vec::new())?;
+        Ok(self.teleplays.as_ref())
+    }
+}
+
+impl<'a, R, P, WP, EP, W> BaseResource<'a, R, P, WP, EP, W>
+where
+    R: Request,
+    P: ResourceParse,
+    WP: TeleplayParse,
+    EP: EpisodeParse,
+    W: Teleplay<'a, R, WP, EP> + 'a,
+{
+    fn push_teleplays(
+        &mut self,
+        infos: Vec<TeleplayInfo>,
+        home_pages: &mut Vec<String>,
+    ) -> Result<(), self::error::Error> {
+        let mut host = Url::parse(self.info.host())?;
+        for mut info in infos {
+            if Url::parse(&info.home_page).is_err() {
+                host.set_path(&info.home_page);
+                info.home_page = host.to_string();
+            }
+            if let Some(cover) = info.cover.as_ref() {
+                info.cover.replace(resolve_url(&info.home_page, cover));
+            }
+            if home_pages.contains(&info.home_page) {
+                continue;
+            }
+            home_pages.push(info.home_page.clone());
             let teleplay = W::new(
                 info,
                 self.requestor.clone(),
@@ -547,7 +1094,7 @@ where
             );
             self.teleplays.push(Arc::new(Mutex::new(teleplay)));
         }
-        Ok(self.teleplays.as_ref())
+        Ok(())
     }
 }
 
@@ -573,7 +1120,7 @@ where
 pub fn create_teleplay<'a, R, P>(
     requestor: Arc<R>,
     parser: Arc<P>,
-    id: u64,
+    id: &TeleplayId,
 ) -> GeneralTeleplay<R, P>
 where
     R: Request,
@@ -586,3 +1133,120 @@ where
         parser.clone(),
     )
 }
+
+#[test]
+fn test_parse_list() {
+    let parser = ZBKYYYParser::default();
+    let html = r#"<html><body>
+        <ul class="nav">
+            <li><a href="/">首页</a></li>
+            <li><a href="/qyvodtype/1.html">电影</a></li>
+            <li><a href="/qyvodtype/2.html">电视剧</a></li>
+            <li><a href="/qyvodtype/dongman.html">动漫</a></li>
+            <li><a href="/qyvodshow/2--hits------1---.html">热播</a></li>
+        </ul>
+        <ul class="list">
+            <li>
+                <a class="pic" href="/qyvoddetail/101.html" title="剧一">
+                    <img data-original="/cover/101.jpg" src="/loading.gif">
+                    <span class="pic-remarks">更新至10集</span>
+                </a>
+                <a href="/qyvoddetail/101.html">剧一</a>
+            </li>
+            <li><a href="/qyvoddetail/102.html"><img src="/cover/102.jpg" alt="剧二"></a></li>
+            <li><a href="/qyvoddetail/fan-hua.html">繁花</a></li>
+        </ul>
+        <div class="page"><a href="?page=1">上一页</a><a href="?page=3">下一页</a></div>
+    </body></html>"#;
+
+    let categories = parser.parse_categories(html);
+    assert_eq!(categories.len(), 3);
+    assert_eq!(categories[1].id, "2");
+    assert_eq!(categories[1].name, "电视剧");
+    assert_eq!(categories[2].id, "dongman");
+
+    let infos = parser.parse_list(html, "https://www.zbkyyy.com/qyvodshow/2--time------2---.html");
+    assert_eq!(infos.len(), 3);
+    assert_eq!(infos[0].id.as_str(), "101");
+    assert_eq!(infos[0].title, "剧一");
+    assert_eq!(infos[0].home_page, "https://www.zbkyyy.com/qyvoddetail/101.html");
+    assert_eq!(infos[0].cover.as_deref(), Some("/cover/101.jpg"));
+    assert_eq!(infos[0].status.as_deref(), Some("更新至10集"));
+    assert_eq!(infos[1].title, "剧二");
+    assert_eq!(infos[2].id.as_str(), "fan-hua");
+    assert_eq!(infos[2].title, "繁花");
+
+    assert_eq!(
+        parser
+            .next_page(html, "https://www.zbkyyy.com/search.html?page=2")
+            .as_deref(),
+        Some("https://www.zbkyyy.com/search.html?page=3")
+    );
+    assert_eq!(parser.next_page("<a>下一页</a>", "https://a.com/"), None);
+}
+
+#[test]
+fn test_parse_artwork() {
+    let parser = ZBKYYYParser::default();
+    let html = r#"<html><head>
+        <meta property="og:image" content="//img.example.com/poster/101.jpg">
+        </head><body>
+        <div class="detail-pic"><img data-original="/upload/101.jpg" src="/loading.gif"></div>
+        <ul class="stills"><li><img src="/upload/101-1.png"></li><li><img src="/loading.gif"></li></ul>
+        </body></html>"#;
+    assert_eq!(
+        parser.parse_artwork(html, "https://www.example.com/qyvoddetail/101.html"),
+        vec![
+            "https://img.example.com/poster/101.jpg",
+            "https://www.example.com/upload/101.jpg",
+            "https://www.example.com/upload/101-1.png",
+        ]
+    );
+    assert_eq!(
+        resolve_url("https://www.example.com/a/b.html", "../c.jpg"),
+        "https://www.example.com/c.jpg"
+    );
+}
+
+#[test]
+fn test_parse_id() {
+    assert_eq!(parse_id("https://www.zbkyyy.com/qyvoddetail/101.html").unwrap().as_str(), "101");
+    assert_eq!(parse_id("/index.php/vod/detail/id/202.html").unwrap().as_str(), "202");
+    assert_eq!(parse_id("https://www.a.com/show/fan-hua/?from=home").unwrap().as_str(), "fan-hua");
+    let error = parse_id("https://www.zbkyyy.com/").unwrap_err();
+    assert!(matches!(error, self::error::Error::InvalidId(_)));
+    assert!(parse_id("").is_err());
+
+    let info = ZBKYYYParser::default().generate_resource_info();
+    assert!(info.owns(&Url::parse("https://zbkyyy.com/qyvoddetail/101.html").unwrap()));
+    assert!(!info.owns(&Url::parse("https://www.jugougou.me/voddetail/1.html").unwrap()));
+}
+
+#[test]
+fn test_page() {
+    let page = |info: &ResourceInfo, url: &str| info.page(&Url::parse(url).unwrap());
+    let info = ZBKYYYParser::default().generate_resource_info();
+    assert_eq!(
+        page(&info, "https://www.zbkyyy.com/qyvoddetail/101.html"),
+        Some(Page::Detail(101.into()))
+    );
+    assert_eq!(
+        page(&info, "https://zbkyyy.com/qyvodplay/101-2-13.html"),
+        Some(Page::Play(101.into()))
+    );
+    assert_eq!(
+        page(&info, "https://www.zbkyyy.com/qyvodsearch/-------------.html?wd=%E7%B9%81%E8%8A%B1"),
+        Some(Page::Search("繁花".to_string()))
+    );
+    assert_eq!(page(&info, "https://www.zbkyyy.com/qyvodshow/1--time------1---.html"), None);
+
+    let info = XMBParser::default().generate_resource_info();
+    assert_eq!(
+        page(&info, "https://tv.xmb.app/index.php/vod/play/id/202/sid/1/nid/3.html"),
+        Some(Page::Play(202.into()))
+    );
+    assert_eq!(
+        XMBParser::default().generate_teleplay_info(&202.into()).home_page,
+        "https://tv.xmb.app/index.php/vod/detail/id/202.html"
+    );
+}
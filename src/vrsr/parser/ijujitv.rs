@@ -1,24 +1,34 @@
 use super::super::error::Error;
-use super::super::{EpisodeInfo, ResourceInfo, TeleplayInfo, TeleplaySrc, URIType, Uri};
-use super::super::{EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
+use super::maccms::{self, MacPlayer};
+use super::super::{EpisodeInfo, ResourceInfo, TeleplayId, TeleplayInfo, TeleplaySrc, Uri};
+use super::super::{parse_id, EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
 use scraper::{Html, Selector};
-use serde_json::Value;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct IJUJITVParser {
     info: ResourceInfo,
+    player: MacPlayer,
 }
 
 impl IJUJITVParser {
+    /// The built-in host, which identifies the site.
+    pub const HOST: &'static str = "https://v.ijujitv.cc";
+
     pub fn new() -> Arc<Self> {
         Arc::new(Self {
             info: ResourceInfo {
                 name: "剧集TV".to_string(),
-                host: "https://v.ijujitv.cc".to_string(),
+                host: Self::HOST.to_string(),
+                mirrors: Vec::new(),
                 search_path: "search/-------------.html".to_string(),
                 search_key: "wd".to_string(),
+                show_path: "show/{id}--{by}------{page}---.html".to_string(),
+                detail_path: "detail/{id}.html".to_string(),
+                play_path: "play/{id}-{sid}-{nid}.html".to_string(),
+                ad_patterns: maccms::feed_ad_patterns(),
             },
+            player: MacPlayer::new(),
         })
     }
 }
@@ -28,11 +38,11 @@ impl GenerateInfo for IJUJITVParser {
         self.info.clone()
     }
 
-    fn generate_teleplay_info(&self, id: u64) -> TeleplayInfo {
-        let mut host_url = url::Url::parse(&self.info.host).unwrap();
-        host_url.set_path(&format!("detail/{}.html", id));
+    fn generate_teleplay_info(&self, id: &TeleplayId) -> TeleplayInfo {
+        let mut host_url = url::Url::parse(self.info.host()).unwrap();
+        host_url.set_path(&self.info.detail_path.replace("{id}", id.as_str()));
         TeleplayInfo {
-            id,
+            id: id.clone(),
             home_page: host_url.to_string(),
             ..TeleplayInfo::default()
         }
@@ -60,25 +70,16 @@ impl ResourceParse for IJUJITVParser {
             let a = teleplay
                 .select(&a_selector)
                 .next()
-                .ok_or_else(|| Error::ParseError("Failed to find title".to_string()))?;
+                .ok_or_else(|| Error::not_found("title", &a_selector))?;
             let title = a.value().attr("title");
             let home_page = a.value().attr("href");
             info.title = title
-                .ok_or_else(|| Error::ParseError("Failed to find title".to_string()))?
+                .ok_or_else(|| Error::attr_not_found("title", &a_selector, "title"))?
                 .to_string();
             info.home_page = home_page
-                .ok_or_else(|| Error::ParseError("Failed to find home page".to_string()))?
+                .ok_or_else(|| Error::attr_not_found("home page", &a_selector, "href"))?
                 .to_string();
-            info.id = info
-                .home_page
-                .split('/')
-                .last()
-                .unwrap()
-                .split('.')
-                .take(1)
-                .map(|v| v.parse::<u64>().unwrap())
-                .last()
-                .unwrap();
+            info.id = parse_id(&info.home_page)?;
             if let Some(cover) = teleplay.select(&img_selector).next() {
                 if let Some(cover) = cover.value().attr("src") {
                     info.cover.replace(cover.to_string());
@@ -199,7 +200,7 @@ impl TeleplayParse for IJUJITVParser {
                 let href = url
                     .value()
                     .attr("href")
-                    .ok_or_else(|| Error::ParseError("Failed to find episode url".to_string()))?;
+                    .ok_or_else(|| Error::attr_not_found("episode url", &uri_selector, "href"))?;
                 if href.starts_with("//") {
                     continue;
                 }
@@ -216,30 +217,38 @@ impl TeleplayParse for IJUJITVParser {
 }
 
 impl EpisodeParse for IJUJITVParser {
-    async fn parse(&self, html: &str, _org_rul: &str, _requestor: Arc<impl Request>) -> Result<Uri, Error> {
-        let html = Html::parse_document(html);
-        let m3u8_selector = Selector::parse("div.playBox script")?;
-        let m3u8_json = html
-            .select(&m3u8_selector)
-            .next()
-            .ok_or_else(|| Error::ParseError("Failed to find m3u8 json msg".to_string()))?
-            .inner_html()
-            .split("=")
-            .skip(1)
-            .collect::<Vec<_>>()
-            .join("")
-            .trim()
-            .to_string();
-        let msg: Value = serde_json::from_str(&m3u8_json)?;
-        Ok(Uri {
-            uri: msg["url"]
-                .as_str()
-                .ok_or_else(|| {
-                    Error::ParseError("Faild to find url string from json msg".to_string())
-                })?
-                .trim_matches('"')
-                .to_string(),
-            utype: URIType::M3U8,
-        })
+    async fn parse(&self, html: &str, org_rul: &str, requestor: Arc<impl Request>) -> Result<Uri, Error> {
+        self.player.resolve(html, org_rul, &self.info, requestor).await
     }
 }
+
+#[tokio::test()]
+async fn test_ijujitv_fixtures() {
+    use super::super::fixture::replay;
+    use super::super::URIType;
+    let replay = replay("ijujitv", IJUJITVParser::new(), "繁花", 303).await.unwrap();
+    let result = &replay.results[0];
+    assert_eq!(replay.results.len(), 1);
+    assert_eq!(result.id.as_str(), "303");
+    assert_eq!(result.title, "繁花");
+    assert_eq!(result.status.as_deref(), Some("全30集"));
+    assert_eq!(result.starring.as_ref().map(|s| s.len()), Some(3));
+
+    assert_eq!(replay.info.times.as_deref(), Some("2023"));
+    assert_eq!(replay.info.language.as_deref(), Some("国语"));
+    assert_eq!(replay.info.director, Some(vec!["王家卫".to_string()]));
+    assert_eq!(replay.info.update_time.as_deref(), Some("2024-01-09"));
+    assert_eq!(replay.info.introduction.as_deref(), Some("九十年代的上海，阿宝的故事"));
+    // only the first source is kept and links off the site are skipped
+    assert_eq!(replay.sources.len(), 1);
+    assert_eq!(
+        replay.sources[0].1,
+        vec![
+            "https://v.ijujitv.cc/play/303-1-1.html".to_string(),
+            "https://v.ijujitv.cc/play/303-1-2.html".to_string()
+        ]
+    );
+
+    assert_eq!(replay.uri.uri, "https://cdn.example.com/fanhua/ij01/index.m3u8");
+    assert!(matches!(replay.uri.utype, URIType::M3U8));
+}
@@ -1,70 +1,19 @@
+use super::super::browser::BrowserPool;
 use super::super::error::Error;
-use super::super::{EpisodeInfo, ResourceInfo, TeleplayInfo, TeleplaySrc, URIType, Uri};
-use super::super::{EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
-use headless_chrome::browser::tab::RequestInterceptor;
-use headless_chrome::protocol::cdp::Network::ResourceType;
-use headless_chrome::{Browser, LaunchOptions};
+use super::super::sniff::Sniffer;
+use super::maccms::{self, MacPlayer};
+use super::parseapi::{ParseApi, ParseMethod};
+use log::warn;
+use super::super::{EpisodeInfo, ResourceInfo, TeleplayId, TeleplayInfo, TeleplaySrc, Uri};
+use super::super::{parse_id, EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
 use scraper::{ElementRef, Html, Selector};
 use std::sync::Arc;
-use std::sync::RwLock;
-use tokio::sync::Notify;
-
-struct VideoInterceptor {
-    uri: Arc<RwLock<Option<Uri>>>,
-    tab: Arc<headless_chrome::browser::tab::Tab>,
-    notify: Notify,
-}
-
-impl VideoInterceptor {
-    fn new(tab: Arc<headless_chrome::browser::tab::Tab>) -> Self {
-        Self {
-            uri: Arc::new(RwLock::new(None)),
-            tab,
-            notify: Notify::new(),
-        }
-    }
-
-    async fn get_uri(&self) -> Uri {
-        self.notify.notified().await;
-        return self.uri.read().unwrap().as_ref().unwrap().clone();
-    }
-}
-
-impl RequestInterceptor for VideoInterceptor {
-    fn intercept(
-        &self,
-        _transport: Arc<headless_chrome::browser::transport::Transport>,
-        _session_id: headless_chrome::browser::transport::SessionId,
-        event: headless_chrome::protocol::cdp::Fetch::events::RequestPausedEvent,
-    ) -> headless_chrome::browser::tab::RequestPausedDecision {
-        if self.uri.read().unwrap().is_some() {
-            return headless_chrome::browser::tab::RequestPausedDecision::Continue(None);
-        }
-        if event.params.resource_Type == ResourceType::Xhr
-            && event.params.request.url.ends_with(".m3u8")
-        {
-            self.uri.write().unwrap().replace(Uri {
-                uri: event.params.request.url.clone(),
-                utype: URIType::M3U8,
-            });
-            let _ = self.tab.close(false);
-            self.notify.notify_one();
-        }
-        if event.params.resource_Type == ResourceType::Media {
-            self.uri.write().unwrap().replace(Uri {
-                uri: event.params.request.url.clone(),
-                utype: URIType::MP4,
-            });
-            let _ = self.tab.close(false);
-            self.notify.notify_one();
-        }
-        headless_chrome::browser::tab::RequestPausedDecision::Continue(None)
-    }
-}
 
 #[derive(Debug, Clone)]
 pub struct JUGOUGOUParser {
     info: ResourceInfo,
+    sniffer: Sniffer,
+    player: MacPlayer,
 }
 
 impl Default for JUGOUGOUParser {
@@ -72,15 +21,34 @@ impl Default for JUGOUGOUParser {
         Self {
             info: ResourceInfo {
                 name: "剧狗狗".to_string(),
-                host: "https://www.jugougou.me".to_string(),
+                host: Self::HOST.to_string(),
+                mirrors: Self::MIRRORS.iter().map(|host| host.to_string()).collect(),
                 search_path: "vodsearch/-------------.html".to_string(),
                 search_key: "wd".to_string(),
+                show_path: "vodshow/{id}--{by}------{page}---.html".to_string(),
+                detail_path: "voddetail/{id}.html".to_string(),
+                play_path: "vodplay/{id}-{sid}-{nid}.html".to_string(),
+                ad_patterns: maccms::feed_ad_patterns(),
             },
+            sniffer: Sniffer::new(),
+            player: MacPlayer::new().parse_api(
+                ParseApi::new(
+                    "jugougou",
+                    ParseMethod::PostForm,
+                    "/parse/api.php",
+                )
+                .param("vid", "{url}"),
+            ),
         }
     }
 }
 
 impl JUGOUGOUParser {
+    /// The built-in host, which identifies the site.
+    pub const HOST: &'static str = "https://www.jugougou.me";
+    /// Other domains the site answers on, requests fail over to them.
+    const MIRRORS: [&'static str; 1] = ["https://jugougou.me"];
+
     pub fn new() -> Arc<Self> {
         Arc::new(Self::default())
     }
@@ -91,11 +59,11 @@ impl GenerateInfo for JUGOUGOUParser {
         self.info.clone()
     }
 
-    fn generate_teleplay_info(&self, id: u64) -> TeleplayInfo {
-        let mut host_url = url::Url::parse(&self.info.host).unwrap();
-        host_url.set_path(&format!("voddetail/{}.html", id));
+    fn generate_teleplay_info(&self, id: &TeleplayId) -> TeleplayInfo {
+        let mut host_url = url::Url::parse(self.info.host()).unwrap();
+        host_url.set_path(&self.info.detail_path.replace("{id}", id.as_str()));
         TeleplayInfo {
-            id,
+            id: id.clone(),
             home_page: host_url.to_string(),
             ..TeleplayInfo::default()
         }
@@ -126,26 +94,17 @@ impl ResourceParse for JUGOUGOUParser {
             info.title = teleplay
                 .select(&title_selector)
                 .next()
-                .ok_or_else(|| Error::ParseError("Failed to find name".to_string()))?
+                .ok_or_else(|| Error::not_found("name", &title_selector))?
                 .inner_html();
             info.home_page = teleplay
                 .select(&home_page_selector)
                 .next()
-                .ok_or_else(|| Error::ParseError("Failed to find home page".to_string()))?
+                .ok_or_else(|| Error::not_found("home page", &home_page_selector))?
                 .value()
                 .attr("href")
-                .ok_or_else(|| Error::ParseError("Failed to find home page".to_string()))?
+                .ok_or_else(|| Error::attr_not_found("home page", &home_page_selector, "href"))?
                 .to_string();
-            info.id = info
-                .home_page
-                .split('/')
-                .last()
-                .unwrap()
-                .split('.')
-                .take(1)
-                .map(|v| v.parse::<u64>().unwrap())
-                .last()
-                .unwrap();
+            info.id = parse_id(&info.home_page)?;
             if let Some(cover) = teleplay.select(&cover_selector).next() {
                 if let Some(cover) = cover.value().attr("data-original") {
                     info.cover.replace(cover.to_string());
@@ -196,7 +155,7 @@ impl TeleplayParse for JUGOUGOUParser {
         let elements = html
             .select(&elements_selector)
             .next()
-            .ok_or_else(|| Error::ParseError("Failed to find elements".to_string()))?;
+            .ok_or_else(|| Error::not_found("details", &elements_selector))?;
 
         if let Some(title) = element_parse(elements, &title_selector) {
             if _teleplay_info.title.is_empty() {
@@ -243,7 +202,7 @@ impl TeleplayParse for JUGOUGOUParser {
             source.set_name(
                 src.select(&name_selector)
                     .next()
-                    .unwrap()
+                    .ok_or_else(|| Error::not_found("source name", &name_selector))?
                     .inner_html()
                     .trim(),
             );
@@ -253,7 +212,7 @@ impl TeleplayParse for JUGOUGOUParser {
                     url: uri
                         .value()
                         .attr("href")
-                        .ok_or_else(|| Error::ParseError("Failed to find episode url".to_string()))?
+                        .ok_or_else(|| Error::attr_not_found("episode url", &uri_selector, "href"))?
                         .to_string(),
                 };
                 source.append_episode(info);
@@ -309,32 +268,56 @@ impl TeleplayParse for JUGOUGOUParser {
 impl EpisodeParse for JUGOUGOUParser {
     async fn parse(
         &self,
-        _html: &str,
+        html: &str,
         org_rul: &str,
-        _requestor: Arc<impl Request>,
+        requestor: Arc<impl Request>,
     ) -> Result<Uri, Error> {
-        let launch_options = LaunchOptions::default_builder()
-            .headless(false)
-            .build()
-            .unwrap();
-        let browser = Browser::new(launch_options).map_err(|_| Error::BrowserError)?;
-        let tab = browser.new_tab().map_err(|_| Error::BrowserError)?;
-        let interceptor = Arc::new(VideoInterceptor::new(tab.clone()));
-        tab.enable_fetch(None, None)
-            .map_err(|_| Error::BrowserError)?;
-        tab.enable_request_interception(interceptor.clone())
-            .map_err(|_| Error::BrowserError)?;
-        tab.navigate_to(org_rul).map_err(|_| Error::BrowserError)?;
-        tab.wait_for_element("div#globalNotice")
-            .map_err(|_| Error::BrowserError)?;
-        tab.evaluate("redirectUrlToActive();", true)
-            .map_err(|_| Error::BrowserError)?;
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-        tab.wait_for_element("div.MacPlayer")
-            .map_err(|_| Error::BrowserError)?
-            .click()
-            .map_err(|_| Error::BrowserError)?;
-        let uri = interceptor.get_uri().await;
-        Ok(uri)
+        match self.player.resolve(html, org_rul, &self.info, requestor).await {
+            Ok(uri) => return Ok(uri),
+            Err(e) => warn!("resolve {} without browser failed: {}", org_rul, e),
+        }
+        // the shared pool is only resolved here so the one main installs with the browser
+        // arguments is used, not a default one fixed by building the parser
+        self.sniffer
+            .resolve(&BrowserPool::shared(), org_rul, |tab| {
+                tab.wait_for_element("div#globalNotice")
+                    .map_err(|_| Error::BrowserError)?;
+                tab.evaluate("redirectUrlToActive();", true)
+                    .map_err(|_| Error::BrowserError)?;
+                tab.wait_until_navigated()
+                    .map_err(|_| Error::BrowserError)?;
+                tab.wait_for_element("div.MacPlayer")
+                    .map_err(|_| Error::BrowserError)?
+                    .click()
+                    .map_err(|_| Error::BrowserError)?;
+                Ok(())
+            })
+            .await
     }
 }
+
+#[tokio::test()]
+async fn test_jugougou_fixtures() {
+    use super::super::fixture::replay;
+    use super::super::URIType;
+    let replay = replay("jugougou", JUGOUGOUParser::new(), "繁花", 404).await.unwrap();
+    let result = &replay.results[0];
+    assert_eq!(replay.results.len(), 1);
+    assert_eq!(result.id.as_str(), "404");
+    assert_eq!(result.title, "繁花");
+    assert_eq!(result.status.as_deref(), Some("全30集"));
+    assert_eq!(result.cover.as_deref(), Some("https://www.jugougou.me/upload/vod/404.jpg"));
+
+    assert_eq!(replay.info.genre.as_deref(), Some("剧情"));
+    assert_eq!(replay.info.region.as_deref(), Some("大陆"));
+    assert_eq!(replay.info.release_time.as_deref(), Some("2023"));
+    assert_eq!(replay.info.language.as_deref(), Some("国语"));
+    assert_eq!(replay.info.update_time.as_deref(), Some("2024-01-09"));
+    assert_eq!(replay.info.introduction.as_deref(), Some("九十年代的上海，阿宝的故事"));
+    assert_eq!(replay.sources.len(), 2);
+    assert_eq!(replay.sources[1].0.as_deref(), Some("备用"));
+
+    // the player url is an id resolved by the parse api, without the browser
+    assert_eq!(replay.uri.uri, "https://cdn.example.com/fanhua/jgg01/index.m3u8");
+    assert!(matches!(replay.uri.utype, URIType::M3U8));
+}
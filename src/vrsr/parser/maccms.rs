@@ -0,0 +1,293 @@
+use super::super::error::Error;
+use super::super::{resolve_url, Request, ResourceInfo, URIType, Uri};
+use super::parseapi::{self, ParseApi, ParseChain};
+use base64::Engine;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use std::sync::Arc;
+
+const PLAYER_VARIABLES: [&str; 2] = ["player_aaaa", "player_data"];
+/// Path markers of the ad clips the resource feeds behind MacCMS sites splice into their
+/// playlists, e.g. `https://cdn.example.com/20240101/adjump/1.ts`.
+const FEED_AD_PATTERNS: [&str; 1] = ["/adjump/"];
+
+/// The ad patterns of the sites playing resource feed m3u8s.
+pub fn feed_ad_patterns() -> Vec<String> {
+    FEED_AD_PATTERNS.iter().map(|pattern| pattern.to_string()).collect()
+}
+
+/// The `player_aaaa` object MacCMS templates put on every play page.
+#[allow(unused)]
+#[derive(Debug, Clone, Default)]
+pub struct PlayerData {
+    pub url: String,
+    pub url_next: Option<String>,
+    pub from: String,
+    pub encrypt: u8,
+}
+
+impl PlayerData {
+    fn from_value(value: &Value) -> Result<Self, Error> {
+        let encrypt = match &value["encrypt"] {
+            Value::Number(number) => number.as_u64().unwrap_or(0) as u8,
+            Value::String(string) => string.trim().parse().unwrap_or(0),
+            _ => 0,
+        };
+        let url = value["url"].as_str().ok_or_else(|| {
+            Error::ParseError("Faild to find url string from player data".to_string())
+        })?;
+        let url_next = value["url_next"]
+            .as_str()
+            .filter(|url| !url.is_empty())
+            .map(|url| decode_url(url, encrypt))
+            .transpose()?;
+        Ok(Self {
+            url: decode_url(url, encrypt)?,
+            url_next,
+            from: value["from"].as_str().unwrap_or_default().to_string(),
+            encrypt,
+        })
+    }
+}
+
+/// JavaScript `unescape`, which also handles the `%uXXXX` form `encodeURIComponent` does not produce.
+pub fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = String::with_capacity(value.len());
+    let mut pending: Vec<u8> = Vec::new();
+    let flush = |pending: &mut Vec<u8>, result: &mut String| {
+        if !pending.is_empty() {
+            result.push_str(&String::from_utf8_lossy(pending));
+            pending.clear();
+        }
+    };
+    let hex = |slice: &[u8]| {
+        std::str::from_utf8(slice)
+            .ok()
+            .and_then(|s| u32::from_str_radix(s, 16).ok())
+    };
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            if bytes.get(index + 1) == Some(&b'u') && index + 6 <= bytes.len() {
+                if let Some(code) = hex(&bytes[index + 2..index + 6]) {
+                    flush(&mut pending, &mut result);
+                    result.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    index += 6;
+                    continue;
+                }
+            }
+            if index + 3 <= bytes.len() {
+                if let Some(byte) = hex(&bytes[index + 1..index + 3]) {
+                    // %XX sequences may form a multi byte utf-8 character together
+                    pending.push(byte as u8);
+                    index += 3;
+                    continue;
+                }
+            }
+        }
+        flush(&mut pending, &mut result);
+        let ch = value[index..].chars().next().unwrap();
+        result.push(ch);
+        index += ch.len_utf8();
+    }
+    flush(&mut pending, &mut result);
+    result
+}
+
+/// Decode a player url according to the MacPlayer `encrypt` mode: 0 plain, 1 `escape`d and
+/// 2 base64 of the `escape`d url.
+pub fn decode_url(url: &str, encrypt: u8) -> Result<String, Error> {
+    match encrypt {
+        0 => Ok(url.to_string()),
+        1 => Ok(unescape(url)),
+        2 => {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(url.trim())
+                .map_err(|e| Error::ParseError(format!("Base64 decode player url error: {}", e)))?;
+            Ok(unescape(&String::from_utf8_lossy(&decoded)))
+        }
+        _ => Err(Error::ParseError(format!(
+            "Unsupported player encrypt mode {}",
+            encrypt
+        ))),
+    }
+}
+
+/// The object literal assigned to `name` in a script, found by matching braces so further
+/// statements after it do not matter.
+fn extract_object<'a>(script: &'a str, name: &str) -> Option<&'a str> {
+    let mut search = 0;
+    while let Some(found) = script[search..].find(name) {
+        let after = search + found + name.len();
+        search = after;
+        let rest = script[after..].trim_start();
+        let Some(rest) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        if !rest.starts_with('{') {
+            continue;
+        }
+        let start = script.len() - rest.len();
+        let (mut depth, mut quote, mut escaped) = (0usize, None, false);
+        for (offset, ch) in rest.char_indices() {
+            if let Some(q) = quote {
+                if escaped {
+                    escaped = false;
+                } else if ch == '\\' {
+                    escaped = true;
+                } else if ch == q {
+                    quote = None;
+                }
+                continue;
+            }
+            match ch {
+                '"' | '\'' => quote = Some(ch),
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(&script[start..start + offset + 1]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        return None;
+    }
+    None
+}
+
+pub fn find_player_data(html: &str) -> Result<PlayerData, Error> {
+    let html = Html::parse_document(html);
+    let script_selector = Selector::parse("script")?;
+    for script in html.select(&script_selector) {
+        let script = script.inner_html();
+        for name in PLAYER_VARIABLES {
+            if let Some(object) = extract_object(&script, name) {
+                let value: Value = serde_json::from_str(object)?;
+                return PlayerData::from_value(&value);
+            }
+        }
+    }
+    Err(Error::not_found("player data", &script_selector))
+}
+
+fn uri_type(url: &str) -> URIType {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    if path.ends_with(".mp4") {
+        URIType::MP4
+    } else {
+        URIType::M3U8
+    }
+}
+
+fn is_direct(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.ends_with(".m3u8") || path.ends_with(".mp4")
+}
+
+/// Resolves the video of a MacCMS play page from its player data, without a browser. Sources
+/// whose url is only an id for the site player are sent through the parse apis accepting their
+/// `from`, the configured ones of the site or else the built-in ones.
+#[derive(Debug, Clone, Default)]
+pub struct MacPlayer {
+    parse_chain: ParseChain,
+}
+
+impl MacPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse_api(mut self, api: ParseApi) -> Self {
+        self.parse_chain.push(api);
+        self
+    }
+
+    /// Video of the play page at `org_rul` of the site `info`, relative player urls are joined
+    /// with it.
+    pub async fn resolve(
+        &self,
+        html: &str,
+        org_rul: &str,
+        info: &ResourceInfo,
+        requestor: Arc<impl Request>,
+    ) -> Result<Uri, Error> {
+        let data = find_player_data(html)?;
+        let url = data.url.trim().to_string();
+        if url.is_empty() {
+            return Err(Error::ParseError("Empty player url".to_string()));
+        }
+        let joined = resolve_url(org_rul, &url);
+        if is_direct(&joined) {
+            return Ok(Uri {
+                utype: uri_type(&joined),
+                uri: joined,
+            });
+        }
+        let parse_chain = parseapi::installed(info.default_host()).unwrap_or(&self.parse_chain);
+        if !parse_chain.accepts(&data.from) {
+            return Err(Error::ParseError(format!(
+                "no parse api for from={}",
+                data.from
+            )));
+        }
+        parse_chain
+            .resolve(&url, &data.from, info.host(), requestor)
+            .await
+    }
+}
+
+#[test]
+fn test_find_player_data() {
+    let html = r#"<html><body><script type="text/javascript">var player_aaaa={"flag":"play","encrypt":0,"link":"/play/1-1-1.html","url":"https:\/\/cdn.example.com\/a=b\/index.m3u8","url_next":"","from":"ffm3u8"};var a = {"x":1};</script></body></html>"#;
+    let data = find_player_data(html).unwrap();
+    assert_eq!(data.url, "https://cdn.example.com/a=b/index.m3u8");
+    assert_eq!(data.from, "ffm3u8");
+    assert!(data.url_next.is_none());
+
+    let html = r#"<script>var player_aaaa={"encrypt":"1","url":"https%3A%2F%2Fcdn.example.com%2F%u4E2D.mp4","from":"x"}</script>"#;
+    let data = find_player_data(html).unwrap();
+    assert_eq!(data.url, "https://cdn.example.com/中.mp4");
+    assert!(matches!(uri_type(&data.url), URIType::MP4));
+
+    let encoded = base64::engine::general_purpose::STANDARD
+        .encode("https%3A%2F%2Fcdn.example.com%2F%E4%B8%AD%7D.m3u8");
+    let html = format!(
+        r#"<script>var player_data = {{"encrypt":2,"url":"{}","url_next":"{}","from":"x"}};</script>"#,
+        encoded, encoded
+    );
+    let data = find_player_data(&html).unwrap();
+    assert_eq!(data.url, "https://cdn.example.com/中}.m3u8");
+    assert_eq!(
+        data.url_next.as_deref(),
+        Some("https://cdn.example.com/中}.m3u8")
+    );
+
+    assert!(find_player_data("<script>var a = 1;</script>").is_err());
+    assert_eq!(unescape("a%20b%zz%u"), "a b%zz%u");
+}
+
+#[tokio::test()]
+async fn test_resolve_player() {
+    use super::super::fixture::{fixture_dir, FixtureRequestor};
+
+    let requestor = FixtureRequestor::new(fixture_dir("maccms"));
+    let player = MacPlayer::new();
+    let info = ResourceInfo::default();
+    let page = "https://www.example.com/play/1-1-1.html";
+    let html = r#"<script>var player_aaaa={"url":"\/m3u8\/1\/index.m3u8","from":"local"}</script>"#;
+    let uri = player.resolve(html, page, &info, requestor.clone()).await.unwrap();
+    assert_eq!(uri.uri, "https://www.example.com/m3u8/1/index.m3u8");
+    assert!(matches!(uri.utype, URIType::M3U8));
+
+    let html = r#"<script>var player_aaaa={"url":"a1b2c3","from":"vendor"}</script>"#;
+    let error = player.resolve(html, page, &info, requestor).await.unwrap_err();
+    assert!(
+        matches!(&error, Error::ParseError(message) if message == "no parse api for from=vendor"),
+        "{}",
+        error
+    );
+}
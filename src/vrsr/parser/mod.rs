@@ -2,3 +2,5 @@ pub mod zbkyyy;
 pub mod ijujitv;
 pub mod jugougou;
 pub mod xmb;
+mod maccms;
+pub mod parseapi;
@@ -0,0 +1,309 @@
+use super::super::error::Error;
+use super::super::{resolve_url, Request, URIType, Uri};
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+static PARSE_APIS: OnceLock<HashMap<String, ParseChain>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMethod {
+    #[default]
+    Get,
+    PostForm,
+    PostJson,
+}
+
+/// How the video url is taken out of a parse api response.
+#[derive(Debug, Clone)]
+pub enum Extractor {
+    /// `url`, `data.url`, a MacCMS player object or any quoted m3u8/mp4 url, in that order.
+    Auto,
+    /// Dotted path into a json response, e.g. `data.url`.
+    Json(String),
+    /// First capture group of a regex.
+    Regex(Regex),
+}
+
+impl Extractor {
+    pub fn json(path: &str) -> Self {
+        Extractor::Json(path.to_string())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, Error> {
+        Ok(Extractor::Regex(Regex::new(pattern)?))
+    }
+
+    fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a str> {
+        path.split('.')
+            .filter(|key| !key.is_empty())
+            .try_fold(value, |value, key| match value {
+                Value::Array(array) => key.parse::<usize>().ok().and_then(|i| array.get(i)),
+                _ => value.get(key),
+            })?
+            .as_str()
+    }
+
+    pub fn extract(&self, response: &str) -> Option<String> {
+        let url = match self {
+            Extractor::Auto => {
+                if let Ok(value) = serde_json::from_str::<Value>(response) {
+                    if let Some(url) = Self::json_path(&value, "url")
+                        .or_else(|| Self::json_path(&value, "data.url"))
+                    {
+                        return Some(url.trim().to_string()).filter(|url| !url.is_empty());
+                    }
+                }
+                if let Ok(data) = super::maccms::find_player_data(response) {
+                    return Some(data.url);
+                }
+                let regex = Regex::new(
+                    r#"(?:url|vurl|video_url)["']?\s*[:=]\s*["']([^"']+\.(?:m3u8|mp4)[^"']*)["']"#,
+                )
+                .unwrap();
+                regex.captures(response).map(|c| c[1].to_string())
+            }
+            Extractor::Json(path) => serde_json::from_str::<Value>(response)
+                .ok()
+                .and_then(|value| Self::json_path(&value, path).map(|url| url.to_string())),
+            Extractor::Regex(regex) => regex
+                .captures(response)
+                .and_then(|c| c.get(1).map(|m| m.as_str().to_string())),
+        }?;
+        Some(url.trim().replace("\\/", "/")).filter(|url| !url.is_empty())
+    }
+}
+
+/// One third-party parse endpoint ("解析接口"). `{url}` and `{from}` in the url template and the
+/// parameter values are replaced by the player url and source; in a GET url they are percent
+/// encoded. A url template without a host is taken on the host the site is used on.
+#[derive(Debug, Clone)]
+pub struct ParseApi {
+    name: String,
+    from: Vec<String>,
+    method: ParseMethod,
+    url: String,
+    params: Vec<(String, String)>,
+    extractor: Extractor,
+}
+
+impl ParseApi {
+    pub fn new(name: &str, method: ParseMethod, url: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            from: Vec::new(),
+            method,
+            url: url.to_string(),
+            params: Vec::new(),
+            extractor: Extractor::Auto,
+        }
+    }
+
+    /// Only use the api for these `from` values, every source when empty.
+    pub fn from(mut self, from: &[&str]) -> Self {
+        self.from = from.iter().map(|from| from.to_string()).collect();
+        self
+    }
+
+    pub fn param(mut self, key: &str, value: &str) -> Self {
+        self.params.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn extractor(mut self, extractor: Extractor) -> Self {
+        self.extractor = extractor;
+        self
+    }
+
+    fn accepts(&self, from: &str) -> bool {
+        self.from.is_empty() || self.from.iter().any(|f| f == from)
+    }
+
+    fn fill(template: &str, url: &str, from: &str, encode: bool) -> String {
+        let encoded = |value: &str| {
+            if encode {
+                url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+            } else {
+                value.to_string()
+            }
+        };
+        template
+            .replace("{url}", &encoded(url))
+            .replace("{from}", &encoded(from))
+    }
+
+    async fn call(
+        &self,
+        url: &str,
+        from: &str,
+        host: &str,
+        requestor: &Arc<impl Request>,
+    ) -> Result<String, Error> {
+        let api_url = resolve_url(host, &Self::fill(&self.url, url, from, true));
+        match self.method {
+            ParseMethod::Get => {
+                let mut api_url = url::Url::parse(&api_url).map_err(|e| {
+                    Error::ParseError(format!("Invalid parse api url {}: {}", api_url, e))
+                })?;
+                if !self.params.is_empty() {
+                    let mut query = api_url.query_pairs_mut();
+                    for (key, value) in self.params.iter() {
+                        query.append_pair(key, &Self::fill(value, url, from, false));
+                    }
+                }
+                requestor.request(api_url.as_str()).await
+            }
+            ParseMethod::PostForm => {
+                let form_data = self
+                    .params
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Self::fill(value, url, from, false)))
+                    .collect::<HashMap<_, _>>();
+                requestor.post_request(&api_url, form_data).await
+            }
+            ParseMethod::PostJson => {
+                let body = self
+                    .params
+                    .iter()
+                    .map(|(key, value)| {
+                        (
+                            key.clone(),
+                            Value::String(Self::fill(value, url, from, false)),
+                        )
+                    })
+                    .collect::<serde_json::Map<_, _>>();
+                requestor
+                    .post_json_request(&api_url, Value::Object(body))
+                    .await
+            }
+        }
+    }
+}
+
+/// Playable url type of a parse result, checking the playlist header for m3u8.
+async fn validate(url: &str, requestor: &Arc<impl Request>) -> Result<URIType, Error> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(Error::ParseError(format!(
+            "Parse result is not a http url: {}",
+            url
+        )));
+    }
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    if path.ends_with(".mp4") {
+        return Ok(URIType::MP4);
+    }
+    let playlist = requestor.request(url).await?;
+    if playlist.trim_start().starts_with("#EXTM3U") {
+        return Ok(URIType::M3U8);
+    }
+    Err(Error::ParseError(format!(
+        "Parse result is not a m3u8 or mp4: {}",
+        url
+    )))
+}
+
+/// Use the parse apis users configured, keyed by the built-in host of their site, instead of
+/// the built-in ones for every parser of the process.
+pub fn install(chains: HashMap<String, ParseChain>) {
+    let _ = PARSE_APIS.set(chains);
+}
+
+/// The installed parse apis of the site whose built-in host is `host`.
+pub fn installed(host: &str) -> Option<&'static ParseChain> {
+    PARSE_APIS.get().and_then(|chains| chains.get(host))
+}
+
+/// Parse apis tried in order until one returns a playable url.
+#[derive(Debug, Clone, Default)]
+pub struct ParseChain {
+    apis: Vec<ParseApi>,
+}
+
+impl ParseChain {
+    pub fn push(&mut self, api: ParseApi) {
+        self.apis.push(api);
+    }
+
+    pub fn accepts(&self, from: &str) -> bool {
+        self.apis.iter().any(|api| api.accepts(from))
+    }
+
+    /// Resolve `url` of the `from` source through the apis, relative api urls taken on `host`.
+    pub async fn resolve(
+        &self,
+        url: &str,
+        from: &str,
+        host: &str,
+        requestor: Arc<impl Request>,
+    ) -> Result<Uri, Error> {
+        let mut last_error = None;
+        for api in self.apis.iter().filter(|api| api.accepts(from)) {
+            let result = match api.call(url, from, host, &requestor).await {
+                Ok(response) => match api.extractor.extract(&response) {
+                    Some(video_url) => validate(&video_url, &requestor).await.map(|utype| Uri {
+                        uri: video_url,
+                        utype,
+                    }),
+                    None => Err(Error::ParseError(format!(
+                        "Parse api {} returned no url",
+                        api.name
+                    ))),
+                },
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(uri) => {
+                    info!("parse api {} resolved {} -> {}", api.name, url, uri.uri);
+                    return Ok(uri);
+                }
+                Err(e) => {
+                    warn!("parse api {} failed for {}: {}", api.name, url, e);
+                    last_error.replace(e);
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| Error::ParseError(format!("No parse api for source {}", from))))
+    }
+}
+
+#[test]
+fn test_extract_parse_result() {
+    let json = r#"{"code":200,"data":{"url":"https:\/\/cdn.example.com\/index.m3u8"}}"#;
+    assert_eq!(
+        Extractor::Auto.extract(json).as_deref(),
+        Some("https://cdn.example.com/index.m3u8")
+    );
+    assert_eq!(
+        Extractor::json("data.url").extract(json).as_deref(),
+        Some("https://cdn.example.com/index.m3u8")
+    );
+    assert_eq!(Extractor::json("url").extract(json), None);
+
+    let html =
+        r#"<script>var config = {"id":"1","url":"https://cdn.example.com/v.mp4?t=1"};</script>"#;
+    assert_eq!(
+        Extractor::Auto.extract(html).as_deref(),
+        Some("https://cdn.example.com/v.mp4?t=1")
+    );
+    let regex = Extractor::regex(r#""id":"(\d+)""#).unwrap();
+    assert_eq!(regex.extract(html).as_deref(), Some("1"));
+
+    assert_eq!(
+        ParseApi::fill(
+            "https://jx.example.com/?v={url}&f={from}",
+            "https://a.com/x?a=1",
+            "qq",
+            true
+        ),
+        "https://jx.example.com/?v=https%3A%2F%2Fa.com%2Fx%3Fa%3D1&f=qq"
+    );
+    let api =
+        ParseApi::new("jx", ParseMethod::Get, "https://jx.example.com/").from(&["qq", "youku"]);
+    assert!(api.accepts("qq"));
+    assert!(!api.accepts("ffm3u8"));
+}
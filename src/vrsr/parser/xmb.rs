@@ -1,13 +1,14 @@
 use super::super::error::Error;
-use super::super::{EpisodeInfo, ResourceInfo, TeleplayInfo, TeleplaySrc, URIType, Uri};
-use super::super::{EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
+use super::maccms::{self, MacPlayer};
+use super::super::{EpisodeInfo, ResourceInfo, TeleplayId, TeleplayInfo, TeleplaySrc, Uri};
+use super::super::{parse_id, EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
 use scraper::{Html, Selector};
-use serde_json::Value;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct XMBParser {
     info: ResourceInfo,
+    player: MacPlayer,
 }
 
 impl Default for XMBParser {
@@ -15,15 +16,24 @@ impl Default for XMBParser {
         Self {
             info: ResourceInfo {
                 name: "小目标".to_string(),
-                host: "https://tv.xmb.app/index.php".to_string(),
+                host: Self::HOST.to_string(),
+                mirrors: Vec::new(),
                 search_path: "index.php/vod/search.html".to_string(),
                 search_key: "wd".to_string(),
+                show_path: "index.php/vod/show/by/{by}/id/{id}/page/{page}.html".to_string(),
+                detail_path: "index.php/vod/detail/id/{id}.html".to_string(),
+                play_path: "index.php/vod/play/id/{id}/sid/{sid}/nid/{nid}.html".to_string(),
+                ad_patterns: maccms::feed_ad_patterns(),
             },
+            player: MacPlayer::new(),
         }
     }
 }
 
 impl XMBParser {
+    /// The built-in host, which identifies the site.
+    pub const HOST: &'static str = "https://tv.xmb.app/index.php";
+
     pub fn new() -> Arc<Self> {
         Arc::new(Self::default())
     }
@@ -34,11 +44,11 @@ impl GenerateInfo for XMBParser {
         self.info.clone()
     }
 
-    fn generate_teleplay_info(&self, id: u64) -> TeleplayInfo {
-        let mut host_url = url::Url::parse(&self.info.host).unwrap();
-        host_url.set_path(&format!("/index.php/vod/detail/id/{}.html", id));
+    fn generate_teleplay_info(&self, id: &TeleplayId) -> TeleplayInfo {
+        let mut host_url = url::Url::parse(self.info.host()).unwrap();
+        host_url.set_path(&self.info.detail_path.replace("{id}", id.as_str()));
         TeleplayInfo {
-            id,
+            id: id.clone(),
             home_page: host_url.to_string(),
             ..TeleplayInfo::default()
         }
@@ -73,26 +83,17 @@ impl ResourceParse for XMBParser {
             info.title = teleplay
                 .select(&title_selector)
                 .next()
-                .ok_or_else(|| Error::ParseError("Failed to find name".to_string()))?
+                .ok_or_else(|| Error::not_found("name", &title_selector))?
                 .inner_html();
             info.home_page = teleplay
                 .select(&home_page_selector)
                 .next()
-                .ok_or_else(|| Error::ParseError("Failed to find home page".to_string()))?
+                .ok_or_else(|| Error::not_found("home page", &home_page_selector))?
                 .value()
                 .attr("href")
-                .ok_or_else(|| Error::ParseError("Failed to find home page".to_string()))?
+                .ok_or_else(|| Error::attr_not_found("home page", &home_page_selector, "href"))?
                 .to_string();
-            info.id = info
-                .home_page
-                .split('/')
-                .last()
-                .unwrap()
-                .split('.')
-                .take(1)
-                .map(|v| v.parse::<u64>().unwrap())
-                .last()
-                .unwrap();
+            info.id = parse_id(&info.home_page)?;
 
             if let Some(cover) = teleplay.select(&cover_selector).next() {
                 if let Some(cover) = cover.value().attr("data-original") {
@@ -201,12 +202,12 @@ impl TeleplayParse for XMBParser {
             for url in urls {
                 let info = EpisodeInfo {
                     name: url.select(&name_selector).next()
-                        .ok_or_else(|| Error::ParseError("Failed to find episode name".to_string()))?
+                        .ok_or_else(|| Error::not_found("episode name", &name_selector))?
                         .inner_html().trim().to_string(),
                     url: url
                         .value()
                         .attr("href")
-                        .ok_or_else(|| Error::ParseError("Failed to find episode url".to_string()))?
+                        .ok_or_else(|| Error::attr_not_found("episode url", &uri_selector, "href"))?
                         .to_string(),
                 };
                 source.append_episode(info);
@@ -218,30 +219,35 @@ impl TeleplayParse for XMBParser {
 }
 
 impl EpisodeParse for XMBParser {
-    async fn parse(&self, html: &str, _org_rul: &str, _requestor: Arc<impl Request>) -> Result<Uri, Error> {
-        let html = Html::parse_document(html);
-        let m3u8_selector = Selector::parse("div.module-main div.player-box div.player-box-main script")?;
-        let m3u8_json = html
-            .select(&m3u8_selector)
-            .next()
-            .ok_or_else(|| Error::ParseError("Failed to find m3u8 json msg".to_string()))?
-            .inner_html()
-            .split("=")
-            .skip(1)
-            .collect::<Vec<_>>()
-            .join("")
-            .trim()
-            .to_string();
-        let msg: Value = serde_json::from_str(&m3u8_json)?;
-        Ok(Uri {
-            uri: msg["url"]
-                .as_str()
-                .ok_or_else(|| {
-                    Error::ParseError("Faild to find url string from json msg".to_string())
-                })?
-                .trim_matches('"')
-                .to_string(),
-            utype: URIType::M3U8,
-        })
+    async fn parse(&self, html: &str, org_rul: &str, requestor: Arc<impl Request>) -> Result<Uri, Error> {
+        self.player.resolve(html, org_rul, &self.info, requestor).await
     }
 }
+
+#[tokio::test()]
+async fn test_xmb_fixtures() {
+    use super::super::fixture::replay;
+    use super::super::URIType;
+    let replay = replay("xmb", XMBParser::new(), "繁花", 202).await.unwrap();
+    let result = &replay.results[0];
+    assert_eq!(replay.results.len(), 1);
+    assert_eq!(result.id.as_str(), "202");
+    assert_eq!(result.title, "繁花");
+    assert_eq!(result.status.as_deref(), Some("全30集"));
+    assert_eq!(result.region.as_deref(), Some("大陆"));
+    assert_eq!(result.starring.as_ref().map(|s| s.len()), Some(3));
+    assert_eq!(result.cover.as_deref(), Some("https://img.xmb.app/upload/vod/202.jpg"));
+
+    assert_eq!(replay.info.times.as_deref(), Some("2023"));
+    assert_eq!(replay.info.genre.as_deref(), Some("剧情"));
+    assert_eq!(replay.info.director, Some(vec!["王家卫".to_string()]));
+    assert_eq!(replay.info.starring.as_ref().map(|s| s.len()), Some(2));
+    assert_eq!(replay.info.update_time.as_deref(), Some("2024-01-09"));
+    assert_eq!(replay.info.introduction.as_deref(), Some("九十年代的上海，阿宝的故事"));
+    assert_eq!(replay.sources.len(), 1);
+    assert_eq!(replay.sources[0].0.as_deref(), Some("小目标"));
+    assert_eq!(replay.sources[0].1.len(), 3);
+
+    assert_eq!(replay.uri.uri, "https://cdn.example.com/fanhua/01.mp4");
+    assert!(matches!(replay.uri.utype, URIType::MP4));
+}
@@ -1,13 +1,14 @@
 use super::super::error::Error;
-use super::super::{EpisodeInfo, ResourceInfo, TeleplayInfo, TeleplaySrc, URIType, Uri};
-use super::super::{EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
+use super::maccms::{self, MacPlayer};
+use super::super::{EpisodeInfo, ResourceInfo, TeleplayId, TeleplayInfo, TeleplaySrc, Uri};
+use super::super::{parse_id, EpisodeParse, GenerateInfo, Request, ResourceParse, TeleplayParse};
 use scraper::{ElementRef, Html, Selector};
-use serde_json::Value;
 use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct ZBKYYYParser {
     info: ResourceInfo,
+    player: MacPlayer,
 }
 
 impl Default for ZBKYYYParser {
@@ -15,15 +16,26 @@ impl Default for ZBKYYYParser {
         Self {
             info: ResourceInfo {
                 name: "真不卡影院".to_string(),
-                host: "https://www.zbkyyy.com".to_string(),
+                host: Self::HOST.to_string(),
+                mirrors: Self::MIRRORS.iter().map(|host| host.to_string()).collect(),
                 search_path: "qyvodsearch/-------------.html".to_string(),
                 search_key: "wd".to_string(),
+                show_path: "qyvodshow/{id}--{by}------{page}---.html".to_string(),
+                detail_path: "qyvoddetail/{id}.html".to_string(),
+                play_path: "qyvodplay/{id}-{sid}-{nid}.html".to_string(),
+                ad_patterns: maccms::feed_ad_patterns(),
             },
+            player: MacPlayer::new(),
         }
     }
 }
 
 impl ZBKYYYParser {
+    /// The built-in host, which identifies the site.
+    pub const HOST: &'static str = "https://www.zbkyyy.com";
+    /// Other domains the site answers on, requests fail over to them.
+    const MIRRORS: [&'static str; 1] = ["https://zbkyyy.com"];
+
     pub fn new() -> Arc<Self> {
         Arc::new(Self::default())
     }
@@ -34,11 +46,11 @@ impl GenerateInfo for ZBKYYYParser {
         self.info.clone()
     }
 
-    fn generate_teleplay_info(&self, id: u64) -> TeleplayInfo {
-        let mut host_url = url::Url::parse(&self.info.host).unwrap();
-        host_url.set_path(&format!("qyvoddetail/{}.html", id));
+    fn generate_teleplay_info(&self, id: &TeleplayId) -> TeleplayInfo {
+        let mut host_url = url::Url::parse(self.info.host()).unwrap();
+        host_url.set_path(&self.info.detail_path.replace("{id}", id.as_str()));
         TeleplayInfo {
-            id,
+            id: id.clone(),
             home_page: host_url.to_string(),
             ..TeleplayInfo::default()
         }
@@ -74,26 +86,17 @@ impl ResourceParse for ZBKYYYParser {
             info.title = teleplay
                 .select(&title_selector)
                 .next()
-                .ok_or_else(|| Error::ParseError("Failed to find name".to_string()))?
+                .ok_or_else(|| Error::not_found("name", &title_selector))?
                 .inner_html();
             info.home_page = teleplay
                 .select(&home_page_selector)
                 .next()
-                .ok_or_else(|| Error::ParseError("Failed to find home page".to_string()))?
+                .ok_or_else(|| Error::not_found("home page", &home_page_selector))?
                 .value()
                 .attr("href")
-                .ok_or_else(|| Error::ParseError("Failed to find home page".to_string()))?
+                .ok_or_else(|| Error::attr_not_found("home page", &home_page_selector, "href"))?
                 .to_string();
-            info.id = info
-                .home_page
-                .split('/')
-                .last()
-                .unwrap()
-                .split('.')
-                .take(1)
-                .map(|v| v.parse::<u64>().unwrap())
-                .last()
-                .unwrap();
+            info.id = parse_id(&info.home_page)?;
             if let Some(score) = teleplay.select(&score_selector).next() {
                 info.score.replace(score.inner_html());
             }
@@ -207,7 +210,7 @@ impl TeleplayParse for ZBKYYYParser {
                     url: url
                         .value()
                         .attr("href")
-                        .ok_or_else(|| Error::ParseError("Failed to find episode url".to_string()))?
+                        .ok_or_else(|| Error::attr_not_found("episode url", &uri_selector, "href"))?
                         .to_string(),
                 };
                 source.append_episode(info);
@@ -219,30 +222,36 @@ impl TeleplayParse for ZBKYYYParser {
 }
 
 impl EpisodeParse for ZBKYYYParser {
-    async fn parse(&self, html: &str, _org_rul: &str, _requestor: Arc<impl Request>) -> Result<Uri, Error> {
-        let html = Html::parse_document(html);
-        let m3u8_selector = Selector::parse("div.iplays script")?;
-        let m3u8_json = html
-            .select(&m3u8_selector)
-            .next()
-            .ok_or_else(|| Error::ParseError("Failed to find m3u8 json msg".to_string()))?
-            .inner_html()
-            .split("=")
-            .skip(1)
-            .collect::<Vec<_>>()
-            .join("")
-            .trim()
-            .to_string();
-        let msg: Value = serde_json::from_str(&m3u8_json)?;
-        Ok(Uri {
-            uri: msg["url"]
-                .as_str()
-                .ok_or_else(|| {
-                    Error::ParseError("Faild to find url string from json msg".to_string())
-                })?
-                .trim_matches('"')
-                .to_string(),
-            utype: URIType::M3U8,
-        })
+    async fn parse(&self, html: &str, org_rul: &str, requestor: Arc<impl Request>) -> Result<Uri, Error> {
+        self.player.resolve(html, org_rul, &self.info, requestor).await
     }
 }
+
+#[tokio::test()]
+async fn test_zbkyyy_fixtures() {
+    use super::super::fixture::replay;
+    use super::super::URIType;
+    let replay = replay("zbkyyy", ZBKYYYParser::new(), "繁花", 101).await.unwrap();
+    let result = &replay.results[0];
+    assert_eq!(replay.results.len(), 1);
+    assert_eq!(result.id.as_str(), "101");
+    assert_eq!(result.title, "繁花");
+    assert_eq!(result.score.as_deref(), Some("8.7"));
+    assert_eq!(result.times.as_deref(), Some("2023"));
+    assert_eq!(result.language.as_deref(), Some("国语"));
+    assert_eq!(result.director, Some(vec!["王家卫".to_string()]));
+    assert_eq!(result.starring.as_ref().map(|s| s.len()), Some(2));
+    assert_eq!(result.cover.as_deref(), Some("https://www.zbkyyy.com/upload/vod/101.jpg"));
+
+    assert_eq!(replay.info.title, "繁花");
+    assert_eq!(replay.info.genre.as_deref(), Some("剧情"));
+    assert_eq!(replay.info.region.as_deref(), Some("大陆"));
+    assert_eq!(replay.info.update_time.as_deref(), Some("2024-01-09"));
+    assert_eq!(replay.info.plot.as_deref(), Some("九十年代的上海，阿宝的故事"));
+    assert_eq!(replay.sources.len(), 2);
+    assert_eq!(replay.sources[0].0.as_deref(), Some("非凡线路"));
+    assert_eq!(replay.sources[0].1[1], "https://www.zbkyyy.com/qyvodplay/101-1-2.html");
+
+    assert_eq!(replay.uri.uri, "https://cdn.example.com/fanhua/01/index.m3u8");
+    assert!(matches!(replay.uri.utype, URIType::M3U8));
+}
@@ -1,7 +1,10 @@
+use super::cookie::CookieJar;
 use super::error::Error;
+use super::mirror;
 use super::Request;
+use log::warn;
 use reqwest::header::{
-    HeaderMap, HeaderValue, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CONNECTION, CONTENT_TYPE,
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CONNECTION, CONTENT_TYPE,
 };
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -16,6 +19,8 @@ pub struct Requestor {
     try_count: u64,
     client: reqwest::Client,
     ignore_cache: bool,
+    /// Hosts of the site, a page that fails on one is requested from the others.
+    hosts: Vec<String>,
 }
 
 impl Requestor {
@@ -37,6 +42,23 @@ impl Requestor {
         }
     }
 
+    async fn base_request_bytes(&self, url: &str) -> Result<Vec<u8>, Error> {
+        let response = self
+            .client
+            .clone()
+            .get(url)
+            .headers(self.headers.clone())
+            .timeout(std::time::Duration::from_secs(self.timeout))
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.bytes().await?.to_vec())
+        } else {
+            Err(Error::ResponseFailed(response.status().as_u16()))
+        }
+    }
+
     fn get_cache_path(&self, url: &str) -> String {
         let hash_name = sha256::digest(url);
         std::path::Path::new(&self.cache_dir)
@@ -68,8 +90,14 @@ impl Requestor {
 
     async fn modifie_time(&self, path: &str) -> Option<Duration> {
         if std::path::Path::new(&path).exists() {
-            let metadata = std::fs::metadata(path).unwrap();
-            let modified_time = metadata.modified().unwrap();
+            // an unreadable cache file counts as missing and is requested again
+            let modified_time = match std::fs::metadata(path).and_then(|m| m.modified()) {
+                Ok(modified_time) => modified_time,
+                Err(e) => {
+                    println!("get modifie time error, path: {}, {}", path, e);
+                    return None;
+                }
+            };
             let current_time = std::time::SystemTime::now();
             let duration_since_modified = current_time.duration_since(modified_time);
             if let Ok(duration) = duration_since_modified {
@@ -82,8 +110,8 @@ impl Requestor {
     }
 }
 
-impl Request for Requestor {
-    async fn request(&self, url: &str) -> Result<String, Error> {
+impl Requestor {
+    async fn request_host(&self, url: &str) -> Result<String, Error> {
         let mut try_count = 0u64;
         while self.try_count == 0 || try_count < self.try_count {
             match self.base_request(url).await {
@@ -98,6 +126,31 @@ impl Request for Requestor {
         }
         Err(Error::RequestOutOfTry(try_count))
     }
+}
+
+impl Request for Requestor {
+    async fn request(&self, url: &str) -> Result<String, Error> {
+        let error = match self.request_host(url).await {
+            Ok(content) => return Ok(content),
+            // a missing page is missing on the mirrors too
+            Err(Error::ResponseFailed(404)) => return Err(Error::ResponseFailed(404)),
+            Err(e) => e,
+        };
+        for host in self.hosts.iter() {
+            let mirrored = mirror::rewrite_url(url, &self.hosts, host);
+            if mirrored == url {
+                continue;
+            }
+            match self.request_host(&mirrored).await {
+                Ok(content) => {
+                    warn!("{} failed: {}, served by {}", url, error, host);
+                    return Ok(content);
+                }
+                Err(e) => warn!("{} failed: {}", mirrored, e),
+            }
+        }
+        Err(error)
+    }
 
     async fn post_request(
         &self,
@@ -110,8 +163,34 @@ impl Request for Requestor {
                 .client
                 .clone()
                 .post(url)
-                .form(&form_data)
                 .headers(self.headers.clone())
+                // after the headers, which would replace its content type
+                .form(&form_data)
+                .timeout(std::time::Duration::from_secs(self.timeout))
+                .send()
+                .await?;
+            if response.status().is_success() {
+                let body = response.text().await?;
+                return Ok(body);
+            }
+            try_count += 1;
+        }
+        Err(Error::RequestOutOfTry(try_count))
+    }
+
+    async fn post_json_request(&self, url: &str, body: serde_json::Value) -> Result<String, Error> {
+        // replace the default text/html content type, adding one would send both
+        let mut headers = self.headers.clone();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let mut try_count = 0u64;
+        while self.try_count == 0 || try_count < self.try_count {
+            let response = self
+                .client
+                .clone()
+                .post(url)
+                .headers(headers.clone())
+                .body(body.to_string())
+                .timeout(std::time::Duration::from_secs(self.timeout))
                 .send()
                 .await?;
             if response.status().is_success() {
@@ -143,6 +222,40 @@ impl Request for Requestor {
         }
         Ok(content)
     }
+
+    async fn request_bytes_with_cache(
+        &self,
+        url: &str,
+        cache_time: Duration,
+    ) -> Result<Vec<u8>, Error> {
+        let cache_path = self.get_cache_path(url);
+        if !self.ignore_cache {
+            if let Some(time) = self.modifie_time(&cache_path).await {
+                if time < cache_time {
+                    if let Ok(cache) = tokio::fs::read(&cache_path).await {
+                        return Ok(cache);
+                    } else {
+                        println!("read cache error, request url: {}", url);
+                    }
+                }
+            }
+        }
+        let mut try_count = 0u64;
+        let content = loop {
+            if self.try_count != 0 && try_count >= self.try_count {
+                return Err(Error::RequestOutOfTry(try_count));
+            }
+            match self.base_request_bytes(url).await {
+                Ok(content) => break content,
+                Err(Error::ResponseFailed(status)) => return Err(Error::ResponseFailed(status)),
+                Err(_) => try_count += 1,
+            }
+        };
+        if let Err(e) = tokio::fs::write(&cache_path, &content).await {
+            println!("write cache error, url: {}, error: {}", url, e);
+        }
+        Ok(content)
+    }
 }
 
 pub struct RequestorBuilder {
@@ -150,8 +263,10 @@ pub struct RequestorBuilder {
     cache_dir: String,
     timeout: u64,
     try_count: u64,
-    client: reqwest::Client,
+    proxy: Option<reqwest::Proxy>,
+    cookie_jar: Option<Arc<CookieJar>>,
     ignore_cache: bool,
+    hosts: Vec<String>,
 }
 
 impl Default for RequestorBuilder {
@@ -171,17 +286,15 @@ impl Default for RequestorBuilder {
             HeaderValue::from_static("zh-CN,zh;q=0.8,en;q=0.6"),
         );
         headers.insert(CONNECTION, HeaderValue::from_static("keep-alive"));
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(true)
-            .build()
-            .unwrap();
         Self {
             headers,
             cache_dir: String::from(".cache"),
             timeout: 30,
             try_count: 3,
-            client,
+            proxy: None,
+            cookie_jar: None,
             ignore_cache: false,
+            hosts: Vec::new(),
         }
     }
 }
@@ -197,38 +310,97 @@ impl RequestorBuilder {
         self
     }
 
-    #[allow(unused)]
     pub fn cache_dir(&mut self, cache_dir: &str) -> &mut Self {
         self.cache_dir = cache_dir.to_string();
         self
     }
 
-    #[allow(unused)]
     pub fn timeout(&mut self, timeout: u64) -> &mut Self {
         self.timeout = timeout;
         self
     }
 
-    #[allow(unused)]
     pub fn try_count(&mut self, count: u64) -> &mut Self {
         self.try_count = count;
         self
     }
 
+    /// Send `value` as the `name` header, skipped when either is not valid in a header.
+    pub fn header(&mut self, name: &str, value: &str) -> &mut Self {
+        match (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            (Ok(name), Ok(value)) => {
+                self.headers.insert(name, value);
+            }
+            _ => warn!("skip invalid header {}: {}", name, value),
+        }
+        self
+    }
+
+    /// Send the requests through the proxy at `url`, e.g. `socks5://127.0.0.1:1080`.
+    pub fn proxy(&mut self, url: &str) -> Result<&mut Self, Error> {
+        self.proxy.replace(reqwest::Proxy::all(url)?);
+        Ok(self)
+    }
+
+    /// Send the cookies of `jar` and keep the ones the responses set in it.
+    pub fn cookie_jar(&mut self, jar: Arc<CookieJar>) -> &mut Self {
+        self.cookie_jar.replace(jar);
+        self
+    }
+
     pub fn ignore_cache(&mut self, ignore: bool) -> &mut Self {
         self.ignore_cache = ignore;
         self
     }
 
+    /// Fail page requests over to the other `hosts` of the site when the one in the url does
+    /// not answer.
+    pub fn hosts(&mut self, hosts: Vec<String>) -> &mut Self {
+        self.hosts = hosts;
+        self
+    }
+
     pub fn build(&self) -> Arc<Requestor> {
         std::fs::create_dir_all(&self.cache_dir).unwrap();
+        let mut client = reqwest::Client::builder().danger_accept_invalid_certs(true);
+        if let Some(proxy) = self.proxy.clone() {
+            client = client.proxy(proxy);
+        }
+        if let Some(jar) = self.cookie_jar.clone() {
+            client = client.cookie_provider(jar);
+        }
+        let client = client.build().unwrap();
         Arc::new(Requestor {
             headers: self.headers.clone(),
             cache_dir: self.cache_dir.clone(),
             timeout: self.timeout,
             try_count: self.try_count,
-            client: self.client.clone(),
+            client,
             ignore_cache: self.ignore_cache,
+            hosts: self.hosts.clone(),
         })
     }
 }
+
+#[tokio::test()]
+async fn test_request_failover() {
+    use crate::downloader::testserver::TestServer;
+
+    let server = TestServer::start().await;
+    server.route("/detail/1.html", "detail");
+    let dead = "http://127.0.0.1:1";
+    let cache_dir = std::env::temp_dir().join("vspider-failover");
+    let requestor = RequestorBuilder::new()
+        .cache_dir(cache_dir.to_str().unwrap())
+        .timeout(1)
+        .try_count(1)
+        .hosts(vec![dead.to_string(), server.url("")])
+        .build();
+    let content = requestor.request(&format!("{}/detail/1.html", dead)).await;
+    assert_eq!(content.unwrap(), "detail");
+    assert_eq!(server.hits("/detail/1.html"), 1);
+    // a missing page is not looked for on the other hosts
+    let missing = requestor.request(&server.url("/detail/2.html")).await;
+    assert!(matches!(missing, Err(Error::ResponseFailed(404))));
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
@@ -0,0 +1,360 @@
+use super::browser::BrowserPool;
+use super::error::Error;
+use super::{URIType, Uri};
+use headless_chrome::browser::tab::Tab;
+use headless_chrome::protocol::cdp::Network::events::ResponseReceivedEventParams;
+use log::{info, warn};
+use regex::Regex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+const HANDLER_NAME: &str = "vspider-sniffer";
+
+/// Decides which network responses are video candidates. Every criteria set on a rule must
+/// match; a response is a candidate when any rule matches.
+#[derive(Debug, Clone, Default)]
+pub struct SniffRule {
+    url: Option<Regex>,
+    resource_type: Option<String>,
+    content_type: Option<Regex>,
+}
+
+impl SniffRule {
+    pub fn url(pattern: &str) -> Result<Self, Error> {
+        Self::default().and_url(pattern)
+    }
+
+    pub fn resource_type(resource_type: &str) -> Self {
+        Self::default().and_resource_type(resource_type)
+    }
+
+    pub fn content_type(pattern: &str) -> Result<Self, Error> {
+        Self::default().and_content_type(pattern)
+    }
+
+    pub fn and_url(mut self, pattern: &str) -> Result<Self, Error> {
+        self.url.replace(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn and_resource_type(mut self, resource_type: &str) -> Self {
+        self.resource_type.replace(resource_type.to_lowercase());
+        self
+    }
+
+    pub fn and_content_type(mut self, pattern: &str) -> Result<Self, Error> {
+        self.content_type.replace(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    fn matches(&self, url: &str, resource_type: &str, content_type: &str) -> bool {
+        self.url.as_ref().is_none_or(|regex| regex.is_match(url))
+            && self
+                .resource_type
+                .as_ref()
+                .is_none_or(|expected| expected == resource_type)
+            && self
+                .content_type
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(content_type))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub uri: Uri,
+    pub resource_type: String,
+    pub content_type: String,
+    pub master: bool,
+    pub duration: Option<f32>,
+    pub score: i32,
+}
+
+impl std::fmt::Display for Candidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:>4}] {:?} {} ({}, {})",
+            self.score, self.uri.utype, self.uri.uri, self.resource_type, self.content_type
+        )?;
+        if self.master {
+            write!(f, " master")?;
+        }
+        if let Some(duration) = self.duration {
+            write!(f, " {:.1}s", duration)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the video of a page by opening it in a pooled browser tab and watching the network
+/// responses, ranking the captured candidates so real content wins over ads and previews.
+#[derive(Debug, Clone)]
+pub struct Sniffer {
+    rules: Vec<SniffRule>,
+    ad_pattern: Regex,
+    max_candidates: usize,
+    settle: Duration,
+}
+
+impl Default for Sniffer {
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                SniffRule::url(r"\.m3u8(\?|$)").unwrap(),
+                SniffRule::content_type(r"(?i)mpegurl").unwrap(),
+                SniffRule::resource_type("media"),
+                SniffRule::content_type(r"(?i)^video/").unwrap(),
+            ],
+            ad_pattern: Regex::new(r"(?i)([/_.=-]|^)(ad|ads|adv|advert|guanggao)([/_.=-]|$)")
+                .unwrap(),
+            max_candidates: 10,
+            settle: Duration::from_secs(3),
+        }
+    }
+}
+
+impl Sniffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the default rules.
+    #[allow(unused)]
+    pub fn rules(mut self, rules: Vec<SniffRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    #[allow(unused)]
+    pub fn max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates.max(1);
+        self
+    }
+
+    /// How long to keep collecting after the first candidate.
+    #[allow(unused)]
+    pub fn settle(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    fn candidate(
+        rules: &[SniffRule],
+        params: &ResponseReceivedEventParams,
+        body: impl Fn() -> Option<String>,
+    ) -> Option<Candidate> {
+        let url = params.response.url.as_str();
+        if !url.starts_with("http") {
+            return None;
+        }
+        let resource_type = format!("{:?}", params.Type).to_lowercase();
+        let content_type = params.response.mime_type.to_lowercase();
+        if !rules
+            .iter()
+            .any(|rule| rule.matches(url, &resource_type, &content_type))
+        {
+            return None;
+        }
+        let utype = if url.contains(".m3u8") || content_type.contains("mpegurl") {
+            URIType::M3U8
+        } else if resource_type == "media" || content_type.starts_with("video/") {
+            URIType::MP4
+        } else {
+            URIType::UNKNOWN
+        };
+        let (mut master, mut duration) = (false, None);
+        if matches!(utype, URIType::M3U8) {
+            if let Some(body) = body() {
+                match m3u8_rs::parse_playlist_res(body.as_bytes()) {
+                    Ok(m3u8_rs::Playlist::MasterPlaylist(_)) => master = true,
+                    Ok(m3u8_rs::Playlist::MediaPlaylist(playlist)) => {
+                        duration.replace(playlist.segments.iter().map(|s| s.duration).sum());
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+        Some(Candidate {
+            uri: Uri {
+                uri: url.to_string(),
+                utype,
+            },
+            resource_type,
+            content_type,
+            master,
+            duration,
+            score: 0,
+        })
+    }
+
+    fn score(&self, candidate: &Candidate) -> i32 {
+        let mut score = match candidate.uri.utype {
+            URIType::M3U8 if candidate.master => 30,
+            URIType::M3U8 => 20,
+            URIType::MP4 => 10,
+            URIType::UNKNOWN => 0,
+        };
+        if self.ad_pattern.is_match(&candidate.uri.uri) {
+            score -= 50;
+        }
+        // previews and ads are short, episodes are not
+        if candidate.duration.is_some_and(|duration| duration < 60.0) {
+            score -= 30;
+        }
+        score
+    }
+
+    /// Open `url`, run `interact` to start the player and collect every matching response,
+    /// best candidate first.
+    pub async fn sniff<F>(
+        &self,
+        pool: &BrowserPool,
+        url: &str,
+        interact: F,
+    ) -> Result<Vec<Candidate>, Error>
+    where
+        F: FnOnce(&Tab) -> Result<(), Error> + Send + 'static,
+    {
+        let pooled = pool.borrow_tab().await?;
+        let tab = pooled.tab();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let rules = self.rules.clone();
+        tab.register_response_handling(
+            HANDLER_NAME,
+            Box::new(move |params, fetch_body| {
+                let body = || {
+                    fetch_body()
+                        .ok()
+                        .filter(|body| !body.base_64_encoded)
+                        .map(|body| body.body)
+                };
+                if let Some(candidate) = Self::candidate(&rules, &params, body) {
+                    let _ = sender.send(candidate);
+                }
+            }),
+        )
+        .map_err(|_| Error::BrowserError)?;
+
+        let page = url.to_string();
+        let mut interaction = Some(tokio::task::spawn_blocking(move || -> Result<(), Error> {
+            tab.navigate_to(&page).map_err(|_| Error::BrowserError)?;
+            interact(&tab)
+        }));
+        let deadline = Instant::now() + pool.timeout();
+        let mut settle_deadline: Option<Instant> = None;
+        let mut candidates: Vec<Candidate> = Vec::new();
+        loop {
+            let until = settle_deadline.map_or(deadline, |settle| settle.min(deadline));
+            tokio::select! {
+                candidate = receiver.recv() => {
+                    let Some(mut candidate) = candidate else {
+                        break;
+                    };
+                    if candidates.iter().any(|c| c.uri.uri == candidate.uri.uri) {
+                        continue;
+                    }
+                    info!("sniff candidate {}", candidate.uri.uri);
+                    candidate.score = self.score(&candidate);
+                    candidates.push(candidate);
+                    if candidates.len() >= self.max_candidates {
+                        break;
+                    }
+                    settle_deadline.get_or_insert(Instant::now() + self.settle);
+                }
+                result = async { interaction.as_mut().unwrap().await }, if interaction.is_some() => {
+                    interaction = None;
+                    let result = result.map_err(|_| Error::BrowserError).and_then(|r| r);
+                    if let Err(e) = result {
+                        if candidates.is_empty() {
+                            let _ = pooled.tab().deregister_response_handling(HANDLER_NAME);
+                            return Err(e);
+                        }
+                        warn!("sniff interaction failed after capture: {}", e);
+                    }
+                }
+                _ = tokio::time::sleep_until(until) => break,
+            }
+        }
+        let _ = pooled.tab().deregister_response_handling(HANDLER_NAME);
+        if candidates.is_empty() {
+            return Err(Error::BrowserTimeout(url.to_string()));
+        }
+        candidates.sort_by_key(|candidate| -candidate.score);
+        Ok(candidates)
+    }
+
+    /// The best candidate of [`Sniffer::sniff`].
+    pub async fn resolve<F>(&self, pool: &BrowserPool, url: &str, interact: F) -> Result<Uri, Error>
+    where
+        F: FnOnce(&Tab) -> Result<(), Error> + Send + 'static,
+    {
+        let candidates = self.sniff(pool, url, interact).await?;
+        Ok(candidates[0].uri.clone())
+    }
+}
+
+#[test]
+fn test_rank_candidates() {
+    let rule = SniffRule::url(r"\.m3u8").unwrap().and_resource_type("XHR");
+    assert!(rule.matches("https://a.com/index.m3u8", "xhr", ""));
+    assert!(!rule.matches("https://a.com/index.m3u8", "media", ""));
+    assert!(!rule.matches("https://a.com/index.mp4", "xhr", ""));
+
+    let sniffer = Sniffer::new();
+    let candidate = |uri: &str, utype: URIType, master: bool, duration: Option<f32>| Candidate {
+        uri: Uri {
+            uri: uri.to_string(),
+            utype,
+        },
+        resource_type: String::new(),
+        content_type: String::new(),
+        master,
+        duration,
+        score: 0,
+    };
+    let mut candidates = [
+        candidate(
+            "https://ad.example.com/ads/index.m3u8",
+            URIType::M3U8,
+            false,
+            Some(1200.0),
+        ),
+        candidate(
+            "https://cdn.example.com/preview.m3u8",
+            URIType::M3U8,
+            false,
+            Some(30.0),
+        ),
+        candidate(
+            "https://cdn.example.com/video.mp4",
+            URIType::MP4,
+            false,
+            None,
+        ),
+        candidate(
+            "https://cdn.example.com/index.m3u8",
+            URIType::M3U8,
+            true,
+            None,
+        ),
+    ];
+    for candidate in candidates.iter_mut() {
+        candidate.score = sniffer.score(candidate);
+    }
+    candidates.sort_by_key(|candidate| -candidate.score);
+    let order = candidates
+        .iter()
+        .map(|candidate| candidate.uri.uri.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        [
+            "https://cdn.example.com/index.m3u8",
+            "https://cdn.example.com/video.mp4",
+            "https://cdn.example.com/preview.m3u8",
+            "https://ad.example.com/ads/index.m3u8",
+        ]
+    );
+}